@@ -10,7 +10,7 @@ use smallpaint::terminator::DepthTerminator;
 use smallpaint::tracer::SimpleTracer;
 
 fn build_vec_storage(item_count: usize) -> Vec<SceneObject> {
-    const BASE_EMISSION: f64 = 0.;
+    const BASE_EMISSION: nalgebra_glm::DVec3 = nalgebra_glm::DVec3::new(0., 0., 0.);
     let mut v = vec![];// Room walls
     v.insert_object(
         SceneObject::new_plane(
@@ -1,8 +1,26 @@
 use crate::common::RandomGen;
 
+/// Maps the pixels of the image to the directions of rays leaving the camera, at the origin of the scene
 pub trait Camera: std::marker::Sync {
     fn view(&self, x: f64, y: f64) -> nalgebra_glm::DVec3;
     fn view_with_filtering(&self, x: f64, y: f64) -> nalgebra_glm::DVec3;
+
+    /// Pixel whose ray leaves the camera towards `direction`, the inverse of `view`
+    ///
+    /// Each pixel covers the directions up to half a pixel away from its ray. Returns `None` outside of the image,
+    /// or if the camera can't be reached by tracers that follow the light.
+    fn pixel(&self, _direction: &nalgebra_glm::DVec3) -> Option<(usize, usize)> {
+        None
+    }
+
+    /// Importance of the camera towards `direction`, `0` outside of the image
+    ///
+    /// It is the solid angle density of the rays of the whole image, so light arriving at the camera from
+    /// `direction`, after travelling a distance `d` from a surface, adds `light * cos * importance / d²` to the
+    /// pixel, where `cos` is the cosine at the surface.
+    fn importance(&self, _direction: &nalgebra_glm::DVec3) -> f64 {
+        0.
+    }
 }
 
 pub struct SimpleCamera(f64, f64);
@@ -11,14 +29,20 @@ impl SimpleCamera {
     pub fn new(width: f64, height: f64) -> Self {
         SimpleCamera(width, height)
     }
+
+    /// Tangents of the horizontal and vertical fields of view
+    fn tangents(&self) -> (f64, f64) {
+        let fovx = std::f64::consts::PI / 4.;
+        let fovy = (self.1 / self.0) * fovx;
+        (fovx.tan(), fovy.tan())
+    }
 }
 
 impl Camera for SimpleCamera {
     fn view(&self, pixelx: f64, pixely: f64) -> nalgebra_glm::DVec3 {
-        let fovx = std::f64::consts::PI / 4.;
-        let fovy = (self.1 / self.0) * fovx;
-        let vecx = ((2. * pixelx - self.0) / self.0) * fovx.tan();
-        let vecy = ((2. * pixely - self.1) / self.1) * fovy.tan();
+        let (tan_x, tan_y) = self.tangents();
+        let vecx = ((2. * pixelx - self.0) / self.0) * tan_x;
+        let vecy = ((2. * pixely - self.1) / self.1) * tan_y;
         nalgebra_glm::DVec3::new(
             vecx,
             vecy,
@@ -32,6 +56,28 @@ impl Camera for SimpleCamera {
         v.y += RandomGen::rand() / 700.;
         v
     }
+
+    fn pixel(&self, direction: &nalgebra_glm::DVec3) -> Option<(usize, usize)> {
+        if direction.z >= 0. {
+            return None;
+        }
+        let (tan_x, tan_y) = self.tangents();
+        let pixelx = ((direction.x / -direction.z) / tan_x * self.0 + self.0) / 2.;
+        let pixely = ((direction.y / -direction.z) / tan_y * self.1 + self.1) / 2.;
+        let (x, y) = (pixelx.round(), pixely.round());
+        (x >= 0. && x < self.0 && y >= 0. && y < self.1).then_some((x as usize, y as usize))
+    }
+
+    fn importance(&self, direction: &nalgebra_glm::DVec3) -> f64 {
+        if self.pixel(direction).is_none() {
+            return 0.;
+        }
+        // Directions are spread uniformly over the image plane at distance 1
+        let (tan_x, tan_y) = self.tangents();
+        let area = 4. * tan_x * tan_y;
+        let cos = -direction.z / direction.magnitude();
+        1. / (area * cos.powi(3))
+    }
 }
 
 #[cfg(test)]
@@ -601,4 +647,38 @@ mod test {
             }
         );
     }
+
+    #[test]
+    fn pixel_inverts_view() {
+        let sc = SimpleCamera::new(64., 32.);
+        for (x, y) in [(0, 0), (63, 31), (10, 20), (40, 5)] {
+            assert_eq!(sc.pixel(&sc.view(x as f64, y as f64)), Some((x, y)));
+            assert_eq!(sc.pixel(&sc.view(x as f64 + 0.4, y as f64 - 0.4)), Some((x, y)));
+        }
+        assert_eq!(sc.pixel(&sc.view(-0.6, 3.)), None);
+        assert_eq!(sc.pixel(&nalgebra_glm::DVec3::new(0., 0., 1.)), None);
+    }
+
+    #[test]
+    fn importance_integrates_to_one() {
+        let sc = SimpleCamera::new(16., 16.);
+        // Sums the importance over the solid angle of each pixel, on a finer grid
+        const STEPS: usize = 8;
+        let (tan_x, tan_y) = sc.tangents();
+        let cell = (2. * tan_x / 16. / STEPS as f64) * (2. * tan_y / 16. / STEPS as f64);
+        let total = (0..16 * STEPS)
+            .flat_map(|i| (0..16 * STEPS).map(move |j| (i, j)))
+            .map(
+                |(i, j)| {
+                    let x = (i as f64 + 0.5) / STEPS as f64 - 0.5;
+                    let y = (j as f64 + 0.5) / STEPS as f64 - 0.5;
+                    let direction = sc.view(x, y);
+                    let cos = 1. / direction.magnitude();
+                    // Area on the plane at distance 1 seen as a solid angle
+                    sc.importance(&direction) * cell * cos.powi(3)
+                }
+            )
+            .sum::<f64>();
+        approx::assert_relative_eq!(total, 1., max_relative = 1e-9);
+    }
 }
\ No newline at end of file
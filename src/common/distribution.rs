@@ -0,0 +1,161 @@
+/// Piecewise constant distribution over `[0, 1)`
+///
+/// Each value of the function covers an interval of the same width, and is sampled proportionally to its value.
+/// Functions that are zero everywhere are sampled uniformly.
+#[derive(Debug, Clone)]
+pub struct Distribution1D {
+    function: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64
+}
+
+impl Distribution1D {
+    /// Creates a distribution from non-negative values, negative values are treated as zero
+    pub fn new(function: &[f64]) -> Self {
+        let count = function.len().max(1);
+        let function = if function.is_empty() {
+            vec![0.]
+        } else {
+            function.iter().map(|value| value.max(0.)).collect()
+        };
+        let mut cdf = Vec::with_capacity(count + 1);
+        cdf.push(0.);
+        for value in &function {
+            cdf.push(cdf.last().unwrap() + value / count as f64);
+        }
+        let integral = cdf[count];
+        for (i, cdf) in cdf.iter_mut().enumerate() {
+            *cdf = if integral > 0. {
+                *cdf / integral
+            } else {
+                i as f64 / count as f64
+            };
+        }
+        Self {
+            function,
+            cdf,
+            integral
+        }
+    }
+
+    /// Integral of the function over `[0, 1)`
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    /// Number of intervals of the function
+    pub fn count(&self) -> usize {
+        self.function.len()
+    }
+
+    /// Maps `u` to a point of the distribution
+    ///
+    /// Returns the point, its density, and the index of the interval it's in.
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        let u = u.clamp(0., 1.);
+        // Last interval whose cdf starts at or before `u`, skipping empty intervals
+        let index = (self.cdf.partition_point(|cdf| *cdf <= u).max(1) - 1).min(self.count() - 1);
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0. {
+            (u - self.cdf[index]) / width
+        } else {
+            0.
+        };
+        let x = ((index as f64 + offset) / self.count() as f64).min(1. - f64::EPSILON);
+        (x, self.pdf_index(index), index)
+    }
+
+    /// Density of sampling `x`
+    pub fn pdf(&self, x: f64) -> f64 {
+        let index = ((x * self.count() as f64) as usize).min(self.count() - 1);
+        self.pdf_index(index)
+    }
+
+    fn pdf_index(&self, index: usize) -> f64 {
+        if self.integral > 0. {
+            self.function[index] / self.integral
+        } else {
+            1.
+        }
+    }
+}
+
+/// Piecewise constant distribution over `[0, 1)²`
+///
+/// Picks a row with the marginal distribution, then a column with the distribution of the row.
+#[derive(Debug, Clone)]
+pub struct Distribution2D {
+    conditionals: Vec<Distribution1D>,
+    marginal: Distribution1D
+}
+
+impl Distribution2D {
+    /// Creates a distribution from a grid of non-negative values, stored row by row
+    ///
+    /// Rows follow the second coordinate, and columns the first.
+    pub fn new(function: &[f64], width: usize, height: usize) -> Self {
+        let conditionals = function.chunks(width.max(1))
+            .take(height)
+            .map(Distribution1D::new)
+            .collect::<Vec<_>>();
+        let marginal = Distribution1D::new(&conditionals.iter().map(Distribution1D::integral).collect::<Vec<_>>());
+        Self {
+            conditionals,
+            marginal
+        }
+    }
+
+    /// Maps `u` to a point of the distribution, returns the point and its density
+    pub fn sample(&self, u: &nalgebra_glm::DVec2) -> (nalgebra_glm::DVec2, f64) {
+        let (y, pdf_y, row) = self.marginal.sample(u.y);
+        let (x, pdf_x, _) = self.conditionals[row].sample(u.x);
+        (nalgebra_glm::DVec2::new(x, y), pdf_x * pdf_y)
+    }
+
+    /// Density of sampling `point`
+    pub fn pdf(&self, point: &nalgebra_glm::DVec2) -> f64 {
+        let row = ((point.y * self.marginal.count() as f64).max(0.) as usize).min(self.marginal.count() - 1);
+        self.marginal.pdf_index(row) * self.conditionals[row].pdf(point.x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distribution_1d() {
+        let distribution = Distribution1D::new(&[1., 0., 3.]);
+        approx::assert_relative_eq!(distribution.integral(), 4. / 3.);
+        let (x, pdf, index) = distribution.sample(0.1);
+        assert_eq!(index, 0);
+        approx::assert_relative_eq!(x, 0.4 / 3.);
+        approx::assert_relative_eq!(pdf, 0.75);
+        // The empty interval is never sampled
+        let (x, pdf, index) = distribution.sample(0.25);
+        assert_eq!(index, 2);
+        approx::assert_relative_eq!(x, 2. / 3.);
+        approx::assert_relative_eq!(pdf, 2.25);
+        approx::assert_relative_eq!(distribution.pdf(x), pdf);
+        approx::assert_relative_eq!(distribution.pdf(0.5), 0.);
+
+        let uniform = Distribution1D::new(&[0., 0.]);
+        let (x, pdf, _) = uniform.sample(0.75);
+        approx::assert_relative_eq!(x, 0.75);
+        approx::assert_relative_eq!(pdf, 1.);
+    }
+
+    #[test]
+    fn distribution_2d() {
+        let distribution = Distribution2D::new(&[1., 1., 0., 2.], 2, 2);
+        for u in [(0.1, 0.1), (0.9, 0.3), (0.5, 0.7), (0.99, 0.99)] {
+            let (point, pdf) = distribution.sample(&nalgebra_glm::DVec2::new(u.0, u.1));
+            assert!((0. ..1.).contains(&point.x) && (0. ..1.).contains(&point.y));
+            approx::assert_relative_eq!(pdf, distribution.pdf(&point));
+        }
+        // Density is the value over the mean of the function
+        approx::assert_relative_eq!(distribution.pdf(&nalgebra_glm::DVec2::new(0.75, 0.75)), 2. / 1.);
+        approx::assert_relative_eq!(distribution.pdf(&nalgebra_glm::DVec2::new(0.25, 0.25)), 1.);
+        approx::assert_relative_eq!(distribution.pdf(&nalgebra_glm::DVec2::new(0.25, 0.75)), 0.);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,75 @@
+/// Orthonormal basis used to move directions in and out of a surface's local space
+///
+/// In local space the normal is `+z`, the tangent is `+x`, and the bitangent is `+y`.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    tangent: nalgebra_glm::DVec3,
+    bitangent: nalgebra_glm::DVec3,
+    normal: nalgebra_glm::DVec3
+}
+
+impl Frame {
+    /// Creates a frame from a normal and a tangent, the tangent is made orthogonal to the normal
+    pub fn new(normal: nalgebra_glm::DVec3, tangent: nalgebra_glm::DVec3) -> Self {
+        let normal = normal.normalize();
+        let tangent = (tangent - normal * normal.dot(&tangent)).normalize();
+        Self {
+            bitangent: normal.cross(&tangent),
+            tangent,
+            normal
+        }
+    }
+
+    /// Rotates the tangent and bitangent around the normal
+    pub fn rotated(&self, angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self {
+            tangent: self.tangent * cos + self.bitangent * sin,
+            bitangent: self.bitangent * cos - self.tangent * sin,
+            normal: self.normal
+        }
+    }
+
+    pub fn tangent(&self) -> &nalgebra_glm::DVec3 {
+        &self.tangent
+    }
+
+    pub fn bitangent(&self) -> &nalgebra_glm::DVec3 {
+        &self.bitangent
+    }
+
+    pub fn normal(&self) -> &nalgebra_glm::DVec3 {
+        &self.normal
+    }
+
+    pub fn to_local(&self, v: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec3 {
+        nalgebra_glm::DVec3::new(
+            v.dot(&self.tangent),
+            v.dot(&self.bitangent),
+            v.dot(&self.normal)
+        )
+    }
+
+    pub fn to_world(&self, v: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec3 {
+        self.tangent * v.x + self.bitangent * v.y + self.normal * v.z
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let frame = Frame::new(
+            nalgebra_glm::DVec3::new(1., 1., 0.),
+            nalgebra_glm::DVec3::new(0., 1., 1.)
+        ).rotated(0.3);
+        approx::assert_abs_diff_eq!(frame.tangent().dot(frame.normal()), 0., epsilon = 1e-12);
+        approx::assert_abs_diff_eq!(frame.bitangent().dot(frame.normal()), 0., epsilon = 1e-12);
+        approx::assert_abs_diff_eq!(frame.tangent().dot(frame.bitangent()), 0., epsilon = 1e-12);
+        let v = nalgebra_glm::DVec3::new(0.2, -0.5, 0.7);
+        approx::assert_abs_diff_eq!(frame.to_world(&frame.to_local(&v)), v, epsilon = 1e-12);
+        approx::assert_abs_diff_eq!(frame.to_local(frame.normal()), nalgebra_glm::DVec3::new(0., 0., 1.), epsilon = 1e-12);
+    }
+}
\ No newline at end of file
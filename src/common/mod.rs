@@ -1,15 +1,70 @@
 mod ray;
 pub use ray::Ray;
 
+mod frame;
+pub use frame::Frame;
+
+mod distribution;
+pub use distribution::{Distribution1D, Distribution2D};
+
+/// Source of the numbers of `RandomGen` while replaying, see `RandomGen::replay`
+pub trait RandomStream: std::any::Any {
+    /// Next number of the stream, in `[0, 1)`
+    fn next_sample(&mut self) -> f64;
+}
+
+thread_local! {
+    static STREAM: std::cell::RefCell<Option<Box<dyn RandomStream>>> = const { std::cell::RefCell::new(None) };
+}
+
+/// Puts back the stream that was replaced by `RandomGen::replay`, even if the replay panics
+struct ReplayGuard(Option<Option<Box<dyn RandomStream>>>);
+
+impl ReplayGuard {
+    /// Puts back the previous stream and returns the one of the replay
+    fn finish(mut self) -> Option<Box<dyn RandomStream>> {
+        STREAM.replace(self.0.take().expect("The replay is only finished once"))
+    }
+}
+
+impl Drop for ReplayGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.0.take() {
+            STREAM.set(previous);
+        }
+    }
+}
+
 pub struct RandomGen;
 
 impl RandomGen {
     pub fn rand() -> f64 {
-        2. * rand::random::<f64>() - 1.
+        2. * Self::rand2() - 1.
     }
 
     pub fn rand2() -> f64 {
-        rand::random::<f64>()
+        STREAM.with_borrow_mut(|stream| stream.as_mut().map(|stream| stream.next_sample()))
+            .unwrap_or_else(rand::random::<f64>)
+    }
+
+    /// Runs `f` with the numbers of `RandomGen` on the current thread taken from `stream`
+    ///
+    /// Running `f` again with a stream that returns the same numbers gives the same result, as long as `f` doesn't
+    /// spread its work to other threads. Returns the result of `f` and the stream.
+    pub fn replay<S: RandomStream, R>(stream: S, f: impl FnOnce() -> R) -> (R, S) {
+        let guard = ReplayGuard(Some(STREAM.replace(Some(Box::new(stream)))));
+        let result = f();
+        let stream: Box<dyn std::any::Any> = guard.finish()
+            .expect("Stream is set until the end of the replay");
+        match stream.downcast::<S>() {
+            Ok(stream) => (result, *stream),
+            Err(_) => unreachable!("Stream was replaced during the replay")
+        }
+    }
+
+    /// If the numbers on the current thread come from a stream, see `RandomGen::replay`
+    pub fn is_replaying() -> bool {
+        STREAM.with_borrow(Option::is_some)
     }
 }
 
@@ -21,7 +76,7 @@ mod test {
     fn rand_within_range() {
         for _ in 0..100_000_000 {
             let r = RandomGen::rand();
-            assert!(r >= -1. && r <= 1., "rand() = {}", r);
+            assert!((-1. ..=1.).contains(&r), "rand() = {}", r);
         }
     }
 
@@ -29,7 +84,43 @@ mod test {
     fn rand2_within_range() {
         for _ in 0..100_000_000 {
             let r = RandomGen::rand2();
-            assert!(r >= 0. && r <= 1., "rand2() = {}", r);
+            assert!((0. ..=1.).contains(&r), "rand2() = {}", r);
+        }
+    }
+
+    #[test]
+    fn replay_stream() {
+        struct Counter(f64);
+
+        impl RandomStream for Counter {
+            fn next_sample(&mut self) -> f64 {
+                self.0 += 0.25;
+                self.0
+            }
         }
+
+        assert!(!RandomGen::is_replaying());
+        let (numbers, counter) = RandomGen::replay(Counter(0.), || {
+            assert!(RandomGen::is_replaying());
+            [RandomGen::rand2(), RandomGen::rand(), RandomGen::rand2()]
+        });
+        assert_eq!(numbers, [0.25, 0., 0.75]);
+        approx::assert_relative_eq!(counter.0, 0.75);
+        assert!(!RandomGen::is_replaying());
+    }
+
+    #[test]
+    fn replay_restored_after_panic() {
+        struct Constant;
+
+        impl RandomStream for Constant {
+            fn next_sample(&mut self) -> f64 {
+                0.5
+            }
+        }
+
+        let result = std::panic::catch_unwind(|| RandomGen::replay(Constant, || panic!("Replay failed")));
+        assert!(result.is_err());
+        assert!(!RandomGen::is_replaying());
     }
 }
\ No newline at end of file
@@ -7,6 +7,50 @@ pub struct RenderParams {
     pub samples_per_pixel: u64,
 }
 
+/// Light that tracers add to arbitrary pixels during a pass, like paths that start on the lights and reach the camera
+///
+/// Splats are added to the pass after every pixel was traced, and are averaged over the passes like the pixels.
+/// Each thread of the pool keeps its own list of splats, so threads don't wait on each other while tracing.
+pub struct Splats {
+    width: usize,
+    height: usize,
+    buffers: Vec<std::sync::Mutex<Vec<(usize, nalgebra_glm::DVec3)>>>
+}
+
+impl Splats {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            // One more for the threads outside of the pool
+            buffers: (0..=rayon::current_num_threads()).map(|_| std::sync::Mutex::new(vec![])).collect()
+        }
+    }
+
+    /// Adds light to the pixel at `x` and `y`
+    pub fn add(&self, x: usize, y: usize, color: nalgebra_glm::DVec3) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let last = self.buffers.len() - 1;
+        let buffer = rayon::current_thread_index().map_or(last, |index| index.min(last));
+        if let Ok(mut buffer) = self.buffers[buffer].lock() {
+            buffer.push((x * self.height + y, color));
+        }
+    }
+
+    /// Light added to each pixel, in the same order as the pixels of the `Renderer`
+    pub fn into_image(self) -> Vec<nalgebra_glm::DVec3> {
+        let mut image = vec![nalgebra_glm::zero(); self.width * self.height];
+        for buffer in self.buffers {
+            for (index, color) in buffer.into_inner().unwrap_or_default() {
+                image[index] += color;
+            }
+        }
+        image
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum RendererStatus {
     Blank,
@@ -84,6 +128,9 @@ impl Renderer {
         camera: &dyn Camera,
         scene: &Scene
     ) -> Vec<nalgebra_glm::DVec3> {
+        let sample = self.current_sample.lock().map_or(0, |sample| *sample);
+        tracer.begin_pass(scene, camera, &self.render_params, (self.width, self.height), sample);
+        let splats = Splats::new(self.width, self.height);
         // Initialy the values in `pass` will be in order of conclusion
         // so map includes the index of the pixel
         let mut pass = (0..(self.width * self.height)).par_bridge()
@@ -95,15 +142,16 @@ impl Renderer {
                         nalgebra_glm::zero(),
                         camera.view_with_filtering(x as f64, y as f64).normalize()
                     );
-                    (i, tracer.trace(ray, scene, &self.render_params, 0))
+                    (i, tracer.trace_pixel(ray, scene, &self.render_params, camera, (x, y), &splats))
                 }
             )
             .collect::<Vec<_>>();
         // Sort by index
-        pass.sort_by(|(a, _), (b, _)| a.cmp(b));
+        pass.sort_by_key(|(a, _)| *a);
         pass.into_iter()
+            .zip(splats.into_image())
             .map(
-                |(_, c)| c
+                |((_, c), splat)| c + splat
             )
             .collect()
     }
@@ -184,11 +232,32 @@ impl Renderer {
     pub fn get_image(&self) -> (Vec<nalgebra_glm::DVec3>, u64) {
         (
             self.image.lock().map_or(vec![], |r| r.clone()),
-            self.current_sample.lock().map_or(0, |r| r.clone())
+            self.current_sample.lock().map_or(0, |r| *r)
         )
     }
 
     pub fn renderer_status(&self) -> RendererStatus {
         self.renderer_status.lock().map_or(RendererStatus::Errored, |r| r.clone())
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::prelude::IntoParallelIterator;
+
+    use super::*;
+
+    #[test]
+    fn splats_from_threads() {
+        let splats = Splats::new(3, 2);
+        (0..600).into_par_iter().for_each(|i| splats.add(i % 3, i % 2, nalgebra_glm::DVec3::from_element(1.)));
+        // Outside of the image
+        splats.add(3, 0, nalgebra_glm::DVec3::from_element(1.));
+        splats.add(0, 2, nalgebra_glm::DVec3::from_element(1.));
+        let image = splats.into_image();
+        assert_eq!(image.len(), 6);
+        for pixel in image {
+            approx::assert_relative_eq!(pixel, nalgebra_glm::DVec3::from_element(100.));
+        }
+    }
 }
\ No newline at end of file
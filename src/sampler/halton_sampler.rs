@@ -1,3 +1,4 @@
+use crate::common::RandomGen;
 use super::Sampler;
 
 pub struct HaltonSampler {
@@ -14,6 +15,10 @@ impl HaltonSampler {
     }
 
     fn next(&self) -> (f64, f64) {
+        if RandomGen::is_replaying() {
+            // The sequences are shared, so only `RandomGen` can replay the numbers of a path
+            return (RandomGen::rand2(), RandomGen::rand2());
+        }
         let a = self.seq1.lock().as_mut().ok()
             .and_then(|guard| guard.next())
             .unwrap_or(0.);
@@ -7,4 +7,36 @@ pub use random_sampler::RandomSampler;
 /// Generaters new directions to sample
 pub trait Sampler: std::marker::Sync {
     fn hemisphere(&self) -> nalgebra_glm::DVec3;
+
+    /// Generates directions on a hemisphere around `z`, with a density proportional to the cosine with `z`
+    ///
+    /// Remaps the directions of `hemisphere`, whose `z` is uniform, so they keep their distribution.
+    fn cosine_hemisphere(&self) -> nalgebra_glm::DVec3 {
+        let direction = self.hemisphere().normalize();
+        let r = direction.xy().magnitude();
+        let z = direction.z.clamp(0., 1.).sqrt();
+        let scale = if r > 0. { (1. - direction.z.clamp(0., 1.)).sqrt() / r } else { 0. };
+        nalgebra_glm::DVec3::new(direction.x * scale, direction.y * scale, z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cosine_hemisphere() {
+        const SAMPLES: usize = 100_000;
+        for sampler in [Box::new(RandomSampler::new()) as Box<dyn Sampler>, Box::new(HaltonSampler::new())] {
+            let mut mean_z = 0.;
+            for _ in 0..SAMPLES {
+                let direction = sampler.cosine_hemisphere();
+                approx::assert_relative_eq!(direction.magnitude(), 1., epsilon = 1e-9);
+                assert!(direction.z >= 0.);
+                mean_z += direction.z;
+            }
+            // The mean cosine of a cosine distribution is 2/3
+            approx::assert_relative_eq!(mean_z / SAMPLES as f64, 2. / 3., epsilon = 0.01);
+        }
+    }
 }
\ No newline at end of file
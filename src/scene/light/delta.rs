@@ -0,0 +1,274 @@
+use crate::{common::Frame, extension::vector_ext::OrthonormalVectorExt};
+
+use super::EmissionSample;
+
+/// Light arriving at a point from a `DeltaLight`
+#[derive(Debug, Clone)]
+pub struct DeltaLightSample {
+    /// Direction from the point to the light
+    pub direction: nalgebra_glm::DVec3,
+    /// Distance from the point to the light, infinite for directional lights
+    pub distance: f64,
+    /// Light arriving at the point, before the cosine of the surface
+    pub radiance: nalgebra_glm::DVec3
+}
+
+/// Light without area, that can only be reached with shadow rays
+///
+/// Delta lights live in the `Scene` separately from its objects, they are never hit by bounces.
+pub trait DeltaLight: std::fmt::Debug + std::marker::Sync + std::marker::Send {
+    /// Light arriving at `point`, `None` if `point` is not lit
+    fn illuminate(&self, point: &nalgebra_glm::DVec3) -> Option<DeltaLightSample>;
+
+    /// Samples a ray leaving the light, `None` if the light can't start paths
+    ///
+    /// # Arguments
+    /// * `u` - two uniform random numbers in `[0, 1)`
+    fn sample_emission(&self, _u: &nalgebra_glm::DVec2) -> Option<EmissionSample> {
+        None
+    }
+
+    /// Solid angle density with which `sample_emission` leaves towards `direction`
+    fn emission_pdf(&self, _direction: &nalgebra_glm::DVec3) -> f64 {
+        0.
+    }
+}
+
+/// Uniform direction inside of a cone around `axis`
+///
+/// # Arguments
+/// * `cos_max` - cosine of the angle between the axis and the edge of the cone
+fn sample_cone(axis: &nalgebra_glm::DVec3, cos_max: f64, u: &nalgebra_glm::DVec2) -> nalgebra_glm::DVec3 {
+    let cos = 1. - u.x * (1. - cos_max);
+    let sin = (1. - cos * cos).max(0.).sqrt();
+    let phi = 2. * std::f64::consts::PI * u.y;
+    Frame::new(*axis, axis.orthonormal().0)
+        .to_world(&nalgebra_glm::DVec3::new(sin * phi.cos(), sin * phi.sin(), cos))
+        .normalize()
+}
+
+/// Light emitted equally in every direction from a point
+#[derive(Debug)]
+pub struct PointLight {
+    position: nalgebra_glm::DVec3,
+    intensity: nalgebra_glm::DVec3
+}
+
+impl PointLight {
+    /// Creates a point light
+    ///
+    /// # Arguments
+    /// * `position` - position of the light
+    /// * `intensity` - power per solid angle, per color channel, falls off with the squared distance
+    pub fn new(position: nalgebra_glm::DVec3, intensity: nalgebra_glm::DVec3) -> Self {
+        Self {
+            position,
+            intensity
+        }
+    }
+}
+
+impl DeltaLight for PointLight {
+    fn illuminate(&self, point: &nalgebra_glm::DVec3) -> Option<DeltaLightSample> {
+        let offset = self.position - point;
+        let distance = offset.magnitude();
+        (distance > 0.).then(
+            || DeltaLightSample {
+                direction: offset / distance,
+                distance,
+                radiance: self.intensity / (distance * distance)
+            }
+        )
+    }
+
+    fn sample_emission(&self, u: &nalgebra_glm::DVec2) -> Option<EmissionSample> {
+        Some(
+            EmissionSample {
+                origin: self.position,
+                normal: nalgebra_glm::zero(),
+                direction: sample_cone(&nalgebra_glm::DVec3::new(0., 1., 0.), -1., u),
+                radiance: self.intensity,
+                pdf_position: 1.,
+                pdf_direction: 1. / (4. * std::f64::consts::PI)
+            }
+        )
+    }
+
+    fn emission_pdf(&self, _direction: &nalgebra_glm::DVec3) -> f64 {
+        1. / (4. * std::f64::consts::PI)
+    }
+}
+
+/// Point light that only emits inside of a cone
+#[derive(Debug)]
+pub struct SpotLight {
+    position: nalgebra_glm::DVec3,
+    direction: nalgebra_glm::DVec3,
+    intensity: nalgebra_glm::DVec3,
+    cos_cone: f64,
+    cos_falloff: f64
+}
+
+impl SpotLight {
+    /// Creates a spot light
+    ///
+    /// # Arguments
+    /// * `position` - position of the light
+    /// * `direction` - axis of the cone, pointing away from the light
+    /// * `intensity` - power per solid angle on the axis, per color channel
+    /// * `cone_angle` - angle, in radians, between the axis and the edge of the cone
+    /// * `falloff_angle` - angle, in radians, at the edge of the cone where the intensity fades to zero
+    pub fn new(
+        position: nalgebra_glm::DVec3,
+        direction: nalgebra_glm::DVec3,
+        intensity: nalgebra_glm::DVec3,
+        cone_angle: f64,
+        falloff_angle: f64
+    ) -> Self {
+        let cone_angle = cone_angle.clamp(0., std::f64::consts::PI);
+        Self {
+            position,
+            direction: direction.normalize(),
+            intensity,
+            cos_cone: cone_angle.cos(),
+            cos_falloff: (cone_angle - falloff_angle.clamp(0., cone_angle)).cos()
+        }
+    }
+
+    /// Fraction of the intensity emitted towards `direction`
+    fn falloff(&self, direction: &nalgebra_glm::DVec3) -> f64 {
+        let cos = self.direction.dot(direction);
+        if cos >= self.cos_falloff {
+            1.
+        } else if cos <= self.cos_cone {
+            0.
+        } else {
+            // Smoothstep between the edge of the cone and the start of the falloff
+            let t = (cos - self.cos_cone) / (self.cos_falloff - self.cos_cone);
+            t * t * (3. - 2. * t)
+        }
+    }
+}
+
+impl DeltaLight for SpotLight {
+    fn illuminate(&self, point: &nalgebra_glm::DVec3) -> Option<DeltaLightSample> {
+        let offset = self.position - point;
+        let distance = offset.magnitude();
+        if distance <= 0. {
+            return None;
+        }
+        let direction = offset / distance;
+        let falloff = self.falloff(&-direction);
+        (falloff > 0.).then(
+            || DeltaLightSample {
+                direction,
+                distance,
+                radiance: self.intensity * (falloff / (distance * distance))
+            }
+        )
+    }
+
+    fn sample_emission(&self, u: &nalgebra_glm::DVec2) -> Option<EmissionSample> {
+        let direction = sample_cone(&self.direction, self.cos_cone, u);
+        Some(
+            EmissionSample {
+                origin: self.position,
+                normal: nalgebra_glm::zero(),
+                direction,
+                radiance: self.intensity * self.falloff(&direction),
+                pdf_position: 1.,
+                pdf_direction: self.emission_pdf(&direction)
+            }
+        )
+    }
+
+    fn emission_pdf(&self, direction: &nalgebra_glm::DVec3) -> f64 {
+        if self.direction.dot(&direction.normalize()) >= self.cos_cone {
+            1. / (2. * std::f64::consts::PI * (1. - self.cos_cone))
+        } else {
+            0.
+        }
+    }
+}
+
+/// Light arriving from a single direction, like the sun
+#[derive(Debug)]
+pub struct DirectionalLight {
+    direction: nalgebra_glm::DVec3,
+    irradiance: nalgebra_glm::DVec3
+}
+
+impl DirectionalLight {
+    /// Creates a directional light
+    ///
+    /// # Arguments
+    /// * `direction` - direction the light travels in
+    /// * `irradiance` - power per area on surfaces facing the light, per color channel
+    pub fn new(direction: nalgebra_glm::DVec3, irradiance: nalgebra_glm::DVec3) -> Self {
+        Self {
+            direction: direction.normalize(),
+            irradiance
+        }
+    }
+}
+
+impl DeltaLight for DirectionalLight {
+    fn illuminate(&self, _point: &nalgebra_glm::DVec3) -> Option<DeltaLightSample> {
+        Some(
+            DeltaLightSample {
+                direction: -self.direction,
+                distance: f64::INFINITY,
+                radiance: self.irradiance
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn point_light_inverse_square() {
+        let light = PointLight::new(nalgebra_glm::DVec3::new(0., 2., 0.), nalgebra_glm::DVec3::from_element(8.));
+        let sample = light.illuminate(&nalgebra_glm::DVec3::new(0., 0., 0.)).unwrap();
+        approx::assert_abs_diff_eq!(sample.direction, nalgebra_glm::DVec3::new(0., 1., 0.));
+        approx::assert_abs_diff_eq!(sample.distance, 2.);
+        approx::assert_abs_diff_eq!(sample.radiance, nalgebra_glm::DVec3::from_element(2.));
+    }
+
+    #[test]
+    fn spot_light_cone() {
+        let light = SpotLight::new(
+            nalgebra_glm::DVec3::new(0., 1., 0.),
+            nalgebra_glm::DVec3::new(0., -1., 0.),
+            nalgebra_glm::DVec3::from_element(1.),
+            std::f64::consts::FRAC_PI_4,
+            0.2
+        );
+        let radiance = |x: f64| light.illuminate(&nalgebra_glm::DVec3::new(x, 0., 0.)).map(|sample| sample.radiance.x * (1. + x * x));
+        approx::assert_abs_diff_eq!(radiance(0.).unwrap(), 1.);
+        approx::assert_abs_diff_eq!(radiance(0.5).unwrap(), 1.);
+        // Fades out inside of the falloff
+        let edge = radiance((std::f64::consts::FRAC_PI_4 - 0.1).tan()).unwrap();
+        assert!(0. < edge && edge < 1., "{edge}");
+        assert!(radiance(1.1).is_none());
+
+        // Rays only leave inside of the cone
+        for u in [(0., 0.3), (0.5, 0.5), (0.999, 0.9)] {
+            let sample = light.sample_emission(&nalgebra_glm::DVec2::new(u.0, u.1)).unwrap();
+            assert!(sample.direction.y <= -std::f64::consts::FRAC_PI_4.cos() + 1e-9);
+            approx::assert_relative_eq!(sample.pdf_direction, light.emission_pdf(&sample.direction));
+        }
+        approx::assert_abs_diff_eq!(light.emission_pdf(&nalgebra_glm::DVec3::new(1., 0., 0.)), 0.);
+    }
+
+    #[test]
+    fn directional_light() {
+        let light = DirectionalLight::new(nalgebra_glm::DVec3::new(0., -2., 0.), nalgebra_glm::DVec3::from_element(3.));
+        let sample = light.illuminate(&nalgebra_glm::DVec3::new(5., -7., 1.)).unwrap();
+        approx::assert_abs_diff_eq!(sample.direction, nalgebra_glm::DVec3::new(0., 1., 0.));
+        assert!(sample.distance.is_infinite());
+        approx::assert_abs_diff_eq!(sample.radiance, nalgebra_glm::DVec3::from_element(3.));
+    }
+}
\ No newline at end of file
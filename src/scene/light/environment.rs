@@ -0,0 +1,350 @@
+use crate::{
+    common::{Distribution2D, Frame},
+    extension::vector_ext::OrthonormalVectorExt,
+    scene::texture::{Texture, ImageTexture}
+};
+
+use super::SunSky;
+
+/// Resolution of the latitude-longitude grid used to sample the sky of a `SunSky`
+const SKY_RESOLUTION: (usize, usize) = (128, 64);
+/// Probability of sampling the sun disk instead of the sky, while the sun is above the horizon
+const SUN_PROBABILITY: f64 = 0.5;
+
+#[derive(Debug)]
+enum EnvironmentEmission {
+    Constant(nalgebra_glm::DVec3),
+    Image {
+        texture: ImageTexture,
+        distribution: Distribution2D
+    },
+    SunSky {
+        sky: SunSky,
+        distribution: Distribution2D
+    }
+}
+
+/// Light arriving from infinitely far away, returned by rays that leave the `Scene`
+///
+/// Images are mapped by longitude and latitude around the vertical axis, the same way as the UVs of a sphere,
+/// with the top row of the image straight up. Skies sample the sun disk and the rest of the sky separately.
+#[derive(Debug)]
+pub struct EnvironmentLight {
+    emission: EnvironmentEmission,
+    rotation: f64,
+    intensity: f64
+}
+
+impl EnvironmentLight {
+    /// Environment with the same color in every direction
+    pub fn constant(color: nalgebra_glm::DVec3) -> Self {
+        Self {
+            emission: EnvironmentEmission::Constant(color),
+            rotation: 0.,
+            intensity: 1.
+        }
+    }
+
+    /// Environment from a latitude-longitude image, usually a high dynamic range image
+    ///
+    /// Directions are sampled proportionally to the luminance of the image.
+    pub fn image(texture: ImageTexture) -> Self {
+        let (width, height) = texture.dimensions();
+        let luminance = |u: f64, v: f64| {
+            let color = texture.evaluate(&nalgebra_glm::zero(), &nalgebra_glm::zero(), &nalgebra_glm::DVec2::new(u, v));
+            0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+        };
+        let mut function = Vec::with_capacity(width * height);
+        for row in 0..height {
+            let v = (row as f64 + 0.5) / height as f64;
+            // Rows close to the poles cover less solid angle
+            let cos = ((v - 0.5) * std::f64::consts::PI).cos();
+            for column in 0..width {
+                let u = (column as f64 + 0.5) / width as f64;
+                // Filtering blends the neighbouring pixels, which must be sampled wherever they are seen
+                let brightest = [-1., 0., 1.]
+                    .into_iter()
+                    .flat_map(|dv| [-1., 0., 1.].map(|du| (u + du / width as f64, Self::clamp_v(v + dv / height as f64, height))))
+                    .map(|(u, v)| luminance(u, v))
+                    .fold(0_f64, f64::max);
+                function.push(brightest * cos);
+            }
+        }
+        Self {
+            emission: EnvironmentEmission::Image {
+                distribution: Distribution2D::new(&function, width, height),
+                texture
+            },
+            rotation: 0.,
+            intensity: 1.
+        }
+    }
+
+    /// Environment from an analytic daylight model, the sky and the sun disk
+    pub fn sun_sky(sky: SunSky) -> Self {
+        let (width, height) = SKY_RESOLUTION;
+        let mut function = Vec::with_capacity(width * height);
+        for row in 0..height {
+            for column in 0..width {
+                let uv = nalgebra_glm::DVec2::new((column as f64 + 0.5) / width as f64, (row as f64 + 0.5) / height as f64);
+                let (direction, cos_elevation) = Self::lat_long(&uv);
+                let color = sky.sky_radiance(&direction);
+                function.push((0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z) * cos_elevation);
+            }
+        }
+        Self {
+            emission: EnvironmentEmission::SunSky {
+                sky,
+                distribution: Distribution2D::new(&function, width, height)
+            },
+            rotation: 0.,
+            intensity: 1.
+        }
+    }
+
+    /// Rotates the environment around the vertical axis, in radians
+    pub fn with_rotation(mut self, rotation: f64) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Scales the light of the environment
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Keeps lookups inside of the first and last rows, so the poles don't wrap to the other side of the image
+    fn clamp_v(v: f64, height: usize) -> f64 {
+        v.clamp(0.5 / height as f64, 1. - 0.5 / height as f64)
+    }
+
+    fn rotate(direction: &nalgebra_glm::DVec3, angle: f64) -> nalgebra_glm::DVec3 {
+        let (sin, cos) = angle.sin_cos();
+        nalgebra_glm::DVec3::new(
+            direction.x * cos - direction.z * sin,
+            direction.y,
+            direction.x * sin + direction.z * cos
+        )
+    }
+
+    /// Longitude and latitude of a direction in the space of the environment, both in `[0, 1]`
+    fn uv(&self, direction: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec2 {
+        let local = Self::rotate(&direction.normalize(), -self.rotation);
+        nalgebra_glm::DVec2::new(
+            0.5 + local.z.atan2(local.x) / (2. * std::f64::consts::PI),
+            0.5 + local.y.clamp(-1., 1.).asin() / std::f64::consts::PI
+        )
+    }
+
+    /// Direction of a longitude and latitude in the space of the environment, and the cosine of the latitude
+    fn lat_long(uv: &nalgebra_glm::DVec2) -> (nalgebra_glm::DVec3, f64) {
+        let phi = (uv.x - 0.5) * 2. * std::f64::consts::PI;
+        let (sin_elevation, cos_elevation) = ((uv.y - 0.5) * std::f64::consts::PI).sin_cos();
+        (nalgebra_glm::DVec3::new(cos_elevation * phi.cos(), sin_elevation, cos_elevation * phi.sin()), cos_elevation)
+    }
+
+    /// Direction of a longitude and latitude, and the cosine of the latitude
+    fn direction(&self, uv: &nalgebra_glm::DVec2) -> (nalgebra_glm::DVec3, f64) {
+        let (local, cos_elevation) = Self::lat_long(uv);
+        (Self::rotate(&local, self.rotation), cos_elevation)
+    }
+
+    /// Light arriving from `direction`, pointing away from the scene
+    pub fn radiance(&self, direction: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec3 {
+        match &self.emission {
+            EnvironmentEmission::Constant(color) => color * self.intensity,
+            EnvironmentEmission::Image { texture, .. } => {
+                let uv = self.uv(direction);
+                let uv = nalgebra_glm::DVec2::new(uv.x, Self::clamp_v(uv.y, texture.dimensions().1));
+                texture.evaluate(&nalgebra_glm::zero(), &nalgebra_glm::zero(), &uv) * self.intensity
+            },
+            EnvironmentEmission::SunSky { sky, .. } => {
+                sky.radiance(&Self::rotate(&direction.normalize(), -self.rotation)) * self.intensity
+            }
+        }
+    }
+
+    /// Probability of sampling the sun disk of a sky
+    fn sun_probability(sky: &SunSky) -> f64 {
+        if sky.sun_radiance().max() > 0. {
+            SUN_PROBABILITY
+        } else {
+            0.
+        }
+    }
+
+    /// Solid angle density of a latitude-longitude distribution choosing `direction`
+    fn distribution_pdf(&self, distribution: &Distribution2D, direction: &nalgebra_glm::DVec3) -> f64 {
+        let uv = self.uv(direction);
+        let cos_elevation = ((uv.y - 0.5) * std::f64::consts::PI).cos();
+        if cos_elevation <= 0. {
+            0.
+        } else {
+            distribution.pdf(&uv) / (2. * std::f64::consts::PI * std::f64::consts::PI * cos_elevation)
+        }
+    }
+
+    /// Samples a latitude-longitude distribution, returns the direction and its solid angle density
+    fn sample_distribution(&self, distribution: &Distribution2D, u: &nalgebra_glm::DVec2) -> Option<(nalgebra_glm::DVec3, f64)> {
+        let (uv, pdf) = distribution.sample(u);
+        let (direction, cos_elevation) = self.direction(&uv);
+        (pdf > 0. && cos_elevation > 0.).then(
+            || (direction, pdf / (2. * std::f64::consts::PI * std::f64::consts::PI * cos_elevation))
+        )
+    }
+
+    /// Samples a direction towards the environment
+    ///
+    /// Returns the direction, the light arriving from it and its solid angle density,
+    /// `None` if the direction can't be used.
+    pub fn sample(&self, u: &nalgebra_glm::DVec2) -> Option<(nalgebra_glm::DVec3, nalgebra_glm::DVec3, f64)> {
+        match &self.emission {
+            EnvironmentEmission::Constant(color) => {
+                let z = 1. - 2. * u.x;
+                let r = (1. - z * z).max(0.).sqrt();
+                let phi = 2. * std::f64::consts::PI * u.y;
+                Some((
+                    nalgebra_glm::DVec3::new(r * phi.cos(), r * phi.sin(), z),
+                    color * self.intensity,
+                    1. / (4. * std::f64::consts::PI)
+                ))
+            },
+            EnvironmentEmission::Image { distribution, .. } => {
+                let (direction, pdf) = self.sample_distribution(distribution, u)?;
+                Some((direction, self.radiance(&direction), pdf))
+            },
+            EnvironmentEmission::SunSky { sky, distribution } => {
+                let sun_probability = Self::sun_probability(sky);
+                let direction = if u.x < sun_probability {
+                    // Uniform directions inside of the sun disk
+                    let cos = 1. - (u.x / sun_probability) * (1. - sky.cos_sun_radius());
+                    let sin = (1. - cos * cos).max(0.).sqrt();
+                    let phi = 2. * std::f64::consts::PI * u.y;
+                    let axis = Self::rotate(sky.sun_direction(), self.rotation);
+                    Frame::new(axis, axis.orthonormal().0)
+                        .to_world(&nalgebra_glm::DVec3::new(sin * phi.cos(), sin * phi.sin(), cos))
+                        .normalize()
+                } else {
+                    let u = nalgebra_glm::DVec2::new((u.x - sun_probability) / (1. - sun_probability), u.y);
+                    self.sample_distribution(distribution, &u)?.0
+                };
+                let pdf = self.pdf(&direction);
+                (pdf > 0.).then(|| (direction, self.radiance(&direction), pdf))
+            }
+        }
+    }
+
+    /// Solid angle density of `sample` choosing `direction`
+    pub fn pdf(&self, direction: &nalgebra_glm::DVec3) -> f64 {
+        match &self.emission {
+            EnvironmentEmission::Constant(_) => 1. / (4. * std::f64::consts::PI),
+            EnvironmentEmission::Image { distribution, .. } => self.distribution_pdf(distribution, direction),
+            EnvironmentEmission::SunSky { sky, distribution } => {
+                let sun_probability = Self::sun_probability(sky);
+                let local = Self::rotate(&direction.normalize(), -self.rotation);
+                let sun_pdf = if local.dot(sky.sun_direction()) >= sky.cos_sun_radius() {
+                    1. / (2. * std::f64::consts::PI * (1. - sky.cos_sun_radius()))
+                } else {
+                    0.
+                };
+                sun_probability * sun_pdf + (1. - sun_probability) * self.distribution_pdf(distribution, direction)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::texture::WrapMode;
+
+    fn sun_image() -> ImageTexture {
+        // Dim sky with a single bright pixel above the horizon
+        let mut pixels = vec![nalgebra_glm::DVec3::from_element(0.1); 8 * 4];
+        pixels[8 + 5] = nalgebra_glm::DVec3::from_element(100.);
+        ImageTexture::new(8, 4, pixels, WrapMode::Repeat).unwrap()
+    }
+
+    #[test]
+    fn sample_matches_pdf() {
+        for environment in [
+            EnvironmentLight::constant(nalgebra_glm::DVec3::from_element(1.)),
+            EnvironmentLight::image(sun_image()).with_rotation(1.).with_intensity(2.),
+            EnvironmentLight::sun_sky(SunSky::new(nalgebra_glm::DVec3::new(1., 1., 0.5), 3.)).with_rotation(0.3)
+        ] {
+            for u in [(0.1, 0.2), (0.5, 0.5), (0.9, 0.7), (0.3, 0.99)] {
+                let (direction, radiance, pdf) = environment.sample(&nalgebra_glm::DVec2::new(u.0, u.1)).unwrap();
+                approx::assert_relative_eq!(direction.magnitude(), 1., epsilon = 1e-9);
+                approx::assert_relative_eq!(pdf, environment.pdf(&direction), max_relative = 1e-9);
+                approx::assert_relative_eq!(radiance, environment.radiance(&direction), max_relative = 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn importance_sampling_is_unbiased() {
+        let environment = EnvironmentLight::image(sun_image()).with_rotation(0.5);
+        // Integral of the radiance over the sphere, estimated with stratified samples
+        const STRATA: usize = 256;
+        let mut sampled = 0.;
+        let mut uniform = 0.;
+        for i in 0..STRATA {
+            for j in 0..STRATA {
+                let u = nalgebra_glm::DVec2::new((i as f64 + 0.5) / STRATA as f64, (j as f64 + 0.5) / STRATA as f64);
+                if let Some((_, radiance, pdf)) = environment.sample(&u) {
+                    sampled += radiance.x / pdf;
+                }
+                let z = 1. - 2. * u.x;
+                let r = (1. - z * z).sqrt();
+                let phi = 2. * std::f64::consts::PI * u.y;
+                uniform += environment.radiance(&nalgebra_glm::DVec3::new(r * phi.cos(), z, r * phi.sin())).x * 4. * std::f64::consts::PI;
+            }
+        }
+        let count = (STRATA * STRATA) as f64;
+        approx::assert_relative_eq!(sampled / count, uniform / count, max_relative = 0.02);
+    }
+
+    #[test]
+    fn rotation() {
+        let environment = EnvironmentLight::image(sun_image());
+        let (direction, _, _) = environment.sample(&nalgebra_glm::DVec2::new(0.5, 0.5)).unwrap();
+        let rotated = EnvironmentLight::image(sun_image()).with_rotation(std::f64::consts::FRAC_PI_2);
+        let turned = EnvironmentLight::rotate(&direction, std::f64::consts::FRAC_PI_2);
+        approx::assert_relative_eq!(rotated.radiance(&turned), environment.radiance(&direction), max_relative = 1e-9);
+        approx::assert_relative_eq!(environment.radiance(&direction), nalgebra_glm::DVec3::from_element(100.), max_relative = 0.5);
+    }
+
+    #[test]
+    fn sun_sky_sampling() {
+        let sky = SunSky::from_date(120., 15., 40., 2.5);
+        let sun_direction = EnvironmentLight::rotate(sky.sun_direction(), 2.);
+        let environment = EnvironmentLight::sun_sky(sky.clone()).with_rotation(2.);
+        // Half of the samples land on the sun disk
+        let (direction, radiance, _) = environment.sample(&nalgebra_glm::DVec2::new(0.2, 0.4)).unwrap();
+        assert!(direction.dot(&sun_direction) > 0.9999);
+        assert!(radiance.y > 1e4);
+        let (direction, _, _) = environment.sample(&nalgebra_glm::DVec2::new(0.7, 0.4)).unwrap();
+        assert!(direction.y > 0.);
+
+        // Estimates of the irradiance of a horizontal surface agree with the importance sampling and the cosine
+        const STRATA: usize = 256;
+        let mut sky_irradiance = 0.;
+        let mut sampled = 0.;
+        for i in 0..STRATA {
+            for j in 0..STRATA {
+                let u = nalgebra_glm::DVec2::new((i as f64 + 0.5) / STRATA as f64, (j as f64 + 0.5) / STRATA as f64);
+                if let Some((direction, radiance, pdf)) = environment.sample(&u) {
+                    sampled += radiance.y * direction.y.max(0.) / pdf;
+                }
+                let (r, phi) = (u.x.sqrt(), 2. * std::f64::consts::PI * u.y);
+                let direction = nalgebra_glm::DVec3::new(r * phi.cos(), (1. - u.x).sqrt(), r * phi.sin());
+                sky_irradiance += sky.sky_radiance(&EnvironmentLight::rotate(&direction, -2.)).y * std::f64::consts::PI;
+            }
+        }
+        let count = (STRATA * STRATA) as f64;
+        // The sun disk is small enough to be lit with the same cosine everywhere
+        let sun = sky.sun_radiance().y * sun_direction.y * 2. * std::f64::consts::PI * (1. - sky.cos_sun_radius());
+        approx::assert_relative_eq!(sampled / count, sky_irradiance / count + sun, max_relative = 0.03);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,201 @@
+use super::LightError;
+
+/// Photometric profile of a light fixture read from an IESNA LM-63 file
+///
+/// Only type C photometry, the one used by architectural fixtures, is supported. Vertical angles
+/// start at the nadir of the fixture and horizontal angles go around it, both in degrees.
+#[derive(Debug, Clone)]
+pub struct IesProfile {
+    vertical_angles: Vec<f64>,
+    horizontal_angles: Vec<f64>,
+    /// Candelas, one row of vertical angles for each horizontal angle
+    candela: Vec<Vec<f64>>,
+    max_candela: f64
+}
+
+impl IesProfile {
+    /// Loads an IES file
+    pub fn from_file(path: &str) -> Result<Self, LightError> {
+        Self::read(std::fs::File::open(path)?)
+    }
+
+    /// Reads an IES file
+    pub fn read(mut reader: impl std::io::Read) -> Result<Self, LightError> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+
+        // Keywords come before the TILT line and are ignored
+        let (_, data) = text.split_once("TILT=").ok_or(LightError::IesFormatError)?;
+        let (tilt, data) = data.split_once('\n').ok_or(LightError::IesFormatError)?;
+        let mut values = data.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|token| !token.is_empty())
+            .map(|token| token.parse::<f64>().map_err(|_| LightError::IesFormatError));
+        let mut next = || values.next().unwrap_or(Err(LightError::IesFormatError));
+        // Counts must be whole numbers, casting would saturate anything else
+        let whole = |value: f64| if value >= 0. && value.fract() == 0. && value <= u32::MAX as f64 {
+            Ok(value as usize)
+        } else {
+            Err(LightError::IesFormatError)
+        };
+
+        if tilt.trim() == "INCLUDE" {
+            // Lamp to luminaire geometry, then the angles and multipliers of the tilt
+            next()?;
+            let count = whole(next()?)?;
+            for _ in 0..count.checked_mul(2).ok_or(LightError::IesFormatError)? {
+                next()?;
+            }
+        }
+
+        let _lamps = next()?;
+        let _lumens_per_lamp = next()?;
+        let multiplier = next()?;
+        let vertical_count = whole(next()?)?;
+        let horizontal_count = whole(next()?)?;
+        let photometric_type = next()?;
+        // Units, dimensions, ballast factor, future use, and input watts
+        for _ in 0..7 {
+            next()?;
+        }
+        if photometric_type != 1. {
+            return Err(LightError::IesPhotometricTypeError);
+        }
+        if vertical_count == 0 || horizontal_count == 0 {
+            return Err(LightError::IesFormatError);
+        }
+
+        let vertical_angles = (0..vertical_count).map(|_| next()).collect::<Result<Vec<_>, _>>()?;
+        let horizontal_angles = (0..horizontal_count).map(|_| next()).collect::<Result<Vec<_>, _>>()?;
+        let candela = (0..horizontal_count)
+            .map(|_| (0..vertical_count).map(|_| next().map(|c| c * multiplier)).collect::<Result<Vec<_>, _>>())
+            .collect::<Result<Vec<_>, _>>()?;
+        let sorted = |angles: &[f64]| angles.windows(2).all(|w| w[0] < w[1]);
+        if !sorted(&vertical_angles) || !sorted(&horizontal_angles) {
+            return Err(LightError::IesFormatError);
+        }
+        let max_candela = candela.iter().flatten().fold(0., |max: f64, c| max.max(*c));
+
+        Ok(
+            Self {
+                vertical_angles,
+                horizontal_angles,
+                candela,
+                max_candela
+            }
+        )
+    }
+
+    /// Luminous intensity, in candelas, at the given angles in degrees
+    pub fn intensity(&self, vertical: f64, horizontal: f64) -> f64 {
+        let horizontal = self.fold_horizontal(horizontal.rem_euclid(360.));
+        match interpolation(&self.horizontal_angles, horizontal) {
+            Some((row, t)) if t > 0. => {
+                self.vertical_intensity(row, vertical) * (1. - t) + self.vertical_intensity(row + 1, vertical) * t
+            },
+            Some((row, _)) => self.vertical_intensity(row, vertical),
+            None => 0.
+        }
+    }
+
+    /// Intensity relative to the brightest direction of the fixture
+    pub fn relative_intensity(&self, vertical: f64, horizontal: f64) -> f64 {
+        if self.max_candela > 0. {
+            self.intensity(vertical, horizontal) / self.max_candela
+        } else {
+            0.
+        }
+    }
+
+    pub fn max_intensity(&self) -> f64 {
+        self.max_candela
+    }
+
+    /// Uses the symmetry given by the last horizontal angle to bring `horizontal` to the measured range
+    fn fold_horizontal(&self, horizontal: f64) -> f64 {
+        let last = self.horizontal_angles[self.horizontal_angles.len() - 1];
+        if last <= 0. {
+            0.
+        } else if last <= 90. {
+            let horizontal = if horizontal > 180. { 360. - horizontal } else { horizontal };
+            if horizontal > 90. { 180. - horizontal } else { horizontal }
+        } else if last <= 180. && horizontal > 180. {
+            360. - horizontal
+        } else {
+            horizontal
+        }
+    }
+
+    fn vertical_intensity(&self, row: usize, vertical: f64) -> f64 {
+        match interpolation(&self.vertical_angles, vertical) {
+            Some((column, t)) if t > 0. => {
+                self.candela[row][column] * (1. - t) + self.candela[row][column + 1] * t
+            },
+            Some((column, _)) => self.candela[row][column],
+            None => 0.
+        }
+    }
+}
+
+/// Index of the last angle before `angle` and how far `angle` is towards the next one
+///
+/// `None` if `angle` is outside of `angles`, a single angle covers everything.
+fn interpolation(angles: &[f64], angle: f64) -> Option<(usize, f64)> {
+    if angles.len() == 1 {
+        return Some((0, 0.));
+    }
+    if angle < angles[0] || angle > angles[angles.len() - 1] {
+        return None;
+    }
+    let i = angles.partition_point(|a| *a <= angle).saturating_sub(1).min(angles.len() - 2);
+    Some((i, ((angle - angles[i]) / (angles[i + 1] - angles[i])).clamp(0., 1.)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUADRANT_SYMMETRIC: &str = "IESNA:LM-63-2002
+[TEST] test fixture
+[MANUFAC] smallpaint
+TILT=NONE
+1 1000 2 3 2 1 2 0.1 0.1 0.05
+1.0 1.0 20
+0 45 90
+0 90
+100 50 0
+200 100 0
+";
+
+    #[test]
+    fn read_and_interpolate() {
+        let profile = IesProfile::read(QUADRANT_SYMMETRIC.as_bytes()).unwrap();
+        approx::assert_abs_diff_eq!(profile.max_intensity(), 400.);
+        approx::assert_abs_diff_eq!(profile.intensity(0., 0.), 200.);
+        approx::assert_abs_diff_eq!(profile.intensity(22.5, 0.), 150.);
+        approx::assert_abs_diff_eq!(profile.intensity(45., 45.), 150.);
+        approx::assert_abs_diff_eq!(profile.intensity(120., 0.), 0.);
+        // Quadrant symmetry
+        approx::assert_abs_diff_eq!(profile.intensity(10., 135.), profile.intensity(10., 45.));
+        approx::assert_abs_diff_eq!(profile.intensity(10., 300.), profile.intensity(10., 60.));
+        approx::assert_abs_diff_eq!(profile.relative_intensity(0., 90.), 1.);
+    }
+
+    #[test]
+    fn read_tilt_and_errors() {
+        let tilted = "IESNA91\nTILT=INCLUDE\n1\n2\n0 90\n1 1\n1 -1 1 2 1 1 1 0 0 0\n1 1 10\n0 180\n0\n10 30\n";
+        let profile = IesProfile::read(tilted.as_bytes()).unwrap();
+        approx::assert_abs_diff_eq!(profile.intensity(90., 200.), 20.);
+
+        assert!(matches!(IesProfile::read("no tilt".as_bytes()), Err(LightError::IesFormatError)));
+        let type_b = QUADRANT_SYMMETRIC.replace("3 2 1 2", "3 2 2 2");
+        assert!(matches!(IesProfile::read(type_b.as_bytes()), Err(LightError::IesPhotometricTypeError)));
+        let truncated = &QUADRANT_SYMMETRIC[..QUADRANT_SYMMETRIC.len() - 8];
+        assert!(matches!(IesProfile::read(truncated.as_bytes()), Err(LightError::IesFormatError)));
+        let huge_tilt = tilted.replace("\n2\n", "\n1e300\n");
+        assert!(matches!(IesProfile::read(huge_tilt.as_bytes()), Err(LightError::IesFormatError)));
+        for count in ["-3 2", "2.5 2", "3 inf"] {
+            let invalid = QUADRANT_SYMMETRIC.replace("3 2 1", &format!("{count} 1"));
+            assert!(matches!(IesProfile::read(invalid.as_bytes()), Err(LightError::IesFormatError)));
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,183 @@
+use std::sync::Arc;
+
+use crate::{common::Frame, scene::obj::SceneObject};
+
+mod ies;
+pub use ies::IesProfile;
+
+mod delta;
+pub use delta::{DeltaLight, DeltaLightSample, PointLight, SpotLight, DirectionalLight};
+
+mod environment;
+pub use environment::EnvironmentLight;
+
+mod sun_sky;
+pub use sun_sky::SunSky;
+
+#[derive(Debug)]
+pub enum LightError {
+    IesFormatError,
+    IesPhotometricTypeError,
+    IesReadError(std::io::Error)
+}
+
+impl std::fmt::Display for LightError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let m = match self {
+            LightError::IesFormatError => String::from("The file is not a valid IESNA LM-63 photometric file."),
+            LightError::IesPhotometricTypeError => String::from("Only type C photometry is supported."),
+            LightError::IesReadError(err) => format!("Could not read the photometric file: {err}")
+        };
+        writeln!(f, "{m}")
+    }
+}
+
+impl std::error::Error for LightError {}
+
+impl From<std::io::Error> for LightError {
+    fn from(value: std::io::Error) -> Self {
+        LightError::IesReadError(value)
+    }
+}
+
+/// Point sampled on an emissive object by `SceneObject::sample_light`
+#[derive(Debug, Clone)]
+pub struct LightSample {
+    /// Point on the surface of the light
+    pub point: nalgebra_glm::DVec3,
+    /// Normal of the light at `point`
+    pub normal: nalgebra_glm::DVec3,
+    /// Direction from the reference point to `point`
+    pub direction: nalgebra_glm::DVec3,
+    /// Distance from the reference point to `point`
+    pub distance: f64,
+    /// Solid angle density of `direction` around the reference point
+    pub pdf: f64
+}
+
+/// Ray leaving a light, sampled by tracers that start paths on the lights
+#[derive(Debug, Clone)]
+pub struct EmissionSample {
+    /// Point the ray leaves from
+    pub origin: nalgebra_glm::DVec3,
+    /// Normal of the light at `origin`, zero for lights without area
+    pub normal: nalgebra_glm::DVec3,
+    /// Direction of the ray
+    pub direction: nalgebra_glm::DVec3,
+    /// Radiance emitted along the ray, or intensity for lights without area
+    pub radiance: nalgebra_glm::DVec3,
+    /// Area density of `origin`, `1` for lights without area
+    pub pdf_position: f64,
+    /// Solid angle density of `direction`
+    pub pdf_direction: f64
+}
+
+/// Light that tracers can start paths from, chosen by `Scene::sample_emitter`
+#[derive(Debug, Clone, Copy)]
+pub enum Emitter<'a> {
+    /// Emissive object with a finite area, see `SceneObject::is_light`
+    Object(&'a SceneObject),
+    /// Light without area
+    Delta(&'a dyn DeltaLight)
+}
+
+impl Emitter<'_> {
+    /// Samples a ray leaving the light, `None` if the light can't start paths
+    ///
+    /// # Arguments
+    /// * `u_position` - two uniform random numbers in `[0, 1)` for the point on the light
+    /// * `u_direction` - two uniform random numbers in `[0, 1)` for the direction of the ray
+    pub fn sample_emission(&self, u_position: &nalgebra_glm::DVec2, u_direction: &nalgebra_glm::DVec2) -> Option<EmissionSample> {
+        match self {
+            Emitter::Object(object) => object.sample_emission(u_position, u_direction),
+            Emitter::Delta(light) => light.sample_emission(u_direction)
+        }
+    }
+
+    /// Solid angle density of rays leaving the light from a point with `normal` towards `direction`
+    pub fn emission_pdf(&self, normal: &nalgebra_glm::DVec3, direction: &nalgebra_glm::DVec3) -> f64 {
+        match self {
+            Emitter::Object(object) => object.emission_pdf(normal, direction),
+            Emitter::Delta(light) => light.emission_pdf(direction)
+        }
+    }
+}
+
+/// How the emission of an object changes with the direction it leaves the surface
+#[derive(Debug, Clone)]
+pub enum EmissionProfile {
+    /// Same emission in every direction, on both sides of the surface
+    TwoSided,
+    /// Emits only on the side the geometric normal points to
+    OneSided,
+    /// Emission scaled by a photometric profile relative to its brightest direction
+    ///
+    /// The normal of the frame is the nadir of the fixture, and the tangent is the
+    /// direction of the horizontal angle `0`.
+    Ies {
+        profile: Arc<IesProfile>,
+        frame: Frame
+    }
+}
+
+impl EmissionProfile {
+    /// Orients a photometric profile
+    ///
+    /// # Arguments
+    /// * `profile` - the photometric profile
+    /// * `nadir` - direction of the vertical angle `0`, usually pointing down
+    /// * `reference` - direction of the horizontal angle `0`
+    pub fn ies(profile: Arc<IesProfile>, nadir: nalgebra_glm::DVec3, reference: nalgebra_glm::DVec3) -> Self {
+        EmissionProfile::Ies {
+            profile,
+            frame: Frame::new(nadir, reference)
+        }
+    }
+
+    /// Fraction of the emission that leaves the surface towards `outgoing`
+    ///
+    /// # Arguments
+    /// * `normal` - geometric normal of the surface
+    /// * `outgoing` - direction leaving the surface
+    pub fn factor(&self, normal: &nalgebra_glm::DVec3, outgoing: &nalgebra_glm::DVec3) -> f64 {
+        match self {
+            EmissionProfile::TwoSided => 1.,
+            EmissionProfile::OneSided => if normal.dot(outgoing) > 0. { 1. } else { 0. },
+            EmissionProfile::Ies { profile, frame } => {
+                let local = frame.to_local(&outgoing.normalize());
+                let vertical = local.z.clamp(-1., 1.).acos().to_degrees();
+                let horizontal = local.y.atan2(local.x).to_degrees();
+                profile.relative_intensity(vertical, horizontal)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_sided() {
+        let normal = nalgebra_glm::DVec3::new(0., 1., 0.);
+        approx::assert_abs_diff_eq!(EmissionProfile::OneSided.factor(&normal, &nalgebra_glm::DVec3::new(0.3, 1., 0.)), 1.);
+        approx::assert_abs_diff_eq!(EmissionProfile::OneSided.factor(&normal, &nalgebra_glm::DVec3::new(0.3, -1., 0.)), 0.);
+        approx::assert_abs_diff_eq!(EmissionProfile::TwoSided.factor(&normal, &nalgebra_glm::DVec3::new(0.3, -1., 0.)), 1.);
+    }
+
+    #[test]
+    fn oriented_ies_profile() {
+        // Brightest straight down, dark sideways
+        let ies = "IESNA:LM-63-2002\nTILT=NONE\n1 1000 1 3 1 1 2 0 0 0\n1 1 10\n0 45 90\n0\n100 50 0\n";
+        let profile = EmissionProfile::ies(
+            Arc::new(IesProfile::read(ies.as_bytes()).unwrap()),
+            nalgebra_glm::DVec3::new(0., -1., 0.),
+            nalgebra_glm::DVec3::new(1., 0., 0.)
+        );
+        let normal = nalgebra_glm::DVec3::new(0., -1., 0.);
+        approx::assert_abs_diff_eq!(profile.factor(&normal, &nalgebra_glm::DVec3::new(0., -1., 0.)), 1.);
+        approx::assert_abs_diff_eq!(profile.factor(&normal, &nalgebra_glm::DVec3::new(1., -1., 0.)), 0.5, epsilon = 1e-9);
+        approx::assert_abs_diff_eq!(profile.factor(&normal, &nalgebra_glm::DVec3::new(0., 0., 1.)), 0., epsilon = 1e-9);
+        approx::assert_abs_diff_eq!(profile.factor(&normal, &nalgebra_glm::DVec3::new(0., 1., 0.)), 0.);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,228 @@
+/// Angular radius of the sun, in radians
+const SUN_ANGULAR_RADIUS: f64 = 0.00465;
+/// Luminance of the sun outside of the atmosphere, in kcd/m²
+const SUN_LUMINANCE: f64 = 2e6;
+/// Representative wavelengths of the red, green, and blue channels, in micrometers
+const WAVELENGTHS: [f64; 3] = [0.68, 0.55, 0.44];
+
+/// Coefficients of the Perez distribution function
+#[derive(Debug, Clone, Copy)]
+struct Perez([f64; 5]);
+
+impl Perez {
+    fn new(turbidity: f64, coefficients: [(f64, f64); 5]) -> Self {
+        Self(coefficients.map(|(t, c)| t * turbidity + c))
+    }
+
+    /// Relative luminance of a point of the sky
+    ///
+    /// # Arguments
+    /// * `cos_theta` - cosine of the angle between the zenith and the point
+    /// * `gamma` - angle between the sun and the point
+    fn eval(&self, cos_theta: f64, gamma: f64) -> f64 {
+        let [a, b, c, d, e] = self.0;
+        (1. + a * (b / cos_theta.max(1e-3)).exp()) * (1. + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+    }
+}
+
+/// Clear sky with the sun, following the analytic daylight model of Preetham et al.
+///
+/// The sky is defined by the position of the sun and the turbidity of the atmosphere, from `2` for a very clear
+/// sky to `10` for hazy days. Radiance is in kcd/m², with the sky around a few units, and the sun disk many orders
+/// of magnitude brighter. The world is oriented with `+Y` up, and `+X` to the east and `+Z` to the south for
+/// positions from a date. Directions below the horizon are black, the ground is expected to be part of the scene.
+#[derive(Debug, Clone)]
+pub struct SunSky {
+    sun_direction: nalgebra_glm::DVec3,
+    turbidity: f64,
+    /// Luminance, and `x` and `y` chromaticities, of the zenith
+    zenith: [f64; 3],
+    /// Distributions of the luminance, and `x` and `y` chromaticities
+    perez: [Perez; 3],
+    sun_radiance: nalgebra_glm::DVec3
+}
+
+impl SunSky {
+    /// Creates a sky
+    ///
+    /// # Arguments
+    /// * `sun_direction` - direction from the scene towards the sun
+    /// * `turbidity` - haziness of the atmosphere, clamped to the `[1.7, 10]` range of the model
+    pub fn new(sun_direction: nalgebra_glm::DVec3, turbidity: f64) -> Self {
+        let sun_direction = sun_direction.normalize();
+        let t = turbidity.clamp(1.7, 10.);
+        let theta_s = sun_direction.y.clamp(-1., 1.).acos().min(std::f64::consts::FRAC_PI_2);
+
+        let chi = (4. / 9. - t / 120.) * (std::f64::consts::PI - 2. * theta_s);
+        let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let chromaticity = |m: [[f64; 4]; 3]| {
+            let thetas = [theta_s.powi(3), theta_s.powi(2), theta_s, 1.];
+            [t * t, t, 1.].iter()
+                .zip(m)
+                .map(|(t, row)| t * row.iter().zip(thetas).map(|(m, theta)| m * theta).sum::<f64>())
+                .sum::<f64>()
+        };
+        let zenith = [
+            luminance,
+            chromaticity([
+                [0.00166, -0.00375, 0.00209, 0.],
+                [-0.02903, 0.06377, -0.03202, 0.00394],
+                [0.11693, -0.21196, 0.06052, 0.25886]
+            ]),
+            chromaticity([
+                [0.00275, -0.00610, 0.00317, 0.],
+                [-0.04214, 0.08970, -0.04153, 0.00516],
+                [0.15346, -0.26756, 0.06670, 0.26688]
+            ])
+        ];
+        let perez = [
+            Perez::new(t, [(0.1787, -1.4630), (-0.3554, 0.4275), (-0.0227, 5.3251), (0.1206, -2.5771), (-0.0670, 0.3703)]),
+            Perez::new(t, [(-0.0193, -0.2592), (-0.0665, 0.0008), (-0.0004, 0.2125), (-0.0641, -0.8989), (-0.0033, 0.0452)]),
+            Perez::new(t, [(-0.0167, -0.2608), (-0.0950, 0.0092), (-0.0079, 0.2102), (-0.0441, -1.6537), (-0.0109, 0.0529)])
+        ];
+
+        let sun_radiance = if sun_direction.y > 0. {
+            // Sunlight scattered away by molecules and aerosols along the path through the atmosphere
+            let zenith_degrees = theta_s.to_degrees();
+            let mass = 1. / (theta_s.cos() + 0.15 * (93.885 - zenith_degrees).powf(-1.253));
+            let beta = 0.04608 * t - 0.04586;
+            nalgebra_glm::DVec3::from_iterator(
+                WAVELENGTHS.iter().map(
+                    |lambda| {
+                        let rayleigh = (-0.008735 * lambda.powf(-4.08) * mass).exp();
+                        let aerosol = (-beta * lambda.powf(-1.3) * mass).exp();
+                        SUN_LUMINANCE * rayleigh * aerosol
+                    }
+                )
+            )
+        } else {
+            nalgebra_glm::zero()
+        };
+
+        Self {
+            sun_direction,
+            turbidity: t,
+            zenith,
+            perez,
+            sun_radiance
+        }
+    }
+
+    /// Creates a sky with the sun where it is seen from the ground at a date and time
+    ///
+    /// # Arguments
+    /// * `day_of_year` - day of the year, from `1` for January 1st
+    /// * `solar_time` - local solar time in hours, with the sun highest at `12`
+    /// * `latitude` - latitude in degrees, positive on the northern hemisphere
+    /// * `turbidity` - haziness of the atmosphere
+    pub fn from_date(day_of_year: f64, solar_time: f64, latitude: f64, turbidity: f64) -> Self {
+        let declination = 0.4093 * (2. * std::f64::consts::PI * (day_of_year - 81.) / 368.).sin();
+        let hour_angle = std::f64::consts::PI * (solar_time - 12.) / 12.;
+        let latitude = latitude.to_radians();
+        let up = latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos();
+        let east = -declination.cos() * hour_angle.sin();
+        let north = latitude.cos() * declination.sin() - latitude.sin() * declination.cos() * hour_angle.cos();
+        Self::new(nalgebra_glm::DVec3::new(east, up, -north), turbidity)
+    }
+
+    /// Direction from the scene towards the sun
+    pub fn sun_direction(&self) -> &nalgebra_glm::DVec3 {
+        &self.sun_direction
+    }
+
+    pub fn turbidity(&self) -> f64 {
+        self.turbidity
+    }
+
+    /// Cosine of the angular radius of the sun disk
+    pub fn cos_sun_radius(&self) -> f64 {
+        SUN_ANGULAR_RADIUS.cos()
+    }
+
+    /// Radiance of the sun disk, zero when the sun is below the horizon
+    pub fn sun_radiance(&self) -> &nalgebra_glm::DVec3 {
+        &self.sun_radiance
+    }
+
+    /// Radiance of the sky towards `direction`, without the sun disk
+    pub fn sky_radiance(&self, direction: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec3 {
+        let direction = direction.normalize();
+        if direction.y <= 0. {
+            return nalgebra_glm::zero();
+        }
+        let theta_s = self.sun_direction.y.clamp(-1., 1.).acos().min(std::f64::consts::FRAC_PI_2);
+        let gamma = direction.dot(&self.sun_direction).clamp(-1., 1.).acos();
+        let [luminance, x, y] = [0, 1, 2].map(
+            |i| self.zenith[i] * self.perez[i].eval(direction.y, gamma) / self.perez[i].eval(1., theta_s)
+        );
+        // xyY to XYZ to linear sRGB
+        let big_x = x / y * luminance;
+        let big_z = (1. - x - y) / y * luminance;
+        nalgebra_glm::DVec3::new(
+            3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+            -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+            0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z
+        ).map(|channel| channel.max(0.))
+    }
+
+    /// Radiance of the sky towards `direction`, including the sun disk
+    pub fn radiance(&self, direction: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec3 {
+        let sky = self.sky_radiance(direction);
+        if direction.normalize().dot(&self.sun_direction) >= self.cos_sun_radius() {
+            sky + self.sun_radiance
+        } else {
+            sky
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sun_position() {
+        // Equinox at noon, the sun is over the equator
+        let equator = SunSky::from_date(81., 12., 0., 3.);
+        approx::assert_abs_diff_eq!(*equator.sun_direction(), nalgebra_glm::DVec3::new(0., 1., 0.), epsilon = 1e-9);
+        let north = SunSky::from_date(81., 12., 45., 3.);
+        let expected = nalgebra_glm::DVec3::new(0., 1., 1.).normalize();
+        approx::assert_abs_diff_eq!(*north.sun_direction(), expected, epsilon = 1e-9);
+        // Rises in the east
+        let morning = SunSky::from_date(172., 6., 45., 3.);
+        assert!(morning.sun_direction().x > morning.sun_direction().z.abs());
+        assert!(morning.sun_direction().y > 0.);
+        // Summer solstice on the tropic of cancer
+        let solstice = SunSky::from_date(172., 12., 23.44, 3.);
+        approx::assert_abs_diff_eq!(solstice.sun_direction().y, 1., epsilon = 1e-4);
+    }
+
+    #[test]
+    fn sky_radiance() {
+        let sky = SunSky::new(nalgebra_glm::DVec3::new(1., 1., 0.), 3.);
+        let towards_sun = sky.sky_radiance(&nalgebra_glm::DVec3::new(1., 0.3, 0.));
+        let away_from_sun = sky.sky_radiance(&nalgebra_glm::DVec3::new(-1., 0.3, 0.));
+        assert!(towards_sun.y > away_from_sun.y);
+        // Rayleigh scattering makes the clear sky blue away from the sun
+        let zenith = sky.sky_radiance(&nalgebra_glm::DVec3::new(0., 1., 0.));
+        assert!(zenith.z > zenith.x, "{zenith}");
+        approx::assert_abs_diff_eq!(sky.sky_radiance(&nalgebra_glm::DVec3::new(0., -0.1, 1.)), nalgebra_glm::zero());
+
+        // The sun disk is only seen around its direction
+        let sun = sky.radiance(sky.sun_direction());
+        assert!(sun.y > 1e4 * zenith.y);
+        approx::assert_abs_diff_eq!(sky.radiance(&nalgebra_glm::DVec3::new(1., 0.9, 0.)), sky.sky_radiance(&nalgebra_glm::DVec3::new(1., 0.9, 0.)));
+    }
+
+    #[test]
+    fn sunset_is_redder() {
+        let noon = SunSky::new(nalgebra_glm::DVec3::new(0., 1., 0.), 3.);
+        let sunset = SunSky::new(nalgebra_glm::DVec3::new(1., 0.05, 0.), 3.);
+        let hazy = SunSky::new(nalgebra_glm::DVec3::new(0., 1., 0.), 8.);
+        let ratio = |sky: &SunSky| sky.sun_radiance().x / sky.sun_radiance().z;
+        assert!(ratio(&sunset) > ratio(&noon));
+        assert!(ratio(&hazy) > ratio(&noon));
+        assert!(sunset.sun_radiance().y < noon.sun_radiance().y);
+        approx::assert_abs_diff_eq!(*SunSky::new(nalgebra_glm::DVec3::new(1., -0.1, 0.), 3.).sun_radiance(), nalgebra_glm::zero());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,128 @@
+/// Smallest `alpha` used by the distribution, smoother surfaces are numerically unstable
+const MIN_ALPHA: f64 = 1e-3;
+
+/// Anisotropic GGX (Trowbridge-Reitz) microfacet distribution
+///
+/// All directions are in the local space of a `Frame`, where the normal is `+z`
+/// and `alpha_x` is the roughness along the tangent.
+#[derive(Debug, Clone)]
+pub struct AnisotropicGgx {
+    alpha_x: f64,
+    alpha_y: f64
+}
+
+impl AnisotropicGgx {
+    /// Creates the distribution from perceptual roughness values in `[0, 1]`
+    pub fn new(roughness_x: f64, roughness_y: f64) -> Self {
+        Self {
+            alpha_x: roughness_x.clamp(0., 1.).powi(2).max(MIN_ALPHA),
+            alpha_y: roughness_y.clamp(0., 1.).powi(2).max(MIN_ALPHA)
+        }
+    }
+
+    /// Density of microfacets with normal `half`
+    pub fn distribution(&self, half: &nalgebra_glm::DVec3) -> f64 {
+        if half.z <= 0. {
+            0.
+        } else {
+            let e = (half.x / self.alpha_x).powi(2) + (half.y / self.alpha_y).powi(2) + half.z.powi(2);
+            1. / (std::f64::consts::PI * self.alpha_x * self.alpha_y * e.powi(2))
+        }
+    }
+
+    fn lambda(&self, w: &nalgebra_glm::DVec3) -> f64 {
+        let tan_sq = ((self.alpha_x * w.x).powi(2) + (self.alpha_y * w.y).powi(2)) / w.z.powi(2);
+        ((1. + tan_sq).sqrt() - 1.) / 2.
+    }
+
+    /// Smith height-correlated masking-shadowing
+    pub fn masking_shadowing(&self, incoming: &nalgebra_glm::DVec3, outgoing: &nalgebra_glm::DVec3) -> f64 {
+        1. / (1. + self.lambda(incoming) + self.lambda(outgoing))
+    }
+
+    /// Samples a microfacet normal proportionally to `D(h) * cos(theta_h)`
+    pub fn sample_half_vector(&self, u1: f64, u2: f64) -> nalgebra_glm::DVec3 {
+        let phi = 2. * std::f64::consts::PI * u2;
+        let phi = (self.alpha_y * phi.sin()).atan2(self.alpha_x * phi.cos());
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        let alpha_sq = 1. / ((cos_phi / self.alpha_x).powi(2) + (sin_phi / self.alpha_y).powi(2));
+        let tan_theta_sq = alpha_sq * u1 / (1. - u1).max(f64::EPSILON);
+        let cos_theta = 1. / (1. + tan_theta_sq).sqrt();
+        let sin_theta = (1. - cos_theta.powi(2)).max(0.).sqrt();
+        nalgebra_glm::DVec3::new(sin_theta * cos_phi, sin_theta * sin_phi, cos_theta)
+    }
+
+    /// Solid angle density of reflecting `incoming` into `outgoing` when sampling with `sample_half_vector`
+    ///
+    /// `incoming` points away from the surface, towards where the ray came from.
+    pub fn reflection_pdf(&self, incoming: &nalgebra_glm::DVec3, outgoing: &nalgebra_glm::DVec3) -> f64 {
+        if incoming.z <= 0. || outgoing.z <= 0. {
+            0.
+        } else {
+            let half = (incoming + outgoing).normalize();
+            self.distribution(&half) * half.z / (4. * outgoing.dot(&half).abs())
+        }
+    }
+
+    /// Reflection BRDF without the Fresnel term
+    pub fn reflection(&self, incoming: &nalgebra_glm::DVec3, outgoing: &nalgebra_glm::DVec3) -> f64 {
+        if incoming.z <= 0. || outgoing.z <= 0. {
+            0.
+        } else {
+            let half = (incoming + outgoing).normalize();
+            self.distribution(&half) * self.masking_shadowing(incoming, outgoing) / (4. * incoming.z * outgoing.z)
+        }
+    }
+}
+
+/// Schlick's approximation of the Fresnel reflectance
+pub fn schlick(f0: &nalgebra_glm::DVec3, cos: f64) -> nalgebra_glm::DVec3 {
+    f0 + (nalgebra_glm::DVec3::from_element(1.) - f0) * (1. - cos.clamp(0., 1.)).powi(5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Integrates `D(h) * cos(theta_h)` over the hemisphere, must be 1
+    fn projected_area(ggx: &AnisotropicGgx) -> f64 {
+        const STEPS: usize = 1_000;
+        let mut sum = 0.;
+        for i in 0..STEPS {
+            let cos_theta = (i as f64 + 0.5) / STEPS as f64;
+            let sin_theta = (1. - cos_theta.powi(2)).sqrt();
+            for j in 0..STEPS {
+                let phi = 2. * std::f64::consts::PI * (j as f64 + 0.5) / STEPS as f64;
+                let h = nalgebra_glm::DVec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+                sum += ggx.distribution(&h) * cos_theta;
+            }
+        }
+        sum * 2. * std::f64::consts::PI / (STEPS * STEPS) as f64
+    }
+
+    #[test]
+    fn distribution_is_normalized() {
+        approx::assert_abs_diff_eq!(projected_area(&AnisotropicGgx::new(0.5, 0.5)), 1., epsilon = 1e-2);
+        approx::assert_abs_diff_eq!(projected_area(&AnisotropicGgx::new(0.3, 0.8)), 1., epsilon = 1e-2);
+    }
+
+    #[test]
+    fn sampled_half_vectors_are_on_hemisphere() {
+        let ggx = AnisotropicGgx::new(0.2, 0.9);
+        for i in 0..100 {
+            for j in 0..100 {
+                let h = ggx.sample_half_vector(i as f64 / 100., j as f64 / 100.);
+                approx::assert_abs_diff_eq!(h.magnitude(), 1., epsilon = 1e-9);
+                assert!(h.z > 0.);
+            }
+        }
+    }
+
+    #[test]
+    fn anisotropy_stretches_along_tangent() {
+        let ggx = AnisotropicGgx::new(0.8, 0.2);
+        let along_tangent = nalgebra_glm::DVec3::new(0.5, 0., 0.75_f64.sqrt());
+        let along_bitangent = nalgebra_glm::DVec3::new(0., 0.5, 0.75_f64.sqrt());
+        assert!(ggx.distribution(&along_tangent) > ggx.distribution(&along_bitangent));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,593 @@
+use std::sync::Arc;
+
+use crate::{
+    common::{RandomGen, Frame},
+    extension::vector_ext::OrthonormalVectorExt,
+    sampler::Sampler,
+    scene::{obj::{SceneObjectError, SceneObjectIntersection}, texture::Texture}
+};
+
+pub mod microfacet;
+use microfacet::{AnisotropicGgx, schlick};
+
+pub mod thin_film;
+
+pub mod principled;
+use principled::PrincipledParameters;
+
+pub mod subsurface;
+
+/// Fraction of the object color that is reflected on a diffuse bounce
+pub const DIFFUSE_SCALE: f64 = 0.1;
+
+/// Weight used by `SceneObjectMaterial::Mix` to blend two materials
+#[derive(Debug, Clone)]
+pub enum MixWeight {
+    /// Same weight over the whole surface
+    Constant(f64),
+    /// Average of the channels of a texture, evaluated on the hit point
+    Texture(Arc<dyn Texture>)
+}
+
+impl MixWeight {
+    /// Weight of the second material at the hit point, clamped to `[0, 1]`
+    pub fn value(&self, intersection: &SceneObjectIntersection) -> f64 {
+        match self {
+            MixWeight::Constant(w) => w.clamp(0., 1.),
+            MixWeight::Texture(texture) => texture.evaluate(
+                &intersection.hit_point(),
+                &intersection.normal(),
+                &intersection.uv()
+            ).mean().clamp(0., 1.)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SceneObjectMaterial {
+    Diffuse,
+    Specular,
+    Refractive,
+    /// Blend of two materials, `weight` is the fraction of `second`
+    Mix {
+        first: Box<SceneObjectMaterial>,
+        second: Box<SceneObjectMaterial>,
+        weight: MixWeight
+    },
+    /// Thin transparent coating over a `Specular` or `Refractive` material,
+    /// `thickness` is in nanometers and `ior` is the refraction index of the film
+    ThinFilm {
+        base: Box<SceneObjectMaterial>,
+        thickness: f64,
+        ior: f64
+    },
+    /// Glossy reflection with different roughness along the tangent and bitangent of the surface,
+    /// such as brushed metal, tinted by the color of the object
+    Anisotropic {
+        roughness_u: f64,
+        roughness_v: f64,
+        rotation: f64
+    },
+    /// Artist friendly material combining diffuse, metallic, sheen, clearcoat, and transmission lobes,
+    /// the base color is the color of the object
+    Principled(PrincipledParameters),
+    /// Invisible surface that only bounds a medium, rays cross it without changing direction
+    Interface,
+    /// Translucent material that scatters light under the surface of closed objects, like skin, wax, or marble
+    ///
+    /// Rendered with `subsurface::scatter`, its BSDF is always zero.
+    Subsurface {
+        mean_free_path: nalgebra_glm::DVec3,
+        albedo: nalgebra_glm::DVec3
+    }
+}
+
+impl SceneObjectMaterial {
+    /// Creates a material that behaves as `first` with probability `1 - weight`
+    /// and as `second` with probability `weight`
+    pub fn mix(first: SceneObjectMaterial, second: SceneObjectMaterial, weight: f64) -> Self {
+        SceneObjectMaterial::Mix {
+            first: Box::new(first),
+            second: Box::new(second),
+            weight: MixWeight::Constant(weight)
+        }
+    }
+
+    /// Creates a material that behaves as `second` where a texture is bright and as `first` where it's dark,
+    /// the weight is the average of the channels of `weight`
+    pub fn mix_with_texture(first: SceneObjectMaterial, second: SceneObjectMaterial, weight: Arc<dyn Texture>) -> Self {
+        SceneObjectMaterial::Mix {
+            first: Box::new(first),
+            second: Box::new(second),
+            weight: MixWeight::Texture(weight)
+        }
+    }
+
+    /// Creates a material that coats `base` with a thin film, which causes interference
+    /// on the reflected light
+    ///
+    /// # Arguments
+    /// * `base` - material under the film, must be `Specular` or `Refractive`
+    /// * `thickness` - thickness of the film, in nanometers
+    /// * `ior` - refraction index of the film
+    pub fn thin_film(base: SceneObjectMaterial, thickness: f64, ior: f64) -> Result<Self, SceneObjectError> {
+        if matches!(base, SceneObjectMaterial::Specular | SceneObjectMaterial::Refractive) {
+            Ok(
+                SceneObjectMaterial::ThinFilm {
+                    base: Box::new(base),
+                    thickness,
+                    ior
+                }
+            )
+        } else {
+            Err(SceneObjectError::ThinFilmBaseMaterialError)
+        }
+    }
+
+    /// Resolves `Mix` materials stochastically at the hit point, the returned material is never a `Mix`
+    pub fn select(&self, intersection: &SceneObjectIntersection) -> &SceneObjectMaterial {
+        match self {
+            SceneObjectMaterial::Mix { first, second, weight } => {
+                if RandomGen::rand2() < weight.value(intersection) {
+                    second.select(intersection)
+                } else {
+                    first.select(intersection)
+                }
+            },
+            _ => self
+        }
+    }
+
+    /// Creates a subsurface scattering material
+    ///
+    /// # Arguments
+    /// * `mean_free_path` - average distance the light travels under the surface between scattering events, per color channel
+    /// * `albedo` - fraction of the light scattered on each event, per color channel
+    pub fn subsurface(mean_free_path: nalgebra_glm::DVec3, albedo: nalgebra_glm::DVec3) -> Self {
+        SceneObjectMaterial::Subsurface {
+            mean_free_path,
+            albedo
+        }
+    }
+
+    /// Creates a glossy reflective material with different roughness along the tangent and the bitangent
+    ///
+    /// # Arguments
+    /// * `roughness_u` - roughness along the tangent, in `[0, 1]`
+    /// * `roughness_v` - roughness along the bitangent, in `[0, 1]`
+    /// * `rotation` - angle, in radians, by which the tangent is rotated around the normal
+    pub fn anisotropic(roughness_u: f64, roughness_v: f64, rotation: f64) -> Self {
+        SceneObjectMaterial::Anisotropic {
+            roughness_u,
+            roughness_v,
+            rotation
+        }
+    }
+
+    /// Checks if the material, or any of its components, transmits light
+    pub fn has_refraction(&self) -> bool {
+        match self {
+            SceneObjectMaterial::Refractive | SceneObjectMaterial::Interface | SceneObjectMaterial::Subsurface { .. } => true,
+            SceneObjectMaterial::Mix { first, second, weight: _ } => first.has_refraction() || second.has_refraction(),
+            SceneObjectMaterial::ThinFilm { base, thickness: _, ior: _ } => base.has_refraction(),
+            SceneObjectMaterial::Principled(params) => params.has_refraction(),
+            _ => false
+        }
+    }
+
+    /// Checks if the material only scatters light into discrete directions
+    pub fn is_delta(&self) -> bool {
+        match self {
+            SceneObjectMaterial::Specular
+                | SceneObjectMaterial::Refractive
+                | SceneObjectMaterial::ThinFilm { .. }
+                | SceneObjectMaterial::Interface => true,
+            SceneObjectMaterial::Mix { first, second, weight: _ } => first.is_delta() && second.is_delta(),
+            _ => false
+        }
+    }
+
+    /// Evaluates the non-delta part of the BSDF
+    ///
+    /// # Arguments
+    /// * `intersection` - the hit point on the object
+    /// * `incoming` - direction of the ray that hit the object
+    /// * `outgoing` - direction of the bounce, leaving the surface
+    pub fn eval(
+        &self,
+        intersection: &SceneObjectIntersection,
+        incoming: &nalgebra_glm::DVec3,
+        outgoing: &nalgebra_glm::DVec3
+    ) -> nalgebra_glm::DVec3 {
+        match self {
+            SceneObjectMaterial::Diffuse => {
+                if intersection.shading_normal(incoming).dot(outgoing) > 0. {
+                    intersection.color() * (DIFFUSE_SCALE / (2. * std::f64::consts::PI))
+                } else {
+                    nalgebra_glm::zero()
+                }
+            },
+            SceneObjectMaterial::Specular
+                | SceneObjectMaterial::Refractive
+                | SceneObjectMaterial::ThinFilm { .. }
+                | SceneObjectMaterial::Interface
+                | SceneObjectMaterial::Subsurface { .. } => nalgebra_glm::zero(),
+            SceneObjectMaterial::Mix { first, second, weight } => {
+                let w = weight.value(intersection);
+                first.eval(intersection, incoming, outgoing) * (1. - w) + second.eval(intersection, incoming, outgoing) * w
+            },
+            SceneObjectMaterial::Anisotropic { roughness_u, roughness_v, rotation } => {
+                let frame = shading_frame(intersection, incoming).rotated(*rotation);
+                let wi = frame.to_local(&-incoming);
+                let wo = frame.to_local(outgoing);
+                let ggx = AnisotropicGgx::new(*roughness_u, *roughness_v);
+                let fresnel = schlick(&reflectance_tint(&intersection.color()), wi.dot(&(wi + wo).normalize()));
+                fresnel * ggx.reflection(&wi, &wo)
+            },
+            SceneObjectMaterial::Principled(params) => {
+                let frame = shading_frame(intersection, incoming);
+                params.eval(&intersection.color(), &frame.to_local(&-incoming), &frame.to_local(outgoing))
+            }
+        }
+    }
+
+    /// Solid angle density with which `sample` returns `outgoing`, ignoring delta bounces
+    ///
+    /// Diffuse bounces are sampled uniformly over the hemisphere.
+    pub fn pdf(
+        &self,
+        intersection: &SceneObjectIntersection,
+        incoming: &nalgebra_glm::DVec3,
+        outgoing: &nalgebra_glm::DVec3
+    ) -> f64 {
+        match self {
+            SceneObjectMaterial::Diffuse => {
+                if intersection.shading_normal(incoming).dot(outgoing) > 0. {
+                    1. / (2. * std::f64::consts::PI)
+                } else {
+                    0.
+                }
+            },
+            SceneObjectMaterial::Specular
+                | SceneObjectMaterial::Refractive
+                | SceneObjectMaterial::ThinFilm { .. }
+                | SceneObjectMaterial::Interface
+                | SceneObjectMaterial::Subsurface { .. } => 0.,
+            SceneObjectMaterial::Mix { first, second, weight } => {
+                let w = weight.value(intersection);
+                first.pdf(intersection, incoming, outgoing) * (1. - w) + second.pdf(intersection, incoming, outgoing) * w
+            },
+            SceneObjectMaterial::Anisotropic { roughness_u, roughness_v, rotation } => {
+                let frame = shading_frame(intersection, incoming).rotated(*rotation);
+                AnisotropicGgx::new(*roughness_u, *roughness_v)
+                    .reflection_pdf(&frame.to_local(&-incoming), &frame.to_local(outgoing))
+            },
+            SceneObjectMaterial::Principled(params) => {
+                let frame = shading_frame(intersection, incoming);
+                params.pdf(&frame.to_local(&-incoming), &frame.to_local(outgoing))
+            }
+        }
+    }
+
+    /// Samples the direction of the bounce
+    ///
+    /// # Arguments
+    /// * `intersection` - the hit point on the object
+    /// * `incoming` - direction of the ray that hit the object
+    /// * `refraction_index` - refraction index of refractive objects
+    /// * `sampler` - sampler used for diffuse bounces
+    pub fn sample(
+        &self,
+        intersection: &SceneObjectIntersection,
+        incoming: &nalgebra_glm::DVec3,
+        refraction_index: f64,
+        sampler: &dyn Sampler
+    ) -> Option<BsdfSample> {
+        let normal = intersection.shading_normal(incoming);
+        match self {
+            SceneObjectMaterial::Diffuse => {
+                let frame = Frame::new(normal, normal.orthonormal().0);
+                let direction = frame.to_world(&sampler.hemisphere().normalize());
+                Some(
+                    BsdfSample {
+                        weight: intersection.color() * (DIFFUSE_SCALE * direction.dot(&normal)),
+                        direction,
+                        pdf: 1. / (2. * std::f64::consts::PI),
+                        delta: false
+                    }
+                )
+            },
+            SceneObjectMaterial::Specular => {
+                let cost = incoming.dot(&normal);
+                Some(
+                    BsdfSample {
+                        direction: (incoming - normal * (cost * 2.)).normalize(),
+                        weight: nalgebra_glm::DVec3::from_element(1.),
+                        pdf: 0.,
+                        delta: true
+                    }
+                )
+            },
+            SceneObjectMaterial::Refractive => {
+                let refr = if intersection.normal().dot(incoming) > 0. { refraction_index } else { 1. / refraction_index };
+                let cost1 = -normal.dot(incoming);
+                let cost2 = 1.0 - refr.powi(2) * (1. - cost1.powi(2));
+                let r0 = ((1. - refraction_index) / (1. + refraction_index)).powi(2);
+                let refr_prob = r0 + (1. - r0) * (1. - cost1).powi(5);
+                Some(
+                    BsdfSample {
+                        direction: if cost2 > 0. && RandomGen::rand2() > refr_prob {
+                            (incoming * refr + (normal * (refr * cost1 - cost2.sqrt()))).normalize()
+                        } else {
+                            (incoming + normal * (cost1 * 2.)).normalize()
+                        },
+                        weight: nalgebra_glm::DVec3::from_element(1.),
+                        pdf: 0.,
+                        delta: true
+                    }
+                )
+            },
+            SceneObjectMaterial::ThinFilm { base, thickness, ior } => {
+                let (direction, weight) = thin_film::scatter(
+                    base,
+                    *thickness,
+                    *ior,
+                    incoming,
+                    &normal,
+                    intersection.normal().dot(incoming) < 0.,
+                    refraction_index
+                );
+                Some(
+                    BsdfSample {
+                        direction,
+                        weight,
+                        pdf: 0.,
+                        delta: true
+                    }
+                )
+            },
+            // Leaves from another point of the surface, see `subsurface::scatter`
+            SceneObjectMaterial::Subsurface { .. } => None,
+            SceneObjectMaterial::Interface => Some(
+                BsdfSample {
+                    direction: *incoming,
+                    weight: nalgebra_glm::DVec3::from_element(1.),
+                    pdf: 0.,
+                    delta: true
+                }
+            ),
+            SceneObjectMaterial::Mix { .. } => {
+                let sample = self.select(intersection).sample(intersection, incoming, refraction_index, sampler)?;
+                if sample.delta {
+                    Some(sample)
+                } else {
+                    // Weight by the whole mixture so that the estimate is consistent with `eval` and `pdf`
+                    let pdf = self.pdf(intersection, incoming, &sample.direction);
+                    let cost = sample.direction.dot(&normal).abs();
+                    Some(
+                        BsdfSample {
+                            weight: self.eval(intersection, incoming, &sample.direction) * (cost / pdf),
+                            pdf,
+                            ..sample
+                        }
+                    )
+                }
+            },
+            SceneObjectMaterial::Anisotropic { roughness_u, roughness_v, rotation } => {
+                let frame = Frame::new(normal, intersection.tangent()).rotated(*rotation);
+                let ggx = AnisotropicGgx::new(*roughness_u, *roughness_v);
+                let wi = frame.to_local(&-incoming);
+                let half = ggx.sample_half_vector(RandomGen::rand2(), RandomGen::rand2());
+                let wo = half * (2. * wi.dot(&half)) - wi;
+                if wo.z <= 0. || wi.z <= 0. {
+                    None
+                } else {
+                    let fresnel = schlick(&reflectance_tint(&intersection.color()), wi.dot(&half));
+                    Some(
+                        BsdfSample {
+                            direction: frame.to_world(&wo),
+                            weight: fresnel * (ggx.masking_shadowing(&wi, &wo) * wi.dot(&half) / (wi.z * half.z)),
+                            pdf: ggx.reflection_pdf(&wi, &wo),
+                            delta: false
+                        }
+                    )
+                }
+            },
+            SceneObjectMaterial::Principled(params) => params.sample(
+                &intersection.color(),
+                &Frame::new(normal, intersection.tangent()),
+                incoming,
+                intersection.normal().dot(incoming) < 0.,
+                refraction_index,
+                sampler
+            )
+        }
+    }
+}
+
+/// Direction sampled by `SceneObjectMaterial::sample`
+#[derive(Debug, Clone)]
+pub struct BsdfSample {
+    /// Direction of the bounce
+    pub direction: nalgebra_glm::DVec3,
+    /// Light carried by the bounce, BSDF times cosine divided by `pdf`
+    pub weight: nalgebra_glm::DVec3,
+    /// Solid angle density of `direction`, `0` for delta bounces
+    pub pdf: f64,
+    /// If the bounce was sampled from a delta distribution (mirror or glass)
+    pub delta: bool
+}
+
+/// Converts an object color into a reflectance in `[0, 1]`
+pub fn reflectance_tint(color: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec3 {
+    (color * DIFFUSE_SCALE).map(|c| c.clamp(0., 1.))
+}
+
+/// Surface normal flipped to the side the ray came from
+fn shading_frame(intersection: &SceneObjectIntersection, incoming: &nalgebra_glm::DVec3) -> Frame {
+    Frame::new(intersection.shading_normal(incoming), intersection.tangent())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sampler::RandomSampler, scene::obj::SceneObject};
+
+    fn test_object(material: SceneObjectMaterial) -> SceneObject {
+        SceneObject::new_sphere(
+            nalgebra_glm::DVec3::new(4., 8., 4.),
+            nalgebra_glm::zero(),
+            material,
+            nalgebra_glm::zero(),
+            1.
+        )
+    }
+
+    fn test_intersection(object: &SceneObject) -> SceneObjectIntersection<'_> {
+        SceneObjectIntersection::new(
+            object,
+            nalgebra_glm::DVec3::new(0., 0., 1.),
+            nalgebra_glm::DVec3::new(0., 0., 1.),
+            1.
+        )
+    }
+
+    #[test]
+    fn mix_select_never_returns_mix() {
+        let object = test_object(SceneObjectMaterial::mix(
+            SceneObjectMaterial::Diffuse,
+            SceneObjectMaterial::mix(SceneObjectMaterial::Specular, SceneObjectMaterial::Refractive, 0.5),
+            0.5
+        ));
+        let intersection = test_intersection(&object);
+        for _ in 0..1_000 {
+            assert!(!matches!(object.material().select(&intersection), SceneObjectMaterial::Mix { .. }));
+        }
+    }
+
+    #[test]
+    fn mix_select_frequency() {
+        let object = test_object(SceneObjectMaterial::mix(SceneObjectMaterial::Diffuse, SceneObjectMaterial::Specular, 0.3));
+        let intersection = test_intersection(&object);
+        let specular = (0..100_000)
+            .filter(|_| matches!(object.material().select(&intersection), SceneObjectMaterial::Specular))
+            .count();
+        approx::assert_abs_diff_eq!(specular as f64 / 100_000., 0.3, epsilon = 0.01);
+    }
+
+    #[test]
+    fn mix_texture_weight() {
+        use crate::scene::texture::{CheckerboardTexture, TextureSpace};
+
+        let object = test_object(SceneObjectMaterial::mix_with_texture(
+            SceneObjectMaterial::Diffuse,
+            SceneObjectMaterial::Specular,
+            Arc::new(CheckerboardTexture::new(
+                nalgebra_glm::DVec3::from_element(1.),
+                nalgebra_glm::zero(),
+                1.,
+                TextureSpace::Position
+            ))
+        ));
+        let diffuse = test_object(SceneObjectMaterial::Diffuse);
+        let normal = nalgebra_glm::DVec3::new(0., 0., 1.);
+        let incoming = nalgebra_glm::DVec3::new(0., 0., -1.);
+        let outgoing = nalgebra_glm::DVec3::new(0., 0.6, 0.8);
+        // Specular on the even cells of the checkerboard
+        let specular = SceneObjectIntersection::new(&object, nalgebra_glm::DVec3::new(0.5, 0.5, 0.5), normal, 1.);
+        assert!((0..1_000).all(|_| matches!(object.material().select(&specular), SceneObjectMaterial::Specular)));
+        approx::assert_abs_diff_eq!(object.material().eval(&specular, &incoming, &outgoing), nalgebra_glm::DVec3::zeros());
+        approx::assert_abs_diff_eq!(object.material().pdf(&specular, &incoming, &outgoing), 0.);
+        // Diffuse on the odd ones
+        let point = nalgebra_glm::DVec3::new(0.5, 0.5, 1.5);
+        let odd = SceneObjectIntersection::new(&object, point, normal, 1.);
+        assert!((0..1_000).all(|_| matches!(object.material().select(&odd), SceneObjectMaterial::Diffuse)));
+        let expected = SceneObjectIntersection::new(&diffuse, point, normal, 1.);
+        approx::assert_abs_diff_eq!(
+            object.material().eval(&odd, &incoming, &outgoing),
+            diffuse.material().eval(&expected, &incoming, &outgoing)
+        );
+        approx::assert_abs_diff_eq!(
+            object.material().pdf(&odd, &incoming, &outgoing),
+            diffuse.material().pdf(&expected, &incoming, &outgoing)
+        );
+    }
+
+    #[test]
+    fn mix_eval_is_weighted_sum() {
+        let incoming = nalgebra_glm::DVec3::new(0., 0., -1.);
+        let outgoing = nalgebra_glm::DVec3::new(0., 0.6, 0.8);
+        let mix = test_object(SceneObjectMaterial::mix(SceneObjectMaterial::Diffuse, SceneObjectMaterial::Specular, 0.3));
+        let diffuse = test_object(SceneObjectMaterial::Diffuse);
+        approx::assert_abs_diff_eq!(
+            mix.material().eval(&test_intersection(&mix), &incoming, &outgoing),
+            diffuse.material().eval(&test_intersection(&diffuse), &incoming, &outgoing) * 0.7
+        );
+        approx::assert_abs_diff_eq!(
+            mix.material().pdf(&test_intersection(&mix), &incoming, &outgoing),
+            diffuse.material().pdf(&test_intersection(&diffuse), &incoming, &outgoing) * 0.7
+        );
+    }
+
+    #[test]
+    fn mix_sample_matches_eval() {
+        let incoming = nalgebra_glm::DVec3::new(0., 0.6, -0.8);
+        let object = test_object(
+            SceneObjectMaterial::mix(SceneObjectMaterial::Diffuse, SceneObjectMaterial::anisotropic(0.3, 0.6, 0.), 0.5)
+        );
+        let intersection = test_intersection(&object);
+        let sampler = RandomSampler::new();
+        for _ in 0..1_000 {
+            if let Some(sample) = object.material().sample(&intersection, &incoming, 1.5, &sampler) {
+                let expected = object.material().eval(&intersection, &incoming, &sample.direction)
+                    * (sample.direction.z / object.material().pdf(&intersection, &incoming, &sample.direction));
+                approx::assert_relative_eq!(sample.weight, expected, max_relative = 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn anisotropic_sample_matches_eval() {
+        let incoming = nalgebra_glm::DVec3::new(0.3, 0.4, -0.5).normalize();
+        let object = test_object(SceneObjectMaterial::anisotropic(0.2, 0.7, 0.4));
+        let intersection = test_intersection(&object);
+        let sampler = RandomSampler::new();
+        for _ in 0..1_000 {
+            if let Some(sample) = object.material().sample(&intersection, &incoming, 1.5, &sampler) {
+                assert!(sample.direction.z > 0.);
+                let expected = object.material().eval(&intersection, &incoming, &sample.direction)
+                    * (sample.direction.z / sample.pdf);
+                approx::assert_relative_eq!(sample.weight, expected, max_relative = 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn anisotropic_reflection_conserves_energy() {
+        let incoming = nalgebra_glm::DVec3::new(0.3, 0.4, -0.5).normalize();
+        let object = test_object(SceneObjectMaterial::anisotropic(0.2, 0.7, 0.4));
+        let intersection = test_intersection(&object);
+        let sampler = RandomSampler::new();
+        let albedo = (0..10_000)
+            .filter_map(|_| object.material().sample(&intersection, &incoming, 1.5, &sampler))
+            .fold(nalgebra_glm::DVec3::zeros(), |acc, sample| acc + sample.weight) / 10_000.;
+        assert!(albedo.iter().all(|a| *a <= 1.), "{albedo:?}");
+    }
+
+    #[test]
+    fn has_refraction() {
+        assert!(!SceneObjectMaterial::Diffuse.has_refraction());
+        assert!(SceneObjectMaterial::Refractive.has_refraction());
+        assert!(SceneObjectMaterial::mix(SceneObjectMaterial::Diffuse, SceneObjectMaterial::Refractive, 0.1).has_refraction());
+        assert!(SceneObjectMaterial::thin_film(SceneObjectMaterial::Refractive, 300., 1.3).unwrap().has_refraction());
+        assert!(!SceneObjectMaterial::thin_film(SceneObjectMaterial::Specular, 300., 1.3).unwrap().has_refraction());
+    }
+
+    #[test]
+    fn thin_film_base_constraint() {
+        assert!(matches!(
+            SceneObjectMaterial::thin_film(SceneObjectMaterial::Diffuse, 300., 1.3),
+            Err(SceneObjectError::ThinFilmBaseMaterialError)
+        ));
+    }
+}
\ No newline at end of file
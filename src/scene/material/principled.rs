@@ -0,0 +1,416 @@
+use crate::{common::{RandomGen, Frame}, sampler::Sampler};
+
+use super::{
+    BsdfSample,
+    DIFFUSE_SCALE,
+    SceneObjectMaterial,
+    microfacet::{AnisotropicGgx, schlick},
+    reflectance_tint
+};
+
+/// Reflectance of the clearcoat layer at normal incidence
+const CLEARCOAT_F0: f64 = 0.04;
+
+/// Parameters of a principled (Disney style) material, all in `[0, 1]`
+///
+/// The base color is the color of the object.
+#[derive(Debug, Clone)]
+pub struct PrincipledParameters {
+    /// Blends between a dielectric and a metal, metals are tinted by the base color
+    pub metallic: f64,
+    /// Roughness of the specular reflection
+    pub roughness: f64,
+    /// Strength of the dielectric specular reflection, `0.5` is a reflectance of 4% at normal incidence
+    pub specular: f64,
+    /// Retro-reflection at grazing angles, for cloth
+    pub sheen: f64,
+    /// Strength of a second, untinted, specular layer
+    pub clearcoat: f64,
+    /// Roughness of the clearcoat layer
+    pub clearcoat_roughness: f64,
+    /// Blends between an opaque and a glass-like dielectric
+    pub transmission: f64
+}
+
+impl Default for PrincipledParameters {
+    fn default() -> Self {
+        Self {
+            metallic: 0.,
+            roughness: 0.5,
+            specular: 0.5,
+            sheen: 0.,
+            clearcoat: 0.,
+            clearcoat_roughness: 0.1,
+            transmission: 0.
+        }
+    }
+}
+
+/// Parameters of a material from an OBJ's MTL file, including the PBR extension
+#[derive(Debug, Clone)]
+pub struct MtlParameters {
+    /// `Kd`
+    pub diffuse: nalgebra_glm::DVec3,
+    /// `Ks`
+    pub specular: nalgebra_glm::DVec3,
+    /// `Ns`
+    pub specular_exponent: f64,
+    /// `d`
+    pub dissolve: f64,
+    /// `Pr`
+    pub roughness: Option<f64>,
+    /// `Pm`
+    pub metallic: Option<f64>,
+    /// `Ps`
+    pub sheen: Option<f64>,
+    /// `Pc`
+    pub clearcoat: Option<f64>,
+    /// `Pcr`
+    pub clearcoat_roughness: Option<f64>
+}
+
+impl Default for MtlParameters {
+    fn default() -> Self {
+        Self {
+            diffuse: nalgebra_glm::DVec3::from_element(0.8),
+            specular: nalgebra_glm::DVec3::from_element(0.5),
+            specular_exponent: 10.,
+            dissolve: 1.,
+            roughness: None,
+            metallic: None,
+            sheen: None,
+            clearcoat: None,
+            clearcoat_roughness: None
+        }
+    }
+}
+
+/// Parameters of a glTF metallic-roughness material, including the
+/// `KHR_materials_specular`, `KHR_materials_sheen`, `KHR_materials_clearcoat`,
+/// and `KHR_materials_transmission` extensions
+#[derive(Debug, Clone)]
+pub struct GltfParameters {
+    pub base_color_factor: nalgebra_glm::DVec3,
+    pub metallic_factor: f64,
+    pub roughness_factor: f64,
+    pub specular_factor: f64,
+    pub sheen_color_factor: nalgebra_glm::DVec3,
+    pub clearcoat_factor: f64,
+    pub clearcoat_roughness_factor: f64,
+    pub transmission_factor: f64
+}
+
+impl Default for GltfParameters {
+    fn default() -> Self {
+        Self {
+            base_color_factor: nalgebra_glm::DVec3::from_element(1.),
+            metallic_factor: 1.,
+            roughness_factor: 1.,
+            specular_factor: 1.,
+            sheen_color_factor: nalgebra_glm::zero(),
+            clearcoat_factor: 0.,
+            clearcoat_roughness_factor: 0.,
+            transmission_factor: 0.
+        }
+    }
+}
+
+/// Converts a reflectance in `[0, 1]` into the color of an object
+pub fn color_from_reflectance(reflectance: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec3 {
+    reflectance / DIFFUSE_SCALE
+}
+
+fn luminance(color: &nalgebra_glm::DVec3) -> f64 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
+/// Lobes of the principled material
+#[derive(Clone, Copy)]
+enum Lobe {
+    Diffuse,
+    Specular,
+    Clearcoat,
+    Transmission
+}
+
+impl PrincipledParameters {
+    /// Creates the color of the object and a principled material from the parameters of a MTL file
+    pub fn from_mtl(mtl: &MtlParameters) -> (nalgebra_glm::DVec3, SceneObjectMaterial) {
+        // Phong exponent to microfacet roughness
+        let phong_roughness = (2. / (mtl.specular_exponent.max(0.) + 2.)).sqrt().sqrt();
+        let metallic = mtl.metallic.unwrap_or(0.);
+        (
+            color_from_reflectance(&if metallic > 0. { mtl.diffuse.lerp(&mtl.specular, metallic) } else { mtl.diffuse }),
+            SceneObjectMaterial::Principled(
+                Self {
+                    metallic,
+                    roughness: mtl.roughness.unwrap_or(phong_roughness),
+                    specular: (luminance(&mtl.specular) / 0.08).clamp(0., 1.),
+                    sheen: mtl.sheen.unwrap_or(0.),
+                    clearcoat: mtl.clearcoat.unwrap_or(0.),
+                    clearcoat_roughness: mtl.clearcoat_roughness.unwrap_or(0.1),
+                    transmission: 1. - mtl.dissolve.clamp(0., 1.)
+                }
+            )
+        )
+    }
+
+    /// Creates the color of the object and a principled material from the parameters of a glTF material
+    pub fn from_gltf(gltf: &GltfParameters) -> (nalgebra_glm::DVec3, SceneObjectMaterial) {
+        (
+            color_from_reflectance(&gltf.base_color_factor),
+            SceneObjectMaterial::Principled(
+                Self {
+                    metallic: gltf.metallic_factor,
+                    roughness: gltf.roughness_factor,
+                    specular: gltf.specular_factor * 0.5,
+                    sheen: gltf.sheen_color_factor.max(),
+                    clearcoat: gltf.clearcoat_factor,
+                    clearcoat_roughness: gltf.clearcoat_roughness_factor,
+                    transmission: gltf.transmission_factor
+                }
+            )
+        )
+    }
+
+    fn specular_f0(&self, tint: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec3 {
+        nalgebra_glm::DVec3::from_element(0.08 * self.specular.clamp(0., 1.)).lerp(tint, self.metallic.clamp(0., 1.))
+    }
+
+    fn specular_lobe(&self) -> AnisotropicGgx {
+        AnisotropicGgx::new(self.roughness, self.roughness)
+    }
+
+    fn clearcoat_lobe(&self) -> AnisotropicGgx {
+        AnisotropicGgx::new(self.clearcoat_roughness, self.clearcoat_roughness)
+    }
+
+    /// Probabilities of sampling each lobe
+    fn lobe_probabilities(&self) -> [(Lobe, f64); 4] {
+        let metallic = self.metallic.clamp(0., 1.);
+        let transmission = self.transmission.clamp(0., 1.);
+        let weights = [
+            (1. - metallic) * (1. - transmission),
+            1.,
+            0.25 * self.clearcoat.clamp(0., 1.),
+            (1. - metallic) * transmission
+        ];
+        let total: f64 = weights.iter().sum();
+        [
+            (Lobe::Diffuse, weights[0] / total),
+            (Lobe::Specular, weights[1] / total),
+            (Lobe::Clearcoat, weights[2] / total),
+            (Lobe::Transmission, weights[3] / total)
+        ]
+    }
+
+    /// Checks if the material transmits light
+    pub fn has_refraction(&self) -> bool {
+        self.transmission > 0. && self.metallic < 1.
+    }
+
+    /// Evaluates the non-delta lobes, directions are in local space and point away from the surface
+    pub fn eval(
+        &self,
+        color: &nalgebra_glm::DVec3,
+        incoming: &nalgebra_glm::DVec3,
+        outgoing: &nalgebra_glm::DVec3
+    ) -> nalgebra_glm::DVec3 {
+        if incoming.z <= 0. || outgoing.z <= 0. {
+            return nalgebra_glm::zero();
+        }
+        let half = (incoming + outgoing).normalize();
+        let cos_d = incoming.dot(&half);
+        let dielectric = (1. - self.metallic.clamp(0., 1.)) * (1. - self.transmission.clamp(0., 1.));
+        let diffuse = color * (dielectric * DIFFUSE_SCALE / (2. * std::f64::consts::PI));
+        let sheen = nalgebra_glm::DVec3::from_element(
+            (1. - self.metallic.clamp(0., 1.)) * self.sheen * (1. - cos_d).powi(5) / (2. * std::f64::consts::PI)
+        );
+        let specular = schlick(&self.specular_f0(&reflectance_tint(color)), cos_d)
+            * self.specular_lobe().reflection(incoming, outgoing);
+        let clearcoat = schlick(&nalgebra_glm::DVec3::from_element(CLEARCOAT_F0), cos_d)
+            * (0.25 * self.clearcoat * self.clearcoat_lobe().reflection(incoming, outgoing));
+        diffuse + sheen + specular + clearcoat
+    }
+
+    /// Solid angle density of sampling `outgoing` from the non-delta lobes
+    pub fn pdf(&self, incoming: &nalgebra_glm::DVec3, outgoing: &nalgebra_glm::DVec3) -> f64 {
+        if incoming.z <= 0. || outgoing.z <= 0. {
+            return 0.;
+        }
+        self.lobe_probabilities().into_iter()
+            .map(
+                |(lobe, prob)| prob * match lobe {
+                    Lobe::Diffuse => 1. / (2. * std::f64::consts::PI),
+                    Lobe::Specular => self.specular_lobe().reflection_pdf(incoming, outgoing),
+                    Lobe::Clearcoat => self.clearcoat_lobe().reflection_pdf(incoming, outgoing),
+                    Lobe::Transmission => 0.
+                }
+            )
+            .sum()
+    }
+
+    /// Samples one of the lobes
+    ///
+    /// # Arguments
+    /// * `color` - color of the object
+    /// * `frame` - shading frame, with the normal facing the incoming ray
+    /// * `incoming` - direction of the ray that hit the object
+    /// * `entering` - if the ray is entering the object
+    /// * `refraction_index` - refraction index of the object
+    /// * `sampler` - sampler used for the diffuse lobe
+    pub fn sample(
+        &self,
+        color: &nalgebra_glm::DVec3,
+        frame: &Frame,
+        incoming: &nalgebra_glm::DVec3,
+        entering: bool,
+        refraction_index: f64,
+        sampler: &dyn Sampler
+    ) -> Option<BsdfSample> {
+        let wi = frame.to_local(&-incoming);
+        if wi.z <= 0. {
+            return None;
+        }
+        let probabilities = self.lobe_probabilities();
+        let mut u = RandomGen::rand2();
+        let (lobe, prob) = probabilities.into_iter()
+            .find(
+                |(_, prob)| {
+                    if u < *prob {
+                        true
+                    } else {
+                        u -= prob;
+                        false
+                    }
+                }
+            )
+            .unwrap_or((Lobe::Specular, probabilities[1].1));
+        let wo = match lobe {
+            Lobe::Diffuse => sampler.hemisphere().normalize(),
+            Lobe::Specular | Lobe::Clearcoat => {
+                let ggx = if matches!(lobe, Lobe::Specular) { self.specular_lobe() } else { self.clearcoat_lobe() };
+                let half = ggx.sample_half_vector(RandomGen::rand2(), RandomGen::rand2());
+                half * (2. * wi.dot(&half)) - wi
+            },
+            Lobe::Transmission => {
+                let refr = if entering { 1. / refraction_index } else { refraction_index };
+                let cost1 = wi.z;
+                let cost2 = 1. - refr.powi(2) * (1. - cost1.powi(2));
+                let r0 = ((1. - refraction_index) / (1. + refraction_index)).powi(2);
+                let fresnel = r0 + (1. - r0) * (1. - cost1).powi(5);
+                let transmission = (1. - self.metallic.clamp(0., 1.)) * self.transmission.clamp(0., 1.) / prob;
+                return Some(
+                    if cost2 > 0. {
+                        BsdfSample {
+                            direction: frame.to_world(&(wi * -refr + nalgebra_glm::DVec3::new(0., 0., refr * cost1 - cost2.sqrt()))).normalize(),
+                            weight: reflectance_tint(color) * ((1. - fresnel) * transmission),
+                            pdf: 0.,
+                            delta: true
+                        }
+                    } else {
+                        BsdfSample {
+                            direction: frame.to_world(&nalgebra_glm::DVec3::new(-wi.x, -wi.y, wi.z)),
+                            weight: nalgebra_glm::DVec3::from_element(transmission),
+                            pdf: 0.,
+                            delta: true
+                        }
+                    }
+                );
+            }
+        };
+        if wo.z <= 0. {
+            None
+        } else {
+            let pdf = self.pdf(&wi, &wo);
+            Some(
+                BsdfSample {
+                    direction: frame.to_world(&wo),
+                    weight: self.eval(color, &wi, &wo) * (wo.z / pdf),
+                    pdf,
+                    delta: false
+                }
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::RandomSampler;
+
+    fn albedo(params: &PrincipledParameters, color: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec3 {
+        let frame = Frame::new(nalgebra_glm::DVec3::new(0., 0., 1.), nalgebra_glm::DVec3::new(1., 0., 0.));
+        let incoming = nalgebra_glm::DVec3::new(0.3, 0., -0.7).normalize();
+        let sampler = RandomSampler::new();
+        (0..10_000)
+            .filter_map(|_| params.sample(color, &frame, &incoming, true, 1.5, &sampler))
+            .fold(nalgebra_glm::DVec3::zeros(), |acc, sample| acc + sample.weight) / 10_000.
+    }
+
+    #[test]
+    fn sample_matches_eval() {
+        let params = PrincipledParameters {
+            metallic: 0.3,
+            roughness: 0.4,
+            sheen: 0.5,
+            clearcoat: 0.7,
+            transmission: 0.2,
+            ..Default::default()
+        };
+        let color = nalgebra_glm::DVec3::new(4., 8., 4.);
+        let frame = Frame::new(nalgebra_glm::DVec3::new(0., 0., 1.), nalgebra_glm::DVec3::new(1., 0., 0.));
+        let incoming = nalgebra_glm::DVec3::new(0.3, 0.2, -0.7).normalize();
+        let sampler = RandomSampler::new();
+        for _ in 0..1_000 {
+            if let Some(sample) = params.sample(&color, &frame, &incoming, true, 1.5, &sampler) {
+                if !sample.delta {
+                    let wi = frame.to_local(&-incoming);
+                    let wo = frame.to_local(&sample.direction);
+                    approx::assert_relative_eq!(sample.weight, params.eval(&color, &wi, &wo) * (wo.z / sample.pdf), max_relative = 1e-9);
+                    approx::assert_relative_eq!(sample.pdf, params.pdf(&wi, &wo), max_relative = 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn metal_conserves_energy() {
+        let params = PrincipledParameters { metallic: 1., roughness: 0.3, ..Default::default() };
+        let a = albedo(&params, &nalgebra_glm::DVec3::from_element(9.));
+        assert!(a.iter().all(|c| *c <= 1.), "{a:?}");
+        assert!(a.iter().all(|c| *c >= 0.5), "{a:?}");
+    }
+
+    #[test]
+    fn rough_dielectric_looks_like_diffuse() {
+        let params = PrincipledParameters { specular: 0., ..Default::default() };
+        let color = nalgebra_glm::DVec3::new(4., 8., 4.);
+        // Same albedo as `SceneObjectMaterial::Diffuse`
+        approx::assert_relative_eq!(albedo(&params, &color), color * (DIFFUSE_SCALE / 2.), max_relative = 0.05);
+    }
+
+    #[test]
+    fn mtl_conversion() {
+        let (color, material) = PrincipledParameters::from_mtl(
+            &MtlParameters { dissolve: 0.25, specular_exponent: 1_000., ..Default::default() }
+        );
+        approx::assert_abs_diff_eq!(color, nalgebra_glm::DVec3::from_element(8.), epsilon = 1e-9);
+        let SceneObjectMaterial::Principled(params) = material else { panic!("Expected `Principled`") };
+        approx::assert_abs_diff_eq!(params.transmission, 0.75);
+        assert!(params.roughness < 0.3);
+        assert!(params.has_refraction());
+    }
+
+    #[test]
+    fn gltf_conversion() {
+        let (color, material) = PrincipledParameters::from_gltf(
+            &GltfParameters { base_color_factor: nalgebra_glm::DVec3::new(1., 0.5, 0.), metallic_factor: 0., ..Default::default() }
+        );
+        approx::assert_abs_diff_eq!(color, nalgebra_glm::DVec3::new(10., 5., 0.), epsilon = 1e-9);
+        let SceneObjectMaterial::Principled(params) = material else { panic!("Expected `Principled`") };
+        approx::assert_abs_diff_eq!(params.specular, 0.5);
+        assert!(!params.has_refraction());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,158 @@
+use crate::common::RandomGen;
+
+use super::SceneObjectMaterial;
+
+/// Wavelengths, in nanometers, used for the red, green, and blue channels
+pub const CHANNEL_WAVELENGTHS: [f64; 3] = [650., 532., 450.];
+
+/// Amplitude of the light reflected by a `Specular` surface under a thin film
+///
+/// A perfect mirror under a lossless film reflects everything regardless of the interference,
+/// so `Specular` bases are treated as a conductor that absorbs a small part of the light.
+pub const MIRROR_AMPLITUDE: f64 = 0.95;
+
+/// Amplitude reflection coefficients for s and p polarized light
+fn fresnel_amplitudes(n1: f64, cos1: f64, n2: f64, cos2: f64) -> (f64, f64) {
+    (
+        (n1 * cos1 - n2 * cos2) / (n1 * cos1 + n2 * cos2),
+        (n2 * cos1 - n1 * cos2) / (n2 * cos1 + n1 * cos2)
+    )
+}
+
+/// Cosine of the refracted angle, or `None` if the light is totally reflected
+fn refracted_cosine(n1: f64, cos1: f64, n2: f64) -> Option<f64> {
+    let sin2_sq = (n1 / n2).powi(2) * (1. - cos1.powi(2));
+    if sin2_sq >= 1. {
+        None
+    } else {
+        Some((1. - sin2_sq).sqrt())
+    }
+}
+
+/// Reflectance of a single layer, summing all internal reflections (Airy summation)
+fn airy_reflectance(r12: f64, r23: f64, phase: f64) -> f64 {
+    let cross = 2. * r12 * r23 * phase.cos();
+    (r12.powi(2) + r23.powi(2) + cross) / (1. + (r12 * r23).powi(2) + cross)
+}
+
+/// Reflectance of a film on top of a substrate, for each color channel
+///
+/// # Arguments
+/// * `cos_incident` - cosine between the incident ray and the surface normal
+/// * `incident_ior` - refraction index of the medium the ray comes from
+/// * `film_ior` - refraction index of the film
+/// * `film_thickness` - thickness of the film, in nanometers
+/// * `substrate_ior` - refraction index of the medium under the film, `None` for a `Specular` substrate
+pub fn reflectance(
+    cos_incident: f64,
+    incident_ior: f64,
+    film_ior: f64,
+    film_thickness: f64,
+    substrate_ior: Option<f64>
+) -> nalgebra_glm::DVec3 {
+    let cos1 = cos_incident.abs().min(1.);
+    let Some(cos2) = refracted_cosine(incident_ior, cos1, film_ior) else {
+        return nalgebra_glm::DVec3::from_element(1.);
+    };
+    let (r12s, r12p) = fresnel_amplitudes(incident_ior, cos1, film_ior, cos2);
+    let (r23s, r23p) = match substrate_ior {
+        Some(substrate_ior) => {
+            let Some(cos3) = refracted_cosine(incident_ior, cos1, substrate_ior) else {
+                return nalgebra_glm::DVec3::from_element(1.);
+            };
+            fresnel_amplitudes(film_ior, cos2, substrate_ior, cos3)
+        },
+        None => (-MIRROR_AMPLITUDE, MIRROR_AMPLITUDE)
+    };
+    let optical_path = 4. * std::f64::consts::PI * film_ior * film_thickness * cos2;
+    let channel = |wavelength: f64| {
+        let phase = optical_path / wavelength;
+        (airy_reflectance(r12s, r23s, phase) + airy_reflectance(r12p, r23p, phase)) / 2.
+    };
+    nalgebra_glm::DVec3::new(
+        channel(CHANNEL_WAVELENGTHS[0]),
+        channel(CHANNEL_WAVELENGTHS[1]),
+        channel(CHANNEL_WAVELENGTHS[2])
+    )
+}
+
+/// Chooses between reflection and transmission on a surface coated by a thin film
+///
+/// Returns the direction of the bounce and the weight, per color channel, of the light it carries.
+///
+/// # Arguments
+/// * `base` - material under the film, either `Specular` or `Refractive`
+/// * `direction` - direction of the incoming ray
+/// * `normal` - surface normal facing the incoming ray
+/// * `entering` - if the ray is entering the object
+/// * `refraction_index` - refraction index of the object
+pub fn scatter(
+    base: &SceneObjectMaterial,
+    film_thickness: f64,
+    film_ior: f64,
+    direction: &nalgebra_glm::DVec3,
+    normal: &nalgebra_glm::DVec3,
+    entering: bool,
+    refraction_index: f64
+) -> (nalgebra_glm::DVec3, nalgebra_glm::DVec3) {
+    let cost1 = -normal.dot(direction);
+    let reflected = (direction + normal * (cost1 * 2.)).normalize();
+    match base {
+        SceneObjectMaterial::Refractive => {
+            let (n1, n3) = if entering { (1., refraction_index) } else { (refraction_index, 1.) };
+            let refl = reflectance(cost1, n1, film_ior, film_thickness, Some(n3));
+            let refl_prob = (refl.x + refl.y + refl.z) / 3.;
+            match refracted_cosine(n1, cost1, n3) {
+                // The film can reflect everything even if the substrate refracts
+                Some(cost2) if refl_prob < 1. && RandomGen::rand2() >= refl_prob => {
+                    let refr = n1 / n3;
+                    (
+                        (direction * refr + (normal * (refr * cost1 - cost2))).normalize(),
+                        (nalgebra_glm::DVec3::from_element(1.) - refl) / (1. - refl_prob)
+                    )
+                },
+                Some(_) => (reflected, refl / refl_prob),
+                None => (reflected, nalgebra_glm::DVec3::from_element(1.))
+            }
+        },
+        _ => {
+            let n1 = if entering { 1. } else { refraction_index };
+            (reflected, reflectance(cost1, n1, film_ior, film_thickness, None))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_film_matches_fresnel() {
+        let r0 = ((1. - 1.5) / (1. + 1.5_f64)).powi(2);
+        let refl = reflectance(1., 1., 1.3, 0., Some(1.5));
+        approx::assert_abs_diff_eq!(refl, nalgebra_glm::DVec3::from_element(r0), epsilon = 1e-9);
+    }
+
+    #[test]
+    fn quarter_wave_coating() {
+        let film_ior = 1.5_f64.sqrt();
+        let thickness = CHANNEL_WAVELENGTHS[1] / (4. * film_ior);
+        let refl = reflectance(1., 1., film_ior, thickness, Some(1.5));
+        approx::assert_abs_diff_eq!(refl.y, 0., epsilon = 1e-9);
+        assert!(refl.x > refl.y && refl.z > refl.y);
+    }
+
+    #[test]
+    fn coated_mirror_reflects_most_light() {
+        for thickness in [0., 100., 250., 400.] {
+            let refl = reflectance(0.7, 1., 1.33, thickness, None);
+            assert!(refl.iter().all(|r| (0.5..=1.).contains(r)), "{refl:?}");
+        }
+    }
+
+    #[test]
+    fn total_internal_reflection() {
+        let refl = reflectance(0.1, 1.5, 1.3, 300., Some(1.));
+        approx::assert_abs_diff_eq!(refl, nalgebra_glm::DVec3::from_element(1.));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,294 @@
+use crate::{common::{Ray, RandomGen}, scene::obj::Cuboid};
+
+use super::{Medium, MediumError, MediumEvent, HenyeyGreenstein};
+
+/// Densities on a regular 3D grid, with `x` changing the fastest and `z` the slowest
+#[derive(Debug, Clone)]
+pub struct DensityGrid {
+    resolution: [usize; 3],
+    values: Vec<f64>
+}
+
+impl DensityGrid {
+    pub fn new(resolution: [usize; 3], values: Vec<f64>) -> Result<Self, MediumError> {
+        let count = resolution.iter().try_fold(1_usize, |count, r| count.checked_mul(*r));
+        if resolution.contains(&0) || count != Some(values.len()) {
+            Err(MediumError::GridSizeError)
+        } else if values.iter().any(|v| !v.is_finite() || *v < 0.) {
+            Err(MediumError::GridFormatError)
+        } else {
+            Ok(
+                Self {
+                    resolution,
+                    values
+                }
+            )
+        }
+    }
+
+    /// Reads a text grid, the resolution along `x`, `y`, and `z` followed by the densities
+    ///
+    /// Values are separated by whitespace, and lines starting with `#` are comments.
+    pub fn read_text(mut reader: impl std::io::Read) -> Result<Self, MediumError> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        let mut tokens = text.lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .flat_map(str::split_whitespace);
+        let mut resolution = [0; 3];
+        for r in resolution.iter_mut() {
+            *r = tokens.next()
+                .and_then(|token| token.parse::<usize>().ok())
+                .ok_or(MediumError::GridFormatError)?;
+        }
+        let values = tokens.map(|token| token.parse::<f64>().map_err(|_| MediumError::GridFormatError))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(resolution, values)
+    }
+
+    /// Reads a raw grid of little endian `f32` densities
+    pub fn read_raw(mut reader: impl std::io::Read, resolution: [usize; 3]) -> Result<Self, MediumError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        if bytes.len() % 4 != 0 {
+            return Err(MediumError::GridSizeError);
+        }
+        let values = bytes.chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64)
+            .collect();
+        Self::new(resolution, values)
+    }
+
+    /// Loads a grid file, `.raw` files are read with `read_raw` and any other with `read_text`
+    pub fn from_file(path: &str, raw_resolution: [usize; 3]) -> Result<Self, MediumError> {
+        let file = std::fs::File::open(path)?;
+        if path.ends_with(".raw") {
+            Self::read_raw(file, raw_resolution)
+        } else {
+            Self::read_text(file)
+        }
+    }
+
+    pub fn resolution(&self) -> [usize; 3] {
+        self.resolution
+    }
+
+    pub fn max(&self) -> f64 {
+        self.values.iter().fold(0., |max: f64, v| max.max(*v))
+    }
+
+    fn value(&self, x: usize, y: usize, z: usize) -> f64 {
+        self.values[(z * self.resolution[1] + y) * self.resolution[0] + x]
+    }
+
+    /// Trilinear interpolation with `point` in `[0, 1]` over the grid, voxel centers hold the values
+    pub fn density(&self, point: &nalgebra_glm::DVec3) -> f64 {
+        let mut base = [0; 3];
+        let mut frac = [0.; 3];
+        for axis in 0..3 {
+            let max = self.resolution[axis] - 1;
+            let p = (point[axis] * self.resolution[axis] as f64 - 0.5).clamp(0., max as f64);
+            base[axis] = (p.floor() as usize).min(max.saturating_sub(1));
+            frac[axis] = p - base[axis] as f64;
+        }
+        let next = |axis: usize| (base[axis] + 1).min(self.resolution[axis] - 1);
+        let mut density = 0.;
+        for corner in 0..8 {
+            let pick = |axis: usize| corner >> axis & 1 == 1;
+            let weight = (0..3)
+                .map(|axis| if pick(axis) { frac[axis] } else { 1. - frac[axis] })
+                .product::<f64>();
+            if weight > 0. {
+                let index = |axis: usize| if pick(axis) { next(axis) } else { base[axis] };
+                density += weight * self.value(index(0), index(1), index(2));
+            }
+        }
+        density
+    }
+}
+
+/// Medium with density varying over a grid that fills a box, like clouds or smoke simulations
+///
+/// The coefficients are multiplied by the density of the grid, which is zero outside of the box.
+/// Distances are sampled with delta tracking and transmittance is estimated with ratio tracking.
+#[derive(Debug)]
+pub struct GridMedium {
+    grid: DensityGrid,
+    bounds: Cuboid,
+    absorption: nalgebra_glm::DVec3,
+    scattering: nalgebra_glm::DVec3,
+    phase: HenyeyGreenstein,
+    majorant: f64
+}
+
+impl GridMedium {
+    /// Creates a new grid medium
+    ///
+    /// # Arguments
+    /// * `grid` - densities
+    /// * `corner_a` and `corner_b` - opposite corners of the box the grid fills
+    /// * `absorption` - absorption coefficient, `σa`, at density `1`
+    /// * `scattering` - scattering coefficient, `σs`, at density `1`
+    /// * `g` - asymmetry of the Henyey-Greenstein phase function
+    pub fn new(
+        grid: DensityGrid,
+        corner_a: nalgebra_glm::DVec3,
+        corner_b: nalgebra_glm::DVec3,
+        absorption: nalgebra_glm::DVec3,
+        scattering: nalgebra_glm::DVec3,
+        g: f64
+    ) -> Self {
+        let majorant = grid.max() * (absorption + scattering).max();
+        Self {
+            grid,
+            bounds: Cuboid::new(corner_a, corner_b),
+            absorption,
+            scattering,
+            phase: HenyeyGreenstein::new(g),
+            majorant
+        }
+    }
+
+    /// Opposite corners of the box filled by the grid
+    pub fn bounds(&self) -> (&nalgebra_glm::DVec3, &nalgebra_glm::DVec3) {
+        (self.bounds.min(), self.bounds.max())
+    }
+
+    fn density(&self, point: &nalgebra_glm::DVec3) -> f64 {
+        let local = (point - self.bounds.min()).component_div(&(self.bounds.max() - self.bounds.min()));
+        if local.iter().all(|c| (0. ..=1.).contains(c)) {
+            self.grid.density(&local)
+        } else {
+            0.
+        }
+    }
+
+    /// Part of the ray inside of the box, clipped to `[0, max_distance]`
+    fn segment(&self, ray: &Ray, max_distance: f64) -> Option<(f64, f64)> {
+        if self.majorant <= 0. {
+            return None;
+        }
+        self.bounds.clip(ray)
+            .map(|(near, far)| (near.max(0.), far.min(max_distance)))
+            .filter(|(near, far)| near < far)
+    }
+}
+
+impl Medium for GridMedium {
+    fn sample(&self, ray: &Ray, max_distance: f64) -> MediumEvent {
+        let Some((mut distance, end)) = self.segment(ray, max_distance) else {
+            return MediumEvent::Pass { weight: nalgebra_glm::DVec3::from_element(1.) };
+        };
+        // Spectral delta tracking, the weight corrects for choosing events with the channel average
+        let mut weight = nalgebra_glm::DVec3::from_element(1.);
+        loop {
+            distance -= (1. - RandomGen::rand2()).ln() / self.majorant;
+            if distance >= end {
+                return MediumEvent::Pass { weight };
+            }
+            let density = self.density(&(ray.origin() + ray.direction() * distance));
+            let absorption = self.absorption * density;
+            let scattering = self.scattering * density;
+            let null = nalgebra_glm::DVec3::from_element(self.majorant) - absorption - scattering;
+            let (absorb_prob, scatter_prob) = (absorption.mean() / self.majorant, scattering.mean() / self.majorant);
+            let u = RandomGen::rand2();
+            if u < absorb_prob {
+                return MediumEvent::Absorb;
+            } else if u < absorb_prob + scatter_prob {
+                return MediumEvent::Scatter {
+                    distance,
+                    weight: weight.component_mul(&scattering) / scattering.mean()
+                };
+            } else {
+                let null_prob = 1. - absorb_prob - scatter_prob;
+                weight = weight.component_mul(&null) / (self.majorant * null_prob);
+            }
+        }
+    }
+
+    fn transmittance(&self, ray: &Ray, distance: f64) -> nalgebra_glm::DVec3 {
+        let Some((mut t, end)) = self.segment(ray, distance) else {
+            return nalgebra_glm::DVec3::from_element(1.);
+        };
+        // Ratio tracking
+        let mut transmittance = nalgebra_glm::DVec3::from_element(1.);
+        loop {
+            t -= (1. - RandomGen::rand2()).ln() / self.majorant;
+            if t >= end {
+                return transmittance;
+            }
+            let extinction = (self.absorption + self.scattering) * self.density(&(ray.origin() + ray.direction() * t));
+            transmittance = transmittance.component_mul(
+                &((nalgebra_glm::DVec3::from_element(self.majorant) - extinction) / self.majorant)
+            );
+        }
+    }
+
+    fn phase(&self) -> &HenyeyGreenstein {
+        &self.phase
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::medium::HomogeneousMedium;
+
+    #[test]
+    fn read_grids() {
+        let text = DensityGrid::read_text("# 2x1x2 grid\n2 1 2\n0 1\n2 3\n".as_bytes()).unwrap();
+        assert_eq!(text.resolution(), [2, 1, 2]);
+        approx::assert_abs_diff_eq!(text.density(&nalgebra_glm::DVec3::new(0.75, 0.5, 0.25)), 1.);
+        approx::assert_abs_diff_eq!(text.density(&nalgebra_glm::DVec3::new(0.25, 0.5, 0.75)), 2.);
+        approx::assert_abs_diff_eq!(text.density(&nalgebra_glm::DVec3::from_element(0.5)), 1.5);
+
+        let raw = [0.5_f32, 1.5].iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<_>>();
+        let raw = DensityGrid::read_raw(raw.as_slice(), [1, 2, 1]).unwrap();
+        approx::assert_abs_diff_eq!(raw.density(&nalgebra_glm::DVec3::new(0.5, 0.5, 0.5)), 1.);
+        approx::assert_abs_diff_eq!(raw.max(), 1.5);
+
+        assert!(DensityGrid::read_text("2 2 2\n1 2 3".as_bytes()).is_err());
+        assert!(DensityGrid::new([1, 1, 1], vec![-1.]).is_err());
+        assert!(matches!(DensityGrid::new([usize::MAX / 2 + 1, 2, 1], vec![]), Err(MediumError::GridSizeError)));
+    }
+
+    #[test]
+    fn constant_grid_matches_homogeneous() {
+        let absorption = nalgebra_glm::DVec3::new(0.1, 0.2, 0.);
+        let scattering = nalgebra_glm::DVec3::new(0.3, 0.1, 0.4);
+        let grid = GridMedium::new(
+            DensityGrid::new([2, 2, 2], vec![2.; 8]).unwrap(),
+            nalgebra_glm::DVec3::from_element(-1.),
+            nalgebra_glm::DVec3::from_element(1.),
+            absorption,
+            scattering,
+            0.
+        );
+        let homogeneous = HomogeneousMedium::new(absorption * 2., scattering * 2., 0.);
+        // Starts outside of the box, only 2 units of the ray are inside
+        let ray = Ray::new(nalgebra_glm::DVec3::new(0., 0., 3.), nalgebra_glm::DVec3::new(0., 0., -1.));
+        let inside = Ray::new(nalgebra_glm::DVec3::new(0., 0., 1.), nalgebra_glm::DVec3::new(0., 0., -1.));
+        let expected = homogeneous.transmittance(&inside, 2.);
+
+        const SAMPLES: usize = 100_000;
+        let ratio = (0..SAMPLES).map(|_| grid.transmittance(&ray, 10.)).sum::<nalgebra_glm::DVec3>() / SAMPLES as f64;
+        approx::assert_abs_diff_eq!(ratio, expected, epsilon = 1e-2);
+
+        let (mut passed, mut scattered) = (nalgebra_glm::DVec3::zeros(), nalgebra_glm::DVec3::zeros());
+        for _ in 0..SAMPLES {
+            match grid.sample(&ray, 10.) {
+                MediumEvent::Pass { weight } => passed += weight,
+                MediumEvent::Scatter { distance, weight } => {
+                    assert!((2. ..=4.).contains(&distance));
+                    scattered += weight;
+                },
+                MediumEvent::Absorb => ()
+            }
+        }
+        approx::assert_abs_diff_eq!(passed / SAMPLES as f64, expected, epsilon = 1e-2);
+        // Everything that doesn't pass is either absorbed or scattered, by the albedo of each channel
+        let albedo = scattering.component_div(&(absorption + scattering));
+        let expected_scattered = (nalgebra_glm::DVec3::from_element(1.) - expected).component_mul(&albedo);
+        approx::assert_abs_diff_eq!(scattered / SAMPLES as f64, expected_scattered, epsilon = 1e-2);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,117 @@
+use crate::common::{Ray, RandomGen};
+
+use super::{Medium, MediumEvent, HenyeyGreenstein};
+
+/// Medium with the same density everywhere
+///
+/// Coefficients are per unit of distance and per color channel.
+#[derive(Debug)]
+pub struct HomogeneousMedium {
+    absorption: nalgebra_glm::DVec3,
+    scattering: nalgebra_glm::DVec3,
+    phase: HenyeyGreenstein
+}
+
+impl HomogeneousMedium {
+    /// Creates a new homogeneous medium
+    ///
+    /// # Arguments
+    /// * `absorption` - absorption coefficient, `σa`
+    /// * `scattering` - scattering coefficient, `σs`
+    /// * `g` - asymmetry of the Henyey-Greenstein phase function
+    pub fn new(absorption: nalgebra_glm::DVec3, scattering: nalgebra_glm::DVec3, g: f64) -> Self {
+        Self {
+            absorption,
+            scattering,
+            phase: HenyeyGreenstein::new(g)
+        }
+    }
+
+    /// Extinction coefficient, `σt = σa + σs`
+    pub fn extinction(&self) -> nalgebra_glm::DVec3 {
+        self.absorption + self.scattering
+    }
+}
+
+impl Medium for HomogeneousMedium {
+    fn sample(&self, ray: &Ray, max_distance: f64) -> MediumEvent {
+        let extinction = self.extinction();
+        // Distances are sampled on a random channel, the pdf is the average over all channels
+        let channel = ((RandomGen::rand2() * 3.) as usize).min(2);
+        let distance = if extinction[channel] > 0. {
+            -(1. - RandomGen::rand2()).ln() / extinction[channel]
+        } else {
+            f64::INFINITY
+        };
+        if distance < max_distance {
+            let transmittance = self.transmittance(ray, distance);
+            let pdf = extinction.component_mul(&transmittance).mean();
+            MediumEvent::Scatter {
+                distance,
+                weight: self.scattering.component_mul(&transmittance) / pdf
+            }
+        } else {
+            let transmittance = self.transmittance(ray, max_distance);
+            let probability = transmittance.mean();
+            MediumEvent::Pass {
+                weight: if probability > 0. { transmittance / probability } else { nalgebra_glm::zero() }
+            }
+        }
+    }
+
+    fn transmittance(&self, _ray: &Ray, distance: f64) -> nalgebra_glm::DVec3 {
+        if distance.is_infinite() {
+            self.extinction().map(|e| if e > 0. { 0. } else { 1. })
+        } else {
+            (-self.extinction() * distance).map(f64::exp)
+        }
+    }
+
+    fn phase(&self) -> &HenyeyGreenstein {
+        &self.phase
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn free_flight_is_unbiased() {
+        // The expected weight of passing through must match the transmittance of each channel
+        let medium = HomogeneousMedium::new(
+            nalgebra_glm::DVec3::new(0.1, 0.3, 0.),
+            nalgebra_glm::DVec3::new(0.2, 0.5, 0.4),
+            0.
+        );
+        let ray = Ray::new(nalgebra_glm::zero(), nalgebra_glm::DVec3::new(0., 0., -1.));
+        const SAMPLES: usize = 200_000;
+        let passed = (0..SAMPLES)
+            .map(|_| match medium.sample(&ray, 2.) {
+                MediumEvent::Pass { weight } => weight,
+                MediumEvent::Scatter { .. } | MediumEvent::Absorb => nalgebra_glm::zero()
+            })
+            .sum::<nalgebra_glm::DVec3>() / SAMPLES as f64;
+        approx::assert_abs_diff_eq!(passed, medium.transmittance(&ray, 2.), epsilon = 1e-2);
+    }
+
+    #[test]
+    fn scattered_fraction_matches_albedo() {
+        // Without a boundary, every ray scatters with a weight averaging to the albedo
+        let medium = HomogeneousMedium::new(
+            nalgebra_glm::DVec3::new(0.5, 0.5, 0.5),
+            nalgebra_glm::DVec3::new(0.5, 1.5, 0.),
+            0.5
+        );
+        let ray = Ray::new(nalgebra_glm::zero(), nalgebra_glm::DVec3::new(0., 0., -1.));
+        const SAMPLES: usize = 200_000;
+        let scattered = (0..SAMPLES)
+            .map(|_| match medium.sample(&ray, f64::INFINITY) {
+                MediumEvent::Scatter { weight, .. } => weight,
+                MediumEvent::Pass { weight } => weight,
+                MediumEvent::Absorb => nalgebra_glm::zero()
+            })
+            .sum::<nalgebra_glm::DVec3>() / SAMPLES as f64;
+        approx::assert_abs_diff_eq!(scattered, nalgebra_glm::DVec3::new(0.5, 0.75, 0.), epsilon = 2e-2);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,139 @@
+use crate::{common::{Ray, Frame}, extension::vector_ext::OrthonormalVectorExt};
+
+mod homogeneous;
+pub use homogeneous::HomogeneousMedium;
+
+mod grid;
+pub use grid::{DensityGrid, GridMedium};
+
+#[derive(Debug)]
+pub enum MediumError {
+    GridSizeError,
+    GridFormatError,
+    GridReadError(std::io::Error)
+}
+
+impl std::fmt::Display for MediumError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let m = match self {
+            MediumError::GridSizeError => String::from("The grid must have at least one voxel and exactly as many densities as voxels."),
+            MediumError::GridFormatError => String::from("The grid has an invalid resolution or density, densities must be finite and positive."),
+            MediumError::GridReadError(err) => format!("Could not read the grid: {err}")
+        };
+        writeln!(f, "{m}")
+    }
+}
+
+impl std::error::Error for MediumError {}
+
+impl From<std::io::Error> for MediumError {
+    fn from(value: std::io::Error) -> Self {
+        MediumError::GridReadError(value)
+    }
+}
+
+/// Outcome of sampling the distance a ray travels inside a medium
+#[derive(Debug)]
+pub enum MediumEvent {
+    /// The ray is scattered `distance` units along the ray, `weight` is the throughput up to there
+    Scatter {
+        distance: f64,
+        weight: nalgebra_glm::DVec3
+    },
+    /// The ray reaches the end of the segment, `weight` is the throughput up to there
+    Pass {
+        weight: nalgebra_glm::DVec3
+    },
+    /// The ray is absorbed by the medium
+    Absorb
+}
+
+/// Volume that absorbs and scatters light, like fog or smoke
+pub trait Medium: std::fmt::Debug + std::marker::Sync + std::marker::Send {
+    /// Samples where the ray interacts with the medium before `max_distance`
+    fn sample(&self, ray: &Ray, max_distance: f64) -> MediumEvent;
+
+    /// Fraction of the light that crosses the medium along the ray up to `distance`
+    fn transmittance(&self, ray: &Ray, distance: f64) -> nalgebra_glm::DVec3;
+
+    /// Phase function used when scattering
+    fn phase(&self) -> &HenyeyGreenstein;
+}
+
+/// Henyey-Greenstein phase function
+///
+/// `g` is the mean cosine of the scattering angle, negative values scatter backwards,
+/// `0` scatters equally in every direction, and positive values scatter forward.
+#[derive(Debug, Clone)]
+pub struct HenyeyGreenstein {
+    g: f64
+}
+
+impl HenyeyGreenstein {
+    pub fn new(g: f64) -> Self {
+        Self {
+            g: g.clamp(-0.99, 0.99)
+        }
+    }
+
+    pub fn g(&self) -> f64 {
+        self.g
+    }
+
+    /// Density of scattering by an angle with cosine `cos`, per solid angle
+    pub fn eval(&self, cos: f64) -> f64 {
+        let denominator = 1. + self.g.powi(2) - 2. * self.g * cos;
+        (1. - self.g.powi(2)) / (4. * std::f64::consts::PI * denominator * denominator.sqrt())
+    }
+
+    /// Samples a new direction for a ray traveling along `direction`
+    ///
+    /// The sample follows the phase function exactly, so its weight is `1`.
+    pub fn sample(&self, direction: &nalgebra_glm::DVec3, u1: f64, u2: f64) -> nalgebra_glm::DVec3 {
+        let cos = if self.g.abs() < 1e-3 {
+            1. - 2. * u1
+        } else {
+            let s = (1. - self.g.powi(2)) / (1. - self.g + 2. * self.g * u1);
+            (1. + self.g.powi(2) - s.powi(2)) / (2. * self.g)
+        }.clamp(-1., 1.);
+        let sin = (1. - cos.powi(2)).max(0.).sqrt();
+        let phi = 2. * std::f64::consts::PI * u2;
+        Frame::new(*direction, direction.orthonormal().0)
+            .to_world(&nalgebra_glm::DVec3::new(sin * phi.cos(), sin * phi.sin(), cos))
+            .normalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn henyey_greenstein_is_normalized() {
+        for g in [-0.7, 0., 0.3, 0.9] {
+            let phase = HenyeyGreenstein::new(g);
+            const STEPS: usize = 100_000;
+            let integral = (0..STEPS)
+                .map(|i| phase.eval(-1. + 2. * (i as f64 + 0.5) / STEPS as f64))
+                .sum::<f64>() * 2. / STEPS as f64 * 2. * std::f64::consts::PI;
+            approx::assert_abs_diff_eq!(integral, 1., epsilon = 1e-3);
+        }
+    }
+
+    #[test]
+    fn henyey_greenstein_mean_cosine() {
+        let direction = nalgebra_glm::DVec3::new(0.3, -1., 0.2).normalize();
+        for g in [-0.5, 0., 0.8] {
+            let phase = HenyeyGreenstein::new(g);
+            const STEPS: usize = 300;
+            let mean = (0..STEPS * STEPS)
+                .map(|i| {
+                    let u1 = ((i / STEPS) as f64 + 0.5) / STEPS as f64;
+                    let u2 = ((i % STEPS) as f64 + 0.5) / STEPS as f64;
+                    phase.sample(&direction, u1, u2).dot(&direction)
+                })
+                .sum::<f64>() / (STEPS * STEPS) as f64;
+            approx::assert_abs_diff_eq!(mean, g, epsilon = 1e-2);
+        }
+    }
+}
\ No newline at end of file
@@ -1,51 +1,207 @@
+use std::sync::{Arc, OnceLock};
+
 use crate::common::Ray;
 
+pub mod light;
+use light::{DeltaLight, Emitter, EnvironmentLight};
+
+pub mod material;
+
+pub mod medium;
+use medium::Medium;
+
 pub mod obj;
 
 use self::obj::SceneObject;
 
 pub mod storage;
+
+pub mod texture;
 use storage::{SceneObjectStorage, BoundingVolumeHierarchy};
 
 #[cfg(feature = "sample-scenes")]
 pub mod sample;
 
 pub struct Scene {
-    objects: Box<dyn SceneObjectStorage>
+    objects: Box<dyn SceneObjectStorage>,
+    medium: Option<Arc<dyn Medium>>,
+    /// Indices of the objects that are lights, found on first use
+    lights: OnceLock<Vec<usize>>,
+    delta_lights: Vec<Box<dyn DeltaLight>>,
+    environment: Option<EnvironmentLight>
 }
 
 impl Scene {
     pub fn new(objects: Box<dyn SceneObjectStorage>) -> Self {
         Self {
-            objects
+            objects,
+            medium: None,
+            lights: OnceLock::new(),
+            delta_lights: Vec::new(),
+            environment: None
         }
     }
 
     pub fn new_with_vec_storage() -> Self {
         Self {
-            objects: Box::<Vec<SceneObject>>::default()
+            objects: Box::<Vec<SceneObject>>::default(),
+            medium: None,
+            lights: OnceLock::new(),
+            delta_lights: Vec::new(),
+            environment: None
         }
     }
 
     pub fn new_with_bounding_volume_hierarchy() -> Self {
         Self {
-            objects: Box::<BoundingVolumeHierarchy>::default()
+            objects: Box::<BoundingVolumeHierarchy>::default(),
+            medium: None,
+            lights: OnceLock::new(),
+            delta_lights: Vec::new(),
+            environment: None
         }
     }
 
-    pub fn find_intersection(&self, ray: &Ray) -> Option<obj::SceneObjectIntersection> {
+    pub fn find_intersection(&self, ray: &Ray) -> Option<obj::SceneObjectIntersection<'_>> {
         self.objects.find_intersection(ray)
     }
 
+    /// Same as `find_intersection`, also returning the number of bounding boxes and objects tested against the ray
+    pub fn find_intersection_with_tests(&self, ray: &Ray) -> (Option<obj::SceneObjectIntersection<'_>>, usize) {
+        self.objects.find_intersection_with_tests(ray)
+    }
+
+    /// Index of `object` in the storage of the scene, indices can change when the storage is rebuilt
+    pub fn object_index(&self, object: &SceneObject) -> Option<usize> {
+        self.objects.object_index(object)
+    }
+
     pub fn insert_object(&mut self, object: obj::SceneObject) {
+        self.lights.take();
         self.objects.insert_object(object)
     }
 
+    fn lights(&self) -> &[usize] {
+        self.lights.get_or_init(
+            || {
+                (0..self.objects.object_count())
+                    .filter(|index| self.objects.object(*index).is_some_and(SceneObject::is_light))
+                    .collect()
+            }
+        )
+    }
+
+    /// Number of objects that tracers can sample as lights, see `SceneObject::is_light`
+    pub fn light_count(&self) -> usize {
+        self.lights().len()
+    }
+
+    /// Chooses one of the lights uniformly, returns it with the probability of choosing it
+    ///
+    /// # Arguments
+    /// * `u` - uniform random number in `[0, 1)`
+    pub fn sample_light(&self, u: f64) -> Option<(&SceneObject, f64)> {
+        let lights = self.lights();
+        let index = ((u * lights.len() as f64) as usize).min(lights.len().checked_sub(1)?);
+        self.objects.object(lights[index])
+            .map(|light| (light, 1. / lights.len() as f64))
+    }
+
+    /// Probability with which `sample_light` chooses `object`
+    pub fn light_selection_pdf(&self, object: &SceneObject) -> f64 {
+        if object.is_light() {
+            1. / self.light_count() as f64
+        } else {
+            0.
+        }
+    }
+
+    /// Solid angle density of sampling `point` on `object` from `reference`, including the choice of the light
+    ///
+    /// `0` if `object` is not one of the lights.
+    pub fn light_pdf(
+        &self,
+        object: &SceneObject,
+        reference: &nalgebra_glm::DVec3,
+        point: &nalgebra_glm::DVec3,
+        normal: &nalgebra_glm::DVec3
+    ) -> f64 {
+        let selection_pdf = self.light_selection_pdf(object);
+        if selection_pdf > 0. {
+            selection_pdf * object.light_pdf(reference, point, normal)
+        } else {
+            0.
+        }
+    }
+
+    /// Number of lights tracers can start paths from, the objects that are lights and the delta lights
+    pub fn emitter_count(&self) -> usize {
+        self.light_count() + self.delta_lights.len()
+    }
+
+    /// Chooses one of the lights or delta lights uniformly, returns it with the probability of choosing it
+    ///
+    /// # Arguments
+    /// * `u` - uniform random number in `[0, 1)`
+    pub fn sample_emitter(&self, u: f64) -> Option<(Emitter<'_>, f64)> {
+        let count = self.emitter_count();
+        let index = ((u * count as f64) as usize).min(count.checked_sub(1)?);
+        let emitter = match self.lights().get(index) {
+            Some(light) => Emitter::Object(self.objects.object(*light)?),
+            None => Emitter::Delta(self.delta_lights[index - self.light_count()].as_ref())
+        };
+        Some((emitter, 1. / count as f64))
+    }
+
+    /// Probability with which `sample_emitter` chooses each of the lights
+    pub fn emitter_selection_pdf(&self) -> f64 {
+        match self.emitter_count() {
+            0 => 0.,
+            count => 1. / count as f64
+        }
+    }
+
+    /// Adds a light without area, like a point or a spot light
+    pub fn insert_delta_light(&mut self, light: Box<dyn DeltaLight>) {
+        self.delta_lights.push(light)
+    }
+
+    /// Lights without area, tracers reach them with shadow rays
+    pub fn delta_lights(&self) -> &[Box<dyn DeltaLight>] {
+        &self.delta_lights
+    }
+
+    /// Lights the scene from infinitely far away, rays that leave the scene return its light
+    pub fn set_environment(&mut self, environment: EnvironmentLight) {
+        self.environment = Some(environment)
+    }
+
+    /// Light arriving from infinitely far away, `None` for a black background
+    pub fn environment(&self) -> Option<&EnvironmentLight> {
+        self.environment.as_ref()
+    }
+
+    /// Light returned by a ray that leaves the scene towards `direction`
+    pub fn environment_radiance(&self, direction: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec3 {
+        self.environment.as_ref().map_or(nalgebra_glm::zero(), |environment| environment.radiance(direction))
+    }
+
+    /// Fills the space outside of the objects with a medium, like fog
+    pub fn set_medium(&mut self, medium: Arc<dyn Medium>) {
+        self.medium = Some(medium)
+    }
+
+    /// Medium outside of the objects, `None` for vacuum
+    pub fn medium(&self) -> Option<&Arc<dyn Medium>> {
+        self.medium.as_ref()
+    }
+
     /// Rebuilds storage, some storage types require rebuilding after changes to its contents.
     /// 
     /// Rebuilds with default parameters, if you wish to rebuild the storage with custom parameters,
     /// construct and rebuild the storage before attaching it to the `Scene`.
     pub fn rebuild_storage(&mut self) {
+        self.lights.take();
         self.objects.rebuild()
     }
 }
\ No newline at end of file
@@ -0,0 +1,169 @@
+use crate::{
+    common::Ray,
+    scene::obj::{SceneObjectGeometry, SELFINTERSECTION_TOLERANCE}
+};
+
+/// Box aligned with the axes of the scene
+#[derive(Debug)]
+pub struct Cuboid {
+    min: nalgebra_glm::DVec3,
+    max: nalgebra_glm::DVec3
+}
+
+impl Cuboid {
+    pub fn new(
+        corner_a: nalgebra_glm::DVec3,
+        corner_b: nalgebra_glm::DVec3
+    ) -> Self {
+        Self {
+            min: nalgebra_glm::min2(&corner_a, &corner_b),
+            max: nalgebra_glm::max2(&corner_a, &corner_b)
+        }
+    }
+
+    pub fn min(&self) -> &nalgebra_glm::DVec3 {
+        &self.min
+    }
+
+    pub fn max(&self) -> &nalgebra_glm::DVec3 {
+        &self.max
+    }
+
+    /// Distances along the ray where it enters and leaves the box, `None` if it misses it
+    ///
+    /// The distances can be negative if the box is behind the origin of the ray.
+    pub fn clip(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let (near, far) = (0..3)
+            .fold(
+                (f64::NEG_INFINITY, f64::INFINITY),
+                |(near, far), axis| {
+                    let inv = 1. / ray.direction()[axis];
+                    let a = (self.min[axis] - ray.origin()[axis]) * inv;
+                    let b = (self.max[axis] - ray.origin()[axis]) * inv;
+                    // NaN, from a ray parallel to a face on it, is ignored by `max` and `min`
+                    (near.max(a.min(b)), far.min(a.max(b)))
+                }
+            );
+        if near <= far {
+            Some((near, far))
+        } else {
+            None
+        }
+    }
+
+    /// Area of the two faces perpendicular to each axis
+    fn face_areas(&self) -> [f64; 3] {
+        let size = self.max - self.min;
+        [size.y * size.z, size.x * size.z, size.x * size.y]
+    }
+
+    fn face_normal(&self, point: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec3 {
+        let center = (self.min + self.max) / 2.;
+        let half = (self.max - self.min) / 2.;
+        let local = (point - center).component_div(&half);
+        let axis = local.iamax();
+        let mut normal = nalgebra_glm::DVec3::zeros();
+        normal[axis] = local[axis].signum();
+        normal
+    }
+}
+
+impl SceneObjectGeometry for Cuboid {
+    fn intersect(&self, ray: &Ray) -> Option<(nalgebra_glm::DVec3, nalgebra_glm::DVec3, f64)> {
+        let (near, far) = self.clip(ray)?;
+        let t = if near > SELFINTERSECTION_TOLERANCE {
+            near
+        } else if far > SELFINTERSECTION_TOLERANCE {
+            far
+        } else {
+            return None;
+        };
+        let hit_point = ray.origin() + ray.direction() * t;
+        Some((hit_point, self.face_normal(&hit_point), t))
+    }
+
+    fn uv(&self, hit_point: &nalgebra_glm::DVec3, normal: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec2 {
+        // Each face is mapped to the whole texture, using the two axes along the face
+        let local = (hit_point - self.min).component_div(&(self.max - self.min));
+        match normal.iamax() {
+            0 => nalgebra_glm::DVec2::new(local.z, local.y),
+            1 => nalgebra_glm::DVec2::new(local.x, local.z),
+            _ => nalgebra_glm::DVec2::new(local.x, local.y)
+        }
+    }
+
+    fn area(&self) -> f64 {
+        self.face_areas().iter().sum::<f64>() * 2.
+    }
+
+    fn sample_area(&self, u: &nalgebra_glm::DVec2) -> Option<(nalgebra_glm::DVec3, nalgebra_glm::DVec3)> {
+        // Chooses one of the six faces proportionally to its area, reusing `u.x` inside of the chosen face
+        let areas = self.face_areas();
+        let mut x = u.x * self.area();
+        let mut face = 0;
+        while face < 5 && x >= areas[face / 2] {
+            x -= areas[face / 2];
+            face += 1;
+        }
+        let axis = face / 2;
+        let v = (x / areas[axis]).clamp(0., 1.);
+        let size = self.max - self.min;
+        let mut point = self.min;
+        point[(axis + 1) % 3] += v * size[(axis + 1) % 3];
+        point[(axis + 2) % 3] += u.y * size[(axis + 2) % 3];
+        let mut normal = nalgebra_glm::DVec3::zeros();
+        if face % 2 == 0 {
+            normal[axis] = -1.;
+        } else {
+            point[axis] = self.max[axis];
+            normal[axis] = 1.;
+        }
+        Some((point, normal))
+    }
+
+    fn bounding_box(&self) -> (nalgebra_glm::DVec3, nalgebra_glm::DVec3) {
+        (self.min, self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intersect_from_outside_and_inside() {
+        let cuboid = Cuboid::new(nalgebra_glm::DVec3::new(1., 1., 1.), nalgebra_glm::DVec3::new(-1., -2., -1.));
+        let outside = Ray::new(nalgebra_glm::DVec3::new(0., 0., 5.), nalgebra_glm::DVec3::new(0., 0., -1.));
+        let (hp, normal, t) = cuboid.intersect(&outside).unwrap();
+        approx::assert_abs_diff_eq!(t, 4.);
+        approx::assert_abs_diff_eq!(hp, nalgebra_glm::DVec3::new(0., 0., 1.));
+        approx::assert_abs_diff_eq!(normal, nalgebra_glm::DVec3::new(0., 0., 1.));
+
+        let inside = Ray::new(nalgebra_glm::zero(), nalgebra_glm::DVec3::new(0., -1., 0.));
+        let (_, normal, t) = cuboid.intersect(&inside).unwrap();
+        approx::assert_abs_diff_eq!(t, 2.);
+        approx::assert_abs_diff_eq!(normal, nalgebra_glm::DVec3::new(0., -1., 0.));
+
+        let miss = Ray::new(nalgebra_glm::DVec3::new(0., 0., 5.), nalgebra_glm::DVec3::new(1., 0., 0.));
+        assert!(cuboid.intersect(&miss).is_none());
+        let behind = Ray::new(nalgebra_glm::DVec3::new(0., 0., 5.), nalgebra_glm::DVec3::new(0., 0., 1.));
+        assert!(cuboid.intersect(&behind).is_none());
+    }
+
+    #[test]
+    fn area_sampling() {
+        let cuboid = Cuboid::new(nalgebra_glm::DVec3::new(1., 1., 1.), nalgebra_glm::DVec3::new(-1., -2., -1.));
+        approx::assert_relative_eq!(cuboid.area(), 2. * (6. + 4. + 6.));
+        let mut faces = [0; 6];
+        for i in 0..64 {
+            let u = nalgebra_glm::DVec2::new((i as f64 + 0.5) / 64., 0.37);
+            let (point, normal) = cuboid.sample_area(&u).unwrap();
+            let (hp, hit_normal, _) = cuboid.intersect(&Ray::new(point + normal, -normal)).unwrap();
+            approx::assert_abs_diff_eq!(hp, point, epsilon = 1e-9);
+            approx::assert_abs_diff_eq!(hit_normal, normal);
+            faces[normal.iamax() * 2 + usize::from(normal.max() > 0.)] += 1;
+        }
+        // Proportional to the area of the faces
+        assert_eq!(faces, [12, 12, 8, 8, 12, 12]);
+    }
+}
\ No newline at end of file
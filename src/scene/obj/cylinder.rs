@@ -74,6 +74,23 @@ impl Cylinder {
         }
     }
 
+    fn side_area(&self) -> f64 {
+        2. * std::f64::consts::PI * self.radius * self.height
+    }
+
+    fn cap_area(&self) -> f64 {
+        std::f64::consts::PI * self.radius.powi(2)
+    }
+
+    /// Number of caps intersected by the cylinder, custom caps are handled by the owner
+    fn cap_count(&self) -> usize {
+        match self.ctype {
+            CylinderType::DoubleCap => 2,
+            CylinderType::SingleCap => 1,
+            CylinderType::ThroughHole | CylinderType::CustomCap => 0
+        }
+    }
+
     fn cap_intersection(&self, ray: &Ray, top_cap: bool) -> Option<(nalgebra_glm::DVec3, nalgebra_glm::DVec3, f64)> {
         let p = if top_cap {
             Plane::new(self.axis.origin() + (self.axis.direction() * (self.height / 2.)), *self.axis.direction())
@@ -124,6 +141,65 @@ impl SceneObjectGeometry for Cylinder {
             )
     }
 
+    fn tangent(&self, _hit_point: &nalgebra_glm::DVec3, normal: &nalgebra_glm::DVec3) -> Option<nalgebra_glm::DVec3> {
+        // The side follows the axis, caps have no natural tangent
+        if approx::abs_diff_eq!(normal.dot(self.axis.direction()), 0., epsilon = 1e-9) {
+            Some(*self.axis.direction())
+        } else {
+            None
+        }
+    }
+
+    fn uv(&self, hit_point: &nalgebra_glm::DVec3, normal: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec2 {
+        let (axis_orth_a, axis_orth_b) = self.axis.direction().orthonormal();
+        let (axis_orth_a, axis_orth_b) = (axis_orth_a.normalize(), axis_orth_b.normalize());
+        let offset = hit_point - self.axis.origin();
+        if approx::abs_diff_eq!(normal.dot(self.axis.direction()), 0., epsilon = 1e-9) {
+            // Angle around the axis and height, infinite cylinders use the distance along the axis
+            let angle = offset.dot(&axis_orth_b).atan2(offset.dot(&axis_orth_a));
+            let height = offset.dot(self.axis.direction());
+            nalgebra_glm::DVec2::new(
+                0.5 + angle / (2. * std::f64::consts::PI),
+                if self.height.is_finite() { 0.5 + height / self.height } else { height }
+            )
+        } else {
+            // Caps are projected on a square around them
+            nalgebra_glm::DVec2::new(
+                0.5 + offset.dot(&axis_orth_a) / (2. * self.radius),
+                0.5 + offset.dot(&axis_orth_b) / (2. * self.radius)
+            )
+        }
+    }
+
+    fn area(&self) -> f64 {
+        self.side_area() + self.cap_area() * self.cap_count() as f64
+    }
+
+    fn sample_area(&self, u: &nalgebra_glm::DVec2) -> Option<(nalgebra_glm::DVec3, nalgebra_glm::DVec3)> {
+        if !self.height.is_finite() {
+            return None;
+        }
+        let (axis_orth_a, axis_orth_b) = self.axis.direction().orthonormal();
+        let (axis_orth_a, axis_orth_b) = (axis_orth_a.normalize(), axis_orth_b.normalize());
+        let around = |angle: f64| axis_orth_a * angle.cos() + axis_orth_b * angle.sin();
+        // Chooses the side or a cap proportionally to their areas, reusing `u.x` inside of the chosen part
+        let x = u.x * self.area();
+        let phi = 2. * std::f64::consts::PI * u.y;
+        if x < self.side_area() {
+            let normal = around(phi);
+            let m = (x / self.side_area() - 0.5) * self.height;
+            Some((self.axis.origin() + self.axis.direction() * m + normal * self.radius, normal))
+        } else {
+            let x = x - self.side_area();
+            // `SingleCap` only has the bottom cap
+            let top = self.cap_count() == 2 && x < self.cap_area();
+            let v = (x / self.cap_area()).fract();
+            let normal = if top { *self.axis.direction() } else { -self.axis.direction() };
+            let center = self.axis.origin() + normal * (self.height / 2.);
+            Some((center + around(phi) * (self.radius * v.sqrt()), normal))
+        }
+    }
+
     fn bounding_box(&self) -> (nalgebra_glm::DVec3, nalgebra_glm::DVec3) {
         let (axis_orth_a, axis_orth_b) = self.axis.direction().orthonormal();
         let (axis_orth_a, axis_orth_b) = (axis_orth_a.normalize(), axis_orth_b.normalize());
@@ -140,7 +216,7 @@ impl SceneObjectGeometry for Cylinder {
             bottom - (axis_orth_b * self.radius),
         ].into_iter()
             .fold(
-                (nalgebra_glm::DVec3::from_element(std::f64::INFINITY), nalgebra_glm::DVec3::from_element(std::f64::NEG_INFINITY)),
+                (nalgebra_glm::DVec3::from_element(f64::INFINITY), nalgebra_glm::DVec3::from_element(f64::NEG_INFINITY)),
                 |state, cur| {
                     (
                         nalgebra_glm::DVec3::new(
@@ -207,7 +283,7 @@ mod test {
 
         let cyl = Cylinder::new(
             Ray::new(nalgebra_glm::zero(), nalgebra_glm::DVec3::new(1., 0., 0.)),
-            std::f64::INFINITY,
+            f64::INFINITY,
             1.,
             CylinderType::ThroughHole
         );
@@ -219,7 +295,7 @@ mod test {
 
         let cyl = Cylinder::new(
             Ray::new(nalgebra_glm::zero(), nalgebra_glm::DVec3::new(1., 1., 0.).normalize()),
-            std::f64::INFINITY,
+            f64::INFINITY,
             1.,
             CylinderType::ThroughHole
         );
@@ -233,7 +309,7 @@ mod test {
 
         let cyl = Cylinder::new(
             Ray::new(nalgebra_glm::zero(), nalgebra_glm::DVec3::new(1., 1., 1.).normalize()),
-            std::f64::INFINITY,
+            f64::INFINITY,
             1.,
             CylinderType::ThroughHole
         );
@@ -265,4 +341,56 @@ mod test {
         approx::assert_abs_diff_eq!(bb.0, nalgebra_glm::DVec3::new(-(2.0_f64).sqrt(), -1., -(2.0_f64).sqrt()));
         approx::assert_abs_diff_eq!(bb.1, nalgebra_glm::DVec3::new((2.0_f64).sqrt(), 1., (2.0_f64).sqrt()));
     }
+
+    #[test]
+    fn uv_test() {
+        let cyl = Cylinder::new(
+            Ray::new(nalgebra_glm::zero(), nalgebra_glm::DVec3::new(0., 1., 0.)),
+            2.,
+            1.,
+            CylinderType::DoubleCap
+        );
+        let side = Ray::new(nalgebra_glm::DVec3::new(5., 0.5, 0.), nalgebra_glm::DVec3::new(-1., 0., 0.));
+        let (hp, normal, _) = cyl.intersect(&side).unwrap();
+        approx::assert_abs_diff_eq!(cyl.uv(&hp, &normal).y, 0.75);
+        let (hp, normal, _) = cyl.intersect(&Ray::new(nalgebra_glm::DVec3::new(0., 5., 0.), nalgebra_glm::DVec3::new(0., -1., 0.))).unwrap();
+        approx::assert_abs_diff_eq!(cyl.uv(&hp, &normal), nalgebra_glm::DVec2::new(0.5, 0.5));
+        for angle in [0.1, 1., 2., 3., 4., 5., 6.] {
+            let direction = nalgebra_glm::DVec3::new(-f64::cos(angle), 0., -f64::sin(angle));
+            let (hp, normal, _) = cyl.intersect(&Ray::new(direction * -5., direction)).unwrap();
+            let uv = cyl.uv(&hp, &normal);
+            assert!((0. ..=1.).contains(&uv.x) && (0. ..=1.).contains(&uv.y), "{uv:?}");
+        }
+    }
+
+    #[test]
+    fn area_sampling() {
+        let shapes: [(Box<dyn SceneObjectGeometry>, f64); 3] = [
+            (
+                Box::new(Cylinder::new(Ray::new(nalgebra_glm::zero(), nalgebra_glm::DVec3::new(0., 1., 0.)), 2., 1., CylinderType::DoubleCap)),
+                6. * std::f64::consts::PI
+            ),
+            (
+                Box::new(Cylinder::new(Ray::new(nalgebra_glm::zero(), nalgebra_glm::DVec3::new(0., 0., 1.)), 2., 1., CylinderType::SingleCap)),
+                5. * std::f64::consts::PI
+            ),
+            (
+                Box::new(Cylinder::new(Ray::new(nalgebra_glm::zero(), nalgebra_glm::DVec3::new(1., 0., 0.)), f64::INFINITY, 1., CylinderType::ThroughHole)),
+                f64::INFINITY
+            )
+        ];
+        for (shape, area) in shapes {
+            approx::assert_relative_eq!(shape.area(), area);
+            for (x, y) in [(0.01, 0.), (0.3, 0.1), (0.7, 0.5), (0.9, 0.2), (0.99, 0.9)] {
+                let Some((point, normal)) = shape.sample_area(&nalgebra_glm::DVec2::new(x, y)) else {
+                    assert!(area.is_infinite());
+                    continue;
+                };
+                // Points are on the surface with the outward normal
+                let (hp, hit_normal, _) = shape.intersect(&Ray::new(point + normal, -normal)).unwrap();
+                approx::assert_abs_diff_eq!(hp, point, epsilon = 1e-9);
+                approx::assert_abs_diff_eq!(hit_normal.dot(&normal).abs(), 1., epsilon = 1e-9);
+            }
+        }
+    }
 }
\ No newline at end of file
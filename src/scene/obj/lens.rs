@@ -45,7 +45,9 @@ pub struct Lens {
     thickness: f64,
     radius: f64,
     front: LensFace,
-    back: LensFace
+    back: LensFace,
+    /// Side of the lens, also used to map the texture coordinates
+    surface: Cylinder
 }
 
 impl Lens {
@@ -76,7 +78,7 @@ impl Lens {
                 LensFace::Concave(_) => 1.,
                 LensFace::Convex(_) => -1.
             };
-            let back_pos = -1. * axis.origin().metric_distance(back.center()) * match back {
+            let back_pos = -axis.origin().metric_distance(back.center()) * match back {
                 LensFace::Concave(_) => 1.,
                 LensFace::Convex(_) => -1.
             };
@@ -85,13 +87,20 @@ impl Lens {
             if diff < SELFINTERSECTION_TOLERANCE {
                 Err(SceneObjectError::LensConcaveFaceTooDeepError)
             } else {
+                let surface = Cylinder::new(
+                    axis.clone(),
+                    thickness,
+                    radius,
+                    super::CylinderType::CustomCap
+                );
                 Ok(
                     Self {
                         axis,
                         thickness,
                         radius,
                         front,
-                        back
+                        back,
+                        surface
                     }
                 )
             }
@@ -127,12 +136,7 @@ impl Lens {
     }
 
     fn surface_intersection(&self, ray: &Ray) -> Option<(nalgebra_glm::DVec3, nalgebra_glm::DVec3, f64)> {
-        Cylinder::new(
-            self.axis.clone(),
-            self.thickness,
-            self.radius,
-            super::CylinderType::CustomCap
-        ).intersect(ray)
+        self.surface.intersect(ray)
             .map(
                 |(hp, normal, t)| {
                     if normal.dot(ray.direction()).is_sign_positive() {
@@ -195,6 +199,11 @@ impl SceneObjectGeometry for Lens {
             .min_by(|(_, _, a), (_, _, b)| a.total_cmp(b))
     }
 
+    fn uv(&self, hit_point: &nalgebra_glm::DVec3, normal: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec2 {
+        // Same mapping as a cylinder, the faces are projected like caps
+        self.surface.uv(hit_point, normal)
+    }
+
     fn bounding_box(&self) -> (nalgebra_glm::DVec3, nalgebra_glm::DVec3) {
         let top = self.axis.origin() + self.axis.direction() * (self.thickness / 2.);
         let bottom = self.axis.origin() - self.axis.direction() * (self.thickness / 2.);
@@ -218,8 +227,8 @@ mod tests {
             Ray::new(nalgebra_glm::zero(), nalgebra_glm::DVec3::new(1., 0., 0.)),
             1.,
             2.,
-            std::f64::INFINITY,
-            std::f64::NEG_INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
         ).unwrap();
         if let LensFace::Convex(sph) = lens.front {
             assert!(sph.radius().is_infinite())
@@ -235,8 +244,8 @@ mod tests {
             Ray::new(nalgebra_glm::zero(), nalgebra_glm::DVec3::new(1., 0., 0.)),
             1.,
             SELFINTERSECTION_TOLERANCE,
-            std::f64::NEG_INFINITY,
-            std::f64::NEG_INFINITY,
+            f64::NEG_INFINITY,
+            f64::NEG_INFINITY,
         ) {
             Ok(_) => panic!("Expected error"),
             Err(SceneObjectError::LensTooThinError) => (),
@@ -246,8 +255,8 @@ mod tests {
             Ray::new(nalgebra_glm::zero(), nalgebra_glm::DVec3::new(1., 0., 0.)),
             SELFINTERSECTION_TOLERANCE,
             1.,
-            std::f64::NEG_INFINITY,
-            std::f64::NEG_INFINITY,
+            f64::NEG_INFINITY,
+            f64::NEG_INFINITY,
         ) {
             Ok(_) => panic!("Expected error"),
             Err(SceneObjectError::LensTooThinError) => (),
@@ -1,4 +1,8 @@
-use crate::common::Ray;
+use std::sync::Arc;
+
+use crate::{common::{Ray, Frame, RandomGen}, extension::vector_ext::OrthonormalVectorExt, scene::{texture::Texture, light::{EmissionProfile, EmissionSample, LightSample}, medium::{Medium, GridMedium}}};
+
+pub use crate::scene::material::SceneObjectMaterial;
 
 mod plane;
 pub use plane::Plane;
@@ -12,14 +16,37 @@ pub use cylinder::{Cylinder, CylinderType};
 mod lens;
 pub use lens::Lens;
 
+mod cuboid;
+pub use cuboid::Cuboid;
+
 pub const SELFINTERSECTION_TOLERANCE: f64 = 1e-6;
 
+/// Distance used to compute the slope of bump maps by finite differences
+const BUMP_DELTA: f64 = 1e-4;
+/// Smallest cosine between the incoming ray and the shading normal
+const SHADING_HORIZON: f64 = 1e-3;
+
+/// Changes the normal used for shading to add detail to a surface
+#[derive(Debug, Clone)]
+pub enum NormalPerturbation {
+    /// Height texture, the average of the channels is the height of the surface
+    Bump {
+        height: Arc<dyn Texture>,
+        strength: f64
+    },
+    /// Tangent space normal map, with channels in `[0, 1]` mapped to `[-1, 1]`
+    ///
+    /// The tangent is the one given by `SceneObjectIntersection::tangent`.
+    NormalMap(Arc<dyn Texture>)
+}
+
 #[derive(Debug)]
 pub enum SceneObjectError {
     RefractiveCylinderConstraintError,
     LensFacesTooShortError,
     LensTooThinError,
-    LensConcaveFaceTooDeepError
+    LensConcaveFaceTooDeepError,
+    ThinFilmBaseMaterialError
 }
 
 impl std::fmt::Display for SceneObjectError {
@@ -28,7 +55,8 @@ impl std::fmt::Display for SceneObjectError {
             SceneObjectError::RefractiveCylinderConstraintError => String::from("A refractive cylinder must be Double Capped."),
             SceneObjectError::LensFacesTooShortError => String::from("One of the faces of the lens has an absolute radius smaller than the the radius of the lens."),
             SceneObjectError::LensTooThinError => String::from("The lens is too thin."),
-            SceneObjectError::LensConcaveFaceTooDeepError => String::from("A concave face is too deep. The concave face can't have a depth too close to half of the thickness.")
+            SceneObjectError::LensConcaveFaceTooDeepError => String::from("A concave face is too deep. The concave face can't have a depth too close to half of the thickness."),
+            SceneObjectError::ThinFilmBaseMaterialError => String::from("A thin film can only coat a Specular or Refractive material.")
         };
         writeln!(f, "{m}")
     }
@@ -59,7 +87,7 @@ impl<'a> SceneObjectIntersection<'a> {
         }
     }
 
-    pub fn object(&self) -> &SceneObject {
+    pub fn object(&self) -> &'a SceneObject {
         self.object
     }
 
@@ -74,27 +102,135 @@ impl<'a> SceneObjectIntersection<'a> {
     pub fn ray_length(&self) -> f64 {
         self.ray_length
     }
-}
 
-#[derive(Debug, Clone, Copy)]
-pub enum SceneObjectMaterial {
-    Diffuse,
-    Specular,
-    Refractive
-} 
+    /// Tangent of the surface at the hit point
+    ///
+    /// Uses the tangent provided by the geometry, or one orthogonal to the normal if the geometry has none.
+    pub fn tangent(&self) -> nalgebra_glm::DVec3 {
+        self.object.tangent(&self.hit_point, &self.normal)
+            .unwrap_or_else(|| self.normal.orthonormal().0)
+            .normalize()
+    }
+
+    /// Normal used for shading, facing the ray that comes from `incoming`
+    ///
+    /// It's the geometric normal perturbed by the bump or normal map of the object. It's tilted
+    /// back towards the geometric normal until `incoming` is above its horizon, and bounces must
+    /// still be checked with `is_leaking`.
+    pub fn shading_normal(&self, incoming: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec3 {
+        let geometric = if self.normal.dot(incoming) > 0. { -self.normal } else { self.normal };
+        let Some(perturbation) = &self.object.normal_perturbation else {
+            return geometric;
+        };
+        let frame = Frame::new(geometric, self.tangent());
+        let shading = match perturbation {
+            NormalPerturbation::Bump { height, strength } => {
+                let height_at = |point: nalgebra_glm::DVec3| {
+                    height.evaluate(&point, &self.normal, &self.object.uv(&point, &self.normal)).mean()
+                };
+                let base = height_at(self.hit_point);
+                let slope_tangent = (height_at(self.hit_point + frame.tangent() * BUMP_DELTA) - base) / BUMP_DELTA;
+                let slope_bitangent = (height_at(self.hit_point + frame.bitangent() * BUMP_DELTA) - base) / BUMP_DELTA;
+                (geometric - (frame.tangent() * slope_tangent + frame.bitangent() * slope_bitangent) * *strength).normalize()
+            },
+            NormalPerturbation::NormalMap(map) => {
+                let local = map.evaluate(&self.hit_point, &self.normal, &self.uv()) * 2.
+                    - nalgebra_glm::DVec3::from_element(1.);
+                // Normals under the surface are pushed back to the horizon
+                let local = nalgebra_glm::DVec3::new(local.x, local.y, local.z.max(SHADING_HORIZON));
+                frame.to_world(&local).normalize()
+            }
+        };
+        let cos = -incoming.dot(&shading);
+        if cos < SHADING_HORIZON {
+            (shading - incoming * (SHADING_HORIZON - cos)).normalize()
+        } else {
+            shading
+        }
+    }
+
+    /// If a bounce leaves on different sides of the surface for the shading and geometric normals
+    ///
+    /// Such bounces go through the geometry, or stay under it, and would leak light.
+    pub fn is_leaking(&self, direction: &nalgebra_glm::DVec3, shading_normal: &nalgebra_glm::DVec3) -> bool {
+        let geometric = if self.normal.dot(shading_normal) < 0. { -self.normal } else { self.normal };
+        direction.dot(shading_normal) * direction.dot(&geometric) < 0.
+    }
+
+    /// Surface coordinates of the hit point
+    pub fn uv(&self) -> nalgebra_glm::DVec2 {
+        self.object.uv(&self.hit_point, &self.normal)
+    }
+
+    /// Color of the object at the hit point, from its color texture if it has one
+    pub fn color(&self) -> nalgebra_glm::DVec3 {
+        match &self.object.color_texture {
+            Some(texture) => texture.evaluate(&self.hit_point, &self.normal, &self.uv()),
+            None => self.object.color
+        }
+    }
+
+    /// Opacity of the object at the hit point, see `SceneObject::with_opacity_texture`
+    pub fn opacity(&self) -> f64 {
+        self.object.opacity_at(&self.hit_point, &self.normal)
+    }
+
+    /// If the ray goes through the surface without bouncing
+    ///
+    /// It does for `SceneObjectMaterial::Interface`, and randomly crosses partially transparent parts with a
+    /// probability of `1 - opacity`.
+    ///
+    /// # Arguments
+    /// * `material` - material selected at the hit point
+    pub fn passes_through(&self, material: &SceneObjectMaterial) -> bool {
+        if matches!(material, SceneObjectMaterial::Interface) {
+            return true;
+        }
+        let opacity = self.opacity();
+        opacity < 1. && RandomGen::rand2() >= opacity
+    }
+
+    /// Light emitted by the object at the hit point towards where `incoming` came from
+    ///
+    /// Uses the emission texture of the object if it has one, scaled by its `EmissionProfile`.
+    pub fn emission(&self, incoming: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec3 {
+        let factor = self.object.emission_profile.factor(&self.normal, &-incoming);
+        if factor <= 0. {
+            return nalgebra_glm::zero();
+        }
+        match &self.object.emission_texture {
+            Some((texture, strength)) => texture.evaluate(&self.hit_point, &self.normal, &self.uv()) * (strength * factor),
+            None => self.object.emission * factor
+        }
+    }
+
+    /// Light emitted at the hit point towards where `incoming` came from, when the hit point was sampled on a light
+    ///
+    /// It's the emission scaled by the opacity, like rays that hit the surface and cross its transparent parts.
+    pub fn sampled_emission(&self, incoming: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec3 {
+        self.emission(incoming) * self.opacity()
+    }
+}
 
 #[derive(Debug)]
 pub struct SceneObject {
     color: nalgebra_glm::DVec3,
-    emission: f64,
+    emission: nalgebra_glm::DVec3,
     material: SceneObjectMaterial,
-    geometry: Box<dyn SceneObjectGeometry>
+    geometry: Box<dyn SceneObjectGeometry>,
+    opacity: f64,
+    opacity_texture: Option<Arc<dyn Texture>>,
+    color_texture: Option<Arc<dyn Texture>>,
+    emission_texture: Option<(Arc<dyn Texture>, f64)>,
+    emission_profile: EmissionProfile,
+    normal_perturbation: Option<NormalPerturbation>,
+    interior_medium: Option<Arc<dyn Medium>>
 }
 
 impl SceneObject {
     pub fn new(
         color: nalgebra_glm::DVec3,
-        emission: f64,
+        emission: nalgebra_glm::DVec3,
         material: SceneObjectMaterial,
         geometry: Box<dyn SceneObjectGeometry>
     ) -> Self {
@@ -102,68 +238,75 @@ impl SceneObject {
             color,
             emission,
             material,
-            geometry
+            geometry,
+            opacity: 1.,
+            opacity_texture: None,
+            color_texture: None,
+            emission_texture: None,
+            emission_profile: EmissionProfile::TwoSided,
+            normal_perturbation: None,
+            interior_medium: None
         }
     }
 
     pub fn new_plane(
         color: nalgebra_glm::DVec3,
-        emission: f64,
+        emission: nalgebra_glm::DVec3,
         material: SceneObjectMaterial,
         point: nalgebra_glm::DVec3,
         normal: nalgebra_glm::DVec3,
     ) -> Self {
-        Self {
+        Self::new(
             color,
             emission,
             material,
-            geometry: Box::new(
+            Box::new(
                 Plane::new(
                     point,
                     normal
                 )
             )
-        }
+        )
     }
 
     pub fn new_sphere(
         color: nalgebra_glm::DVec3,
-        emission: f64,
+        emission: nalgebra_glm::DVec3,
         material: SceneObjectMaterial,
         center: nalgebra_glm::DVec3,
         radius: f64
     ) -> Self {
-        Self {
+        Self::new(
             color,
             emission,
             material,
-            geometry: Box::new(
+            Box::new(
                 Sphere::new(
                     center,
                     radius
                 )
             )
-        }
+        )
     }
 
     pub fn new_cylinder(
         color: nalgebra_glm::DVec3,
-        emission: f64,
+        emission: nalgebra_glm::DVec3,
         material: SceneObjectMaterial,
         axis: Ray,
         height: f64,
         radius: f64,
         ctype: CylinderType
     ) -> Result<Self, SceneObjectError> {
-        if matches!(material, SceneObjectMaterial::Refractive) && !matches!(ctype, CylinderType::DoubleCap) {
+        if material.has_refraction() && !matches!(ctype, CylinderType::DoubleCap) {
             Err(SceneObjectError::RefractiveCylinderConstraintError)
         } else {
             Ok(     
-                Self {
+                Self::new(
                     color,
                     emission,
                     material,
-                    geometry: Box::new(
+                    Box::new(
                         Cylinder::new(
                             axis,
                             height,
@@ -171,7 +314,7 @@ impl SceneObject {
                             ctype
                         )
                     )
-                }
+                )
             )
         }
     }
@@ -179,7 +322,7 @@ impl SceneObject {
     #[allow(clippy::too_many_arguments)]
     pub fn new_lens(
         color: nalgebra_glm::DVec3,
-        emission: f64,
+        emission: nalgebra_glm::DVec3,
         material: SceneObjectMaterial,
         axis: Ray,
         thickness: f64,
@@ -195,37 +338,476 @@ impl SceneObject {
             back_radius
         )?;
         Ok(
-            Self {
+            Self::new(
                 color,
                 emission,
                 material,
-                geometry: Box::new(lens)
-            }
+                Box::new(lens)
+            )
+        )
+    }
+
+    pub fn new_cuboid(
+        color: nalgebra_glm::DVec3,
+        emission: nalgebra_glm::DVec3,
+        material: SceneObjectMaterial,
+        corner_a: nalgebra_glm::DVec3,
+        corner_b: nalgebra_glm::DVec3
+    ) -> Self {
+        Self::new(
+            color,
+            emission,
+            material,
+            Box::new(
+                Cuboid::new(
+                    corner_a,
+                    corner_b
+                )
+            )
         )
     }
 
+    /// Creates an invisible box that holds a `GridMedium`, so it can be stored with the other objects
+    pub fn new_volume(medium: Arc<GridMedium>) -> Self {
+        let (min, max) = medium.bounds();
+        Self::new_cuboid(
+            nalgebra_glm::zero(),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Interface,
+            *min,
+            *max
+        ).with_interior_medium(medium)
+    }
+
     pub fn color(&self) -> &nalgebra_glm::DVec3 {
         &self.color
     }
 
-    pub fn emission(&self) -> f64 {
-        self.emission
+    pub fn emission(&self) -> &nalgebra_glm::DVec3 {
+        &self.emission
+    }
+
+    pub fn material(&self) -> &SceneObjectMaterial {
+        &self.material
+    }
+
+    /// Replaces the color of the object by a texture
+    pub fn with_color_texture(mut self, texture: Arc<dyn Texture>) -> Self {
+        self.color_texture = Some(texture);
+        self
+    }
+
+    /// Replaces the emission of the object by a texture multiplied by `strength`
+    pub fn with_emission_texture(mut self, texture: Arc<dyn Texture>, strength: f64) -> Self {
+        self.emission_texture = Some((texture, strength));
+        self
+    }
+
+    /// Changes how the emission varies with the direction, the default is `EmissionProfile::TwoSided`
+    pub fn with_emission_profile(mut self, profile: EmissionProfile) -> Self {
+        self.emission_profile = profile;
+        self
+    }
+
+    pub fn emission_profile(&self) -> &EmissionProfile {
+        &self.emission_profile
+    }
+
+    /// Fills the inside of the object with a medium, the object must be closed
+    ///
+    /// Use `SceneObjectMaterial::Interface` for volumes without a visible surface.
+    pub fn with_interior_medium(mut self, medium: Arc<dyn Medium>) -> Self {
+        self.interior_medium = Some(medium);
+        self
+    }
+
+    /// Medium inside of the object, `None` for vacuum
+    pub fn interior_medium(&self) -> Option<&Arc<dyn Medium>> {
+        self.interior_medium.as_ref()
+    }
+
+    /// Perturbs the shading normal with the slope of a height texture
+    pub fn with_bump_map(mut self, height: Arc<dyn Texture>, strength: f64) -> Self {
+        self.normal_perturbation = Some(NormalPerturbation::Bump { height, strength });
+        self
+    }
+
+    /// Replaces the shading normal with a tangent space normal map
+    pub fn with_normal_map(mut self, map: Arc<dyn Texture>) -> Self {
+        self.normal_perturbation = Some(NormalPerturbation::NormalMap(map));
+        self
     }
 
-    pub fn material(&self) -> SceneObjectMaterial {
-        self.material
+    /// Sets how much light the surface stops, `0` is fully transparent and `1` is fully opaque
+    ///
+    /// Fully transparent objects are skipped by the storages, partially transparent
+    /// objects are crossed randomly by the tracers.
+    pub fn with_opacity(mut self, opacity: f64) -> Self {
+        self.opacity = opacity.clamp(0., 1.);
+        self
+    }
+
+    /// Multiplies the opacity by the average of the channels of a texture, for alpha cutouts like leaves or fences
+    ///
+    /// Hits where the opacity is `0` are skipped by the storages.
+    pub fn with_opacity_texture(mut self, texture: Arc<dyn Texture>) -> Self {
+        self.opacity_texture = Some(texture);
+        self
+    }
+
+    /// Opacity of the object, without its opacity texture
+    pub fn opacity(&self) -> f64 {
+        self.opacity
+    }
+
+    /// Opacity of the object at `hit_point`, including its opacity texture
+    pub fn opacity_at(&self, hit_point: &nalgebra_glm::DVec3, normal: &nalgebra_glm::DVec3) -> f64 {
+        match &self.opacity_texture {
+            Some(texture) => (texture.evaluate(hit_point, normal, &self.uv(hit_point, normal)).mean() * self.opacity).clamp(0., 1.),
+            None => self.opacity
+        }
     }
 
     pub fn intersect(&self, ray: &Ray) -> Option<(nalgebra_glm::DVec3, nalgebra_glm::DVec3, f64)> {
         self.geometry.intersect(ray)
     }
 
+    /// Closest hit of `ray` where the object isn't fully transparent, see `opacity_at`
+    pub fn intersect_visible(&self, ray: &Ray) -> Option<(nalgebra_glm::DVec3, nalgebra_glm::DVec3, f64)> {
+        if self.opacity <= 0. {
+            return None;
+        }
+        if self.opacity_texture.is_none() {
+            return self.intersect(ray);
+        }
+        let mut ray = ray.clone();
+        let mut travelled = 0.;
+        loop {
+            let (hp, normal, t) = self.intersect(&ray)?;
+            travelled += t;
+            if self.opacity_at(&hp, &normal) > 0. {
+                return Some((hp, normal, travelled));
+            }
+            // Looks for a visible hit further on the same object
+            ray = Ray::new(hp, *ray.direction());
+        }
+    }
+
     pub fn bounding_box(&self) -> (nalgebra_glm::DVec3, nalgebra_glm::DVec3) {
         self.geometry.bounding_box()
     }
+
+    pub fn tangent(&self, hit_point: &nalgebra_glm::DVec3, normal: &nalgebra_glm::DVec3) -> Option<nalgebra_glm::DVec3> {
+        self.geometry.tangent(hit_point, normal)
+    }
+
+    pub fn uv(&self, hit_point: &nalgebra_glm::DVec3, normal: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec2 {
+        self.geometry.uv(hit_point, normal)
+    }
+
+    /// Checks if the object emits light and its surface can be sampled, so tracers can send shadow rays towards it
+    pub fn is_light(&self) -> bool {
+        self.opacity > 0.
+            && (self.emission.max() > 0. || self.emission_texture.is_some())
+            && self.geometry.area().is_finite()
+    }
+
+    /// Samples a point on the surface of the object as seen from `reference`
+    ///
+    /// # Arguments
+    /// * `reference` - point that receives the light
+    /// * `u` - two uniform random numbers in `[0, 1)`
+    pub fn sample_light(&self, reference: &nalgebra_glm::DVec3, u: &nalgebra_glm::DVec2) -> Option<LightSample> {
+        let (point, normal, pdf) = self.geometry.sample_from(reference, u)?;
+        let offset = point - reference;
+        let distance = offset.magnitude();
+        if distance > SELFINTERSECTION_TOLERANCE {
+            Some(
+                LightSample {
+                    point,
+                    normal,
+                    direction: offset / distance,
+                    distance,
+                    pdf
+                }
+            )
+        } else {
+            None
+        }
+    }
+
+    /// Area of the surface of the object, infinite if it can't be sampled
+    pub fn area(&self) -> f64 {
+        self.geometry.area()
+    }
+
+    /// Samples a ray leaving the surface of a light, with a cosine distribution around the normal
+    ///
+    /// Rays leave both sides of the surface, unless the `EmissionProfile` is one sided.
+    /// Returns `None` if the object is not a light, see `is_light`.
+    ///
+    /// # Arguments
+    /// * `u_position` - two uniform random numbers in `[0, 1)` for the point on the surface
+    /// * `u_direction` - two uniform random numbers in `[0, 1)` for the direction of the ray
+    pub fn sample_emission(&self, u_position: &nalgebra_glm::DVec2, u_direction: &nalgebra_glm::DVec2) -> Option<EmissionSample> {
+        if !self.is_light() {
+            return None;
+        }
+        let (point, normal) = self.geometry.sample_area(u_position)?;
+        let (side, u) = match self.emission_profile {
+            EmissionProfile::OneSided => (normal, *u_direction),
+            _ if u_direction.x < 0.5 => (normal, nalgebra_glm::DVec2::new(u_direction.x * 2., u_direction.y)),
+            _ => (-normal, nalgebra_glm::DVec2::new(u_direction.x * 2. - 1., u_direction.y))
+        };
+        let r = u.x.sqrt();
+        let phi = 2. * std::f64::consts::PI * u.y;
+        let direction = Frame::new(side, side.orthonormal().0)
+            .to_world(&nalgebra_glm::DVec3::new(r * phi.cos(), r * phi.sin(), (1. - u.x).max(0.).sqrt()))
+            .normalize();
+        let pdf_direction = self.emission_pdf(&normal, &direction);
+        (pdf_direction > 0.).then(
+            || EmissionSample {
+                origin: point,
+                normal,
+                direction,
+                radiance: SceneObjectIntersection::new(self, point, normal, 0.).sampled_emission(&-direction),
+                pdf_position: 1. / self.area(),
+                pdf_direction
+            }
+        )
+    }
+
+    /// Solid angle density with which `sample_emission` leaves towards `direction` from a point with `normal`
+    pub fn emission_pdf(&self, normal: &nalgebra_glm::DVec3, direction: &nalgebra_glm::DVec3) -> f64 {
+        let cos = normal.dot(&direction.normalize());
+        match self.emission_profile {
+            EmissionProfile::OneSided => cos.max(0.) / std::f64::consts::PI,
+            _ => cos.abs() / (2. * std::f64::consts::PI)
+        }
+    }
+
+    /// Solid angle density with which `sample_light` returns `point` as seen from `reference`
+    pub fn light_pdf(&self, reference: &nalgebra_glm::DVec3, point: &nalgebra_glm::DVec3, normal: &nalgebra_glm::DVec3) -> f64 {
+        self.geometry.pdf_from(reference, point, normal)
+    }
 }
 
 pub trait SceneObjectGeometry: std::fmt::Debug + std::marker::Sync {
     fn intersect(&self, ray: &Ray) -> Option<(nalgebra_glm::DVec3, nalgebra_glm::DVec3, f64)>;
     fn bounding_box(&self) -> (nalgebra_glm::DVec3, nalgebra_glm::DVec3);
+
+    /// Tangent of the surface at `hit_point`, `None` if the geometry has no natural tangent there
+    fn tangent(&self, _hit_point: &nalgebra_glm::DVec3, _normal: &nalgebra_glm::DVec3) -> Option<nalgebra_glm::DVec3> {
+        None
+    }
+
+    /// Surface coordinates of `hit_point`, used to map textures
+    ///
+    /// Geometries without a parameterization map the whole surface to `(0, 0)`.
+    fn uv(&self, _hit_point: &nalgebra_glm::DVec3, _normal: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec2 {
+        nalgebra_glm::zero()
+    }
+
+    /// Area of the surface, infinite if the geometry is unbounded or can't be sampled
+    fn area(&self) -> f64 {
+        f64::INFINITY
+    }
+
+    /// Samples a point uniformly over the surface, returns the point and the outward normal there
+    ///
+    /// # Arguments
+    /// * `u` - two uniform random numbers in `[0, 1)`
+    fn sample_area(&self, _u: &nalgebra_glm::DVec2) -> Option<(nalgebra_glm::DVec3, nalgebra_glm::DVec3)> {
+        None
+    }
+
+    /// Samples a point on the surface as seen from `reference`
+    ///
+    /// Returns the point, the normal there, and the solid angle density of the direction to the point.
+    /// Samples the area uniformly unless the geometry has a better strategy.
+    fn sample_from(
+        &self,
+        reference: &nalgebra_glm::DVec3,
+        u: &nalgebra_glm::DVec2
+    ) -> Option<(nalgebra_glm::DVec3, nalgebra_glm::DVec3, f64)> {
+        let (point, normal) = self.sample_area(u)?;
+        let pdf = area_pdf(self.area(), reference, &point, &normal);
+        if pdf.is_finite() && pdf > 0. {
+            Some((point, normal, pdf))
+        } else {
+            None
+        }
+    }
+
+    /// Solid angle density with which `sample_from` returns `point` as seen from `reference`
+    fn pdf_from(&self, reference: &nalgebra_glm::DVec3, point: &nalgebra_glm::DVec3, normal: &nalgebra_glm::DVec3) -> f64 {
+        area_pdf(self.area(), reference, point, normal)
+    }
+}
+
+/// Converts the density of sampling `point` uniformly on a surface of `area` into a solid angle density around `reference`
+///
+/// Returns `0` for infinite areas, and infinity if the surface is seen edge on.
+pub fn area_pdf(area: f64, reference: &nalgebra_glm::DVec3, point: &nalgebra_glm::DVec3, normal: &nalgebra_glm::DVec3) -> f64 {
+    let offset = point - reference;
+    let distance_squared = offset.norm_squared();
+    let cos = normal.dot(&offset).abs() / distance_squared.sqrt();
+    if !area.is_finite() {
+        // Unbounded surfaces can't be sampled
+        0.
+    } else if cos > 0. {
+        distance_squared / (cos * area)
+    } else {
+        f64::INFINITY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::texture::{GradientTexture, GradientAxis, ConstantTexture};
+
+    fn floor() -> SceneObject {
+        SceneObject::new_plane(
+            nalgebra_glm::DVec3::from_element(1.),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::new(0., 1., 0.)
+        )
+    }
+
+    fn hit(object: &SceneObject, direction: nalgebra_glm::DVec3) -> SceneObjectIntersection<'_> {
+        let ray = Ray::new(nalgebra_glm::DVec3::new(0., 1., 0.) - direction, direction);
+        let (hit_point, normal, t) = object.intersect(&ray).unwrap();
+        SceneObjectIntersection::new(object, hit_point, normal, t)
+    }
+
+    #[test]
+    fn bump_map_tilts_shading_normal() {
+        let object = floor().with_bump_map(
+            Arc::new(
+                GradientTexture::new(
+                    nalgebra_glm::zero(),
+                    nalgebra_glm::DVec3::from_element(1.),
+                    GradientAxis::Direction {
+                        origin: nalgebra_glm::DVec3::from_element(-10.),
+                        direction: nalgebra_glm::DVec3::new(20., 0., 0.)
+                    }
+                )
+            ),
+            20.
+        );
+        let direction = nalgebra_glm::DVec3::new(0., -1., 0.);
+        let inter = hit(&object, direction);
+        let shading = inter.shading_normal(&direction);
+        // The height grows along x with a slope of 1
+        approx::assert_abs_diff_eq!(shading, nalgebra_glm::DVec3::new(-1., 1., 0.).normalize(), epsilon = 1e-6);
+        approx::assert_abs_diff_eq!(inter.normal(), nalgebra_glm::DVec3::new(0., 1., 0.));
+    }
+
+    #[test]
+    fn shading_normal_stays_above_horizon() {
+        // Normal map pointing almost along the surface
+        let object = floor().with_normal_map(Arc::new(ConstantTexture(nalgebra_glm::DVec3::new(1., 1., 0.5))));
+        for direction in [
+            nalgebra_glm::DVec3::new(1., -0.1, 0.),
+            nalgebra_glm::DVec3::new(-1., -0.1, 0.),
+            nalgebra_glm::DVec3::new(0., -0.1, 1.),
+            nalgebra_glm::DVec3::new(0.3, -1., 0.2)
+        ] {
+            let direction = direction.normalize();
+            let inter = hit(&object, direction);
+            let shading = inter.shading_normal(&direction);
+            assert!(-direction.dot(&shading) > 0.);
+            assert!(shading.dot(&inter.normal()) > 0.);
+            let reflected = direction - shading * (2. * direction.dot(&shading));
+            if reflected.y < 0. {
+                assert!(inter.is_leaking(&reflected, &shading));
+            } else {
+                assert!(!inter.is_leaking(&reflected, &shading));
+            }
+        }
+    }
+
+    #[test]
+    fn sphere_emission() {
+        let light = SceneObject::new_sphere(
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::from_element(10.),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::DVec3::new(0., 4., 0.),
+            2.
+        );
+        for u in [(0.1, 0.2, 0.3, 0.4), (0.9, 0.6, 0.7, 0.05), (0.5, 0.5, 0.2, 0.9)] {
+            let sample = light.sample_emission(&nalgebra_glm::DVec2::new(u.0, u.1), &nalgebra_glm::DVec2::new(u.2, u.3)).unwrap();
+            approx::assert_relative_eq!((sample.origin - nalgebra_glm::DVec3::new(0., 4., 0.)).magnitude(), 2., max_relative = 1e-9);
+            approx::assert_relative_eq!(sample.pdf_position, 1. / (16. * std::f64::consts::PI), max_relative = 1e-9);
+            approx::assert_relative_eq!(sample.pdf_direction, light.emission_pdf(&sample.normal, &sample.direction));
+            approx::assert_relative_eq!(sample.radiance, nalgebra_glm::DVec3::from_element(10.));
+        }
+        assert!(floor().sample_emission(&nalgebra_glm::DVec2::new(0.5, 0.5), &nalgebra_glm::DVec2::new(0.5, 0.5)).is_none());
+    }
+
+    #[test]
+    fn rgb_emission() {
+        let emission = nalgebra_glm::DVec3::new(8., 4., 1.);
+        let light = SceneObject::new_plane(
+            nalgebra_glm::zero(),
+            emission,
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::new(0., 1., 0.)
+        );
+        let direction = nalgebra_glm::DVec3::new(0., -1., 0.);
+        approx::assert_relative_eq!(hit(&light, direction).emission(&direction), emission);
+    }
+
+    #[test]
+    fn grey_emission_without_texture() {
+        let light = SceneObject::new_sphere(
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::from_element(5.),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::zero(),
+            1.
+        );
+        let direction = nalgebra_glm::DVec3::new(0., -1., 0.);
+        approx::assert_relative_eq!(hit(&light, direction).emission(&direction), nalgebra_glm::DVec3::from_element(5.));
+    }
+
+    #[test]
+    fn emission_texture_times_strength() {
+        let texture: Arc<dyn Texture> = Arc::new(GradientTexture::new(
+            nalgebra_glm::DVec3::new(1., 0., 0.),
+            nalgebra_glm::DVec3::new(0., 0., 1.),
+            GradientAxis::Direction {
+                origin: nalgebra_glm::DVec3::new(-1., 0., 0.),
+                direction: nalgebra_glm::DVec3::new(2., 0., 0.)
+            }
+        ));
+        // The texture replaces the emission of the object
+        let light = SceneObject::new_sphere(
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::from_element(100.),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::zero(),
+            1.
+        )
+            .with_emission_texture(texture.clone(), 4.);
+        assert!(light.is_light());
+        let expected = |hit_point: &nalgebra_glm::DVec3, normal: &nalgebra_glm::DVec3| {
+            texture.evaluate(hit_point, normal, &light.uv(hit_point, normal)) * 4.
+        };
+        for direction in [nalgebra_glm::DVec3::new(0., -1., 0.), nalgebra_glm::DVec3::new(1., 0., 0.), nalgebra_glm::DVec3::new(-1., 0., 0.)] {
+            let (hit_point, normal, t) = light.intersect(&Ray::new(-direction * 2., direction)).unwrap();
+            let inter = SceneObjectIntersection::new(&light, hit_point, normal, t);
+            approx::assert_relative_eq!(inter.emission(&direction), expected(&hit_point, &normal));
+        }
+        let sample = light.sample_emission(&nalgebra_glm::DVec2::new(0.3, 0.6), &nalgebra_glm::DVec2::new(0.2, 0.4)).unwrap();
+        approx::assert_relative_eq!(sample.radiance, expected(&sample.origin, &sample.normal));
+        // The side facing the start of the gradient is red
+        let side = nalgebra_glm::DVec3::new(-1., 0., 0.);
+        approx::assert_relative_eq!(expected(&side, &side), nalgebra_glm::DVec3::new(4., 0., 0.));
+    }
 }
\ No newline at end of file
@@ -45,10 +45,17 @@ impl SceneObjectGeometry for Plane {
         }
     }
 
+    fn uv(&self, hit_point: &nalgebra_glm::DVec3, _normal: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec2 {
+        // Distance from `point` along two directions on the plane, so textures tile with `WrapMode::Repeat`
+        let (orth1, orth2) = self.normal.orthonormal();
+        let offset = hit_point - self.point;
+        nalgebra_glm::DVec2::new(offset.dot(&orth1.normalize()), offset.dot(&orth2.normalize()))
+    }
+
     fn bounding_box(&self) -> (nalgebra_glm::DVec3, nalgebra_glm::DVec3) {
         let (orth1, orth2) = self.normal.orthonormal();
         let a = (
-            (orth1.abs() + orth2.abs()).map(|n| if approx::abs_diff_eq!(n, 0.) { n } else { n * std::f64::INFINITY})
+            (orth1.abs() + orth2.abs()).map(|n| if approx::abs_diff_eq!(n, 0.) { n } else { n * f64::INFINITY})
         ) + self.normal.abs().component_mul(&self.point);
         (
             a * -1.,
@@ -89,7 +96,7 @@ mod tests {
     fn front_hit() {
         let p = Plane::new(nalgebra_glm::DVec3::new(5., 0., 0.), nalgebra_glm::DVec3::new(-1., 0., 0.));
         let r = Ray::new(nalgebra_glm::zero(), nalgebra_glm::DVec3::new(1., 0., 0.));
-        let (hp, normal, t) = p.intersect(&r).expect("Expected intersection");
+        let (_, normal, t) = p.intersect(&r).expect("Expected intersection");
         approx::assert_abs_diff_eq!(normal, p.normal());
         approx::assert_abs_diff_eq!(t, 5.);
     }
@@ -98,7 +105,7 @@ mod tests {
     fn back_hit() {
         let p = Plane::new(nalgebra_glm::DVec3::new(5., 0., 0.), nalgebra_glm::DVec3::new(1., 0., 0.));
         let r = Ray::new(nalgebra_glm::zero(), nalgebra_glm::DVec3::new(1., 0., 0.));
-        let (hp, normal, t) = p.intersect(&r).expect("Expected intersection");
+        let (_, normal, t) = p.intersect(&r).expect("Expected intersection");
         approx::assert_abs_diff_eq!(normal, p.normal());
         approx::assert_abs_diff_eq!(t, 5.);
     }
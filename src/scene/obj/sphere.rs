@@ -1,6 +1,7 @@
 use crate::{
-    common::Ray,
-    scene::obj::{SceneObjectGeometry, SELFINTERSECTION_TOLERANCE}
+    common::{Ray, Frame},
+    extension::vector_ext::OrthonormalVectorExt,
+    scene::obj::{SceneObjectGeometry, SELFINTERSECTION_TOLERANCE, area_pdf}
 };
 
 #[derive(Debug)]
@@ -27,6 +28,17 @@ impl Sphere {
     pub fn radius(&self) -> f64 {
         self.radius
     } 
+
+    /// `1 - cos` of the half angle of the cone the sphere covers seen from `reference`, `None` if `reference` is inside
+    fn cone(&self, reference: &nalgebra_glm::DVec3) -> Option<f64> {
+        let sin2 = self.radius.powi(2) / reference.metric_distance(&self.center).powi(2);
+        if sin2 < 1. {
+            // Avoids the cancellation of `1 - cos` on small or distant spheres
+            Some(sin2 / (1. + (1. - sin2).sqrt()))
+        } else {
+            None
+        }
+    }
 }
 
 impl SceneObjectGeometry for Sphere {
@@ -53,6 +65,73 @@ impl SceneObjectGeometry for Sphere {
         } else { None }
     }
 
+    fn tangent(&self, _hit_point: &nalgebra_glm::DVec3, normal: &nalgebra_glm::DVec3) -> Option<nalgebra_glm::DVec3> {
+        // Follows the parallels around the vertical axis, undefined on the poles
+        let tangent = nalgebra_glm::DVec3::new(0., 1., 0.).cross(normal);
+        if approx::abs_diff_eq!(tangent.magnitude(), 0.) {
+            None
+        } else {
+            Some(tangent.normalize())
+        }
+    }
+
+    fn uv(&self, hit_point: &nalgebra_glm::DVec3, _normal: &nalgebra_glm::DVec3) -> nalgebra_glm::DVec2 {
+        // Longitude and latitude around the vertical axis
+        let n = (hit_point - self.center) / self.radius;
+        nalgebra_glm::DVec2::new(
+            0.5 + n.z.atan2(n.x) / (2. * std::f64::consts::PI),
+            0.5 + n.y.clamp(-1., 1.).asin() / std::f64::consts::PI
+        )
+    }
+
+    fn area(&self) -> f64 {
+        4. * std::f64::consts::PI * self.radius.powi(2)
+    }
+
+    fn sample_area(&self, u: &nalgebra_glm::DVec2) -> Option<(nalgebra_glm::DVec3, nalgebra_glm::DVec3)> {
+        let z = 1. - 2. * u.x;
+        let r = (1. - z * z).max(0.).sqrt();
+        let phi = 2. * std::f64::consts::PI * u.y;
+        let normal = nalgebra_glm::DVec3::new(r * phi.cos(), r * phi.sin(), z);
+        Some((self.center + normal * self.radius, normal))
+    }
+
+    fn sample_from(
+        &self,
+        reference: &nalgebra_glm::DVec3,
+        u: &nalgebra_glm::DVec2
+    ) -> Option<(nalgebra_glm::DVec3, nalgebra_glm::DVec3, f64)> {
+        let Some(one_minus_cos_max) = self.cone(reference) else {
+            // Every direction sees the sphere from the inside
+            let (point, normal) = self.sample_area(u)?;
+            let pdf = area_pdf(self.area(), reference, &point, &normal);
+            return (pdf.is_finite() && pdf > 0.).then_some((point, normal, pdf));
+        };
+        // Uniform directions inside of the cone the sphere covers
+        let cos = 1. - u.x * one_minus_cos_max;
+        let sin = (1. - cos * cos).max(0.).sqrt();
+        let phi = 2. * std::f64::consts::PI * u.y;
+        let axis = (self.center - reference).normalize();
+        let direction = Frame::new(axis, axis.orthonormal().0)
+            .to_world(&nalgebra_glm::DVec3::new(sin * phi.cos(), sin * phi.sin(), cos))
+            .normalize();
+        let (point, normal, _) = self.intersect(&Ray::new(*reference, direction))
+            .unwrap_or_else(|| {
+                // Directions grazing the silhouette can miss by rounding, they touch the sphere at the closest point
+                let closest = reference + direction * (self.center - reference).dot(&direction);
+                let normal = (closest - self.center).normalize();
+                (self.center + normal * self.radius, normal, 0.)
+            });
+        Some((point, normal, 1. / (2. * std::f64::consts::PI * one_minus_cos_max)))
+    }
+
+    fn pdf_from(&self, reference: &nalgebra_glm::DVec3, point: &nalgebra_glm::DVec3, normal: &nalgebra_glm::DVec3) -> f64 {
+        match self.cone(reference) {
+            Some(one_minus_cos_max) => 1. / (2. * std::f64::consts::PI * one_minus_cos_max),
+            None => area_pdf(self.area(), reference, point, normal)
+        }
+    }
+
     fn bounding_box(&self) -> (nalgebra_glm::DVec3, nalgebra_glm::DVec3) {
         (
             nalgebra_glm::DVec3::new(
@@ -67,4 +146,34 @@ impl SceneObjectGeometry for Sphere {
             ),
         )
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cone_sampling() {
+        let sphere = Sphere::new(nalgebra_glm::DVec3::new(0., 0., -10.), 2.);
+        let reference = nalgebra_glm::DVec3::new(1., 0., 0.);
+        let cone = 1. - (1. - 4. / 101_f64).sqrt();
+        for (x, y) in [(0., 0.), (0.5, 0.25), (0.999, 0.75)] {
+            let (point, normal, pdf) = sphere.sample_from(&reference, &nalgebra_glm::DVec2::new(x, y)).unwrap();
+            approx::assert_abs_diff_eq!(point.metric_distance(sphere.center()), 2., epsilon = 1e-9);
+            // Only the side facing the reference is sampled
+            assert!(normal.dot(&(reference - point)) >= -1e-6);
+            approx::assert_relative_eq!(pdf, 1. / (2. * std::f64::consts::PI * cone), epsilon = 1e-9);
+            approx::assert_relative_eq!(pdf, sphere.pdf_from(&reference, &point, &normal));
+        }
+    }
+
+    #[test]
+    fn area_sampling_from_inside() {
+        let sphere = Sphere::new(nalgebra_glm::zero(), 1.);
+        let (point, normal, pdf) = sphere.sample_from(&nalgebra_glm::zero(), &nalgebra_glm::DVec2::new(0.3, 0.6)).unwrap();
+        approx::assert_abs_diff_eq!(point.magnitude(), 1., epsilon = 1e-9);
+        approx::assert_abs_diff_eq!(normal, point, epsilon = 1e-9);
+        // Seen from the center the whole sphere is uniform over the directions
+        approx::assert_relative_eq!(pdf, 1. / (4. * std::f64::consts::PI), epsilon = 1e-9);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,31 @@
+use std::sync::Arc;
+
+use crate::scene::{
+    Scene,
+    medium::HomogeneousMedium,
+    sample::{SampleScene, RingCaustics}
+};
+
+/// `RingCaustics` filled with fog, light scattered by the fog shows the shafts of light around the ring
+pub struct FoggyRingCaustics;
+
+impl SampleScene for FoggyRingCaustics {
+    fn build_sample_scene() -> Scene {
+        const FOG_ABSORPTION: nalgebra_glm::DVec3 = nalgebra_glm::DVec3::new(0.01, 0.01, 0.01);
+        const FOG_SCATTERING: nalgebra_glm::DVec3 = nalgebra_glm::DVec3::new(0.05, 0.05, 0.05);
+        const FOG_ASYMMETRY: f64 = 0.6;
+
+        let mut rscene = RingCaustics::build_sample_scene();
+        rscene.set_medium(
+            Arc::new(
+                HomogeneousMedium::new(
+                    FOG_ABSORPTION,
+                    FOG_SCATTERING,
+                    FOG_ASYMMETRY
+                )
+            )
+        );
+
+        rscene
+    }
+}
\ No newline at end of file
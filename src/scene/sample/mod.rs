@@ -10,6 +10,9 @@ pub use lenses_and_bars::LensesAndBars;
 mod ring_caustics;
 pub use ring_caustics::RingCaustics;
 
+mod foggy_ring_caustics;
+pub use foggy_ring_caustics::FoggyRingCaustics;
+
 use crate::scene::Scene;
 
 pub trait SampleScene {
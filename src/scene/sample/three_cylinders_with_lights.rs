@@ -10,8 +10,10 @@ pub struct ThreeCylindersWithLightsSampleScene;
 
 impl SampleScene for ThreeCylindersWithLightsSampleScene {
     fn build_sample_scene() -> Scene {
-        const BASE_EMISSION: f64 = 0.;
-        const LIGHT_EMISSION: f64 = 5_000.;
+        const BASE_EMISSION: nalgebra_glm::DVec3 = nalgebra_glm::DVec3::new(0., 0., 0.);
+        const LIGHT_EMISSION: nalgebra_glm::DVec3 = nalgebra_glm::DVec3::new(5_000., 5_000., 5_000.);
+        const WARM_LIGHT_EMISSION: nalgebra_glm::DVec3 = nalgebra_glm::DVec3::new(6_000., 4_500., 3_000.);
+        const COOL_LIGHT_EMISSION: nalgebra_glm::DVec3 = nalgebra_glm::DVec3::new(3_000., 4_500., 6_000.);
 
         let mut rscene: Scene = Scene::new_with_vec_storage();
 
@@ -112,7 +114,7 @@ impl SampleScene for ThreeCylindersWithLightsSampleScene {
         rscene.insert_object(
             SceneObject::new_sphere(
                 nalgebra_glm::DVec3::new(0., 0., 0.),
-                LIGHT_EMISSION,
+                WARM_LIGHT_EMISSION,
                 SceneObjectMaterial::Diffuse,
                 nalgebra_glm::DVec3::new(0., -2.25, -4.0),
                 0.375
@@ -136,7 +138,7 @@ impl SampleScene for ThreeCylindersWithLightsSampleScene {
         rscene.insert_object(
             SceneObject::new_sphere(
                 nalgebra_glm::DVec3::new(0., 0., 0.),
-                LIGHT_EMISSION,
+                COOL_LIGHT_EMISSION,
                 SceneObjectMaterial::Diffuse,
                 nalgebra_glm::DVec3::new(0., 2.25, -4.0),
                 0.375
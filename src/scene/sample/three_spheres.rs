@@ -4,8 +4,8 @@ pub struct ThreeSpheresSampleScene;
 
 impl SampleScene for ThreeSpheresSampleScene {
     fn build_sample_scene() -> Scene {
-        const BASE_EMISSION: f64 = 0.;
-        const LIGHT_EMISSION: f64 = 5_000.;
+        const BASE_EMISSION: nalgebra_glm::DVec3 = nalgebra_glm::DVec3::new(0., 0., 0.);
+        const LIGHT_EMISSION: nalgebra_glm::DVec3 = nalgebra_glm::DVec3::new(5_000., 5_000., 5_000.);
 
         let mut rscene: Scene = Scene::new_with_vec_storage();
 
@@ -6,7 +6,7 @@ use crate::{
             SceneObject,
             SceneObjectIntersection
         },
-        storage::SceneObjectStorage
+        storage::{SceneObjectStorage, index_in}
     }
 };
 
@@ -42,8 +42,8 @@ impl AxisAlignedBoundingBox {
 
     pub fn new_infinity_box() -> Self {
         Self {
-            min: nalgebra_glm::DVec3::from_element(std::f64::NEG_INFINITY),
-            max: nalgebra_glm::DVec3::from_element(std::f64::INFINITY),
+            min: nalgebra_glm::DVec3::from_element(f64::NEG_INFINITY),
+            max: nalgebra_glm::DVec3::from_element(f64::INFINITY),
         }
     }
 
@@ -160,13 +160,27 @@ impl BoundingVolumeHierarchyNode {
         }
     }
 
-    pub fn find_intersection<'a>(&'a self, ray: &Ray, objects: &'a [SceneObject], closest_int: f64) -> Option<SceneObjectIntersection> {
+    pub fn find_intersection<'a>(&'a self, ray: &Ray, objects: &'a [SceneObject], closest_int: f64) -> Option<SceneObjectIntersection<'a>> {
+        self.find_intersection_with_tests(ray, objects, closest_int, &mut 0)
+    }
+
+    /// Same as `find_intersection`, adding the number of bounding boxes and objects tested against the ray to `tests`
+    pub fn find_intersection_with_tests<'a>(
+        &'a self,
+        ray: &Ray,
+        objects: &'a [SceneObject],
+        closest_int: f64,
+        tests: &mut usize
+    ) -> Option<SceneObjectIntersection<'a>> {
+        *tests += 1;
         if self.bounding_box().intersect(ray, closest_int) {
             match self {
                 BoundingVolumeHierarchyNode::Leaf { aabb: _, object_cout, first_index } => {
                     let slc = &objects[*first_index..(first_index + object_cout)];
+                    *tests += slc.len();
                     slc.iter()
-                        .filter_map(|obj| obj.intersect(ray).map(|int| (obj, int.0, int.1, int.2)))
+                        // Fully transparent parts of the objects can't be hit
+                        .filter_map(|obj| obj.intersect_visible(ray).map(|int| (obj, int.0, int.1, int.2)))
                         .filter(|(_, _, _, t)| t < &closest_int)
                         .filter(|(_, _, _, t)| t >= &SELFINTERSECTION_TOLERANCE)
                         .min_by(|(_, _, _, rt), (_, _, _, lt)| rt.total_cmp(lt))
@@ -178,12 +192,12 @@ impl BoundingVolumeHierarchyNode {
                     left,
                     right
                 } => {
-                    let left_int = left.as_ref().as_ref().and_then(|l| l.find_intersection(ray, objects, closest_int));
+                    let left_int = left.as_ref().as_ref().and_then(|l| l.find_intersection_with_tests(ray, objects, closest_int, tests));
                     let t = match &left_int {
                         Some(a) => a.ray_length(),
                         None => closest_int
                     };
-                    let right_int = right.as_ref().as_ref().and_then(|l| l.find_intersection(ray, objects, t));
+                    let right_int = right.as_ref().as_ref().and_then(|l| l.find_intersection_with_tests(ray, objects, t, tests));
                     if right_int.is_some() {
                         right_int
                     } else {
@@ -275,24 +289,29 @@ impl BoundingVolumeHierarchy {
 }
 
 impl SceneObjectStorage for BoundingVolumeHierarchy {
-    fn find_intersection(&self, ray: &Ray) -> Option<SceneObjectIntersection> {
+    fn find_intersection(&self, ray: &Ray) -> Option<SceneObjectIntersection<'_>> {
+        self.find_intersection_with_tests(ray).0
+    }
+
+    fn find_intersection_with_tests(&self, ray: &Ray) -> (Option<SceneObjectIntersection<'_>>, usize) {
         if self.needs_rebuild {
-            None
+            (None, 0)
         } else {
             let unb_int = self.unbounded.find_intersection(ray);
+            let mut tests = self.unbounded.len();
             let t = match &unb_int {
                 Some(int) => int.ray_length(),
-                None => std::f64::INFINITY
+                None => f64::INFINITY
             };
             let b_int = self.tree.as_ref().and_then(
                 |root| {
-                    root.find_intersection(ray, &self.bounded, t)
+                    root.find_intersection_with_tests(ray, &self.bounded, t, &mut tests)
                 }
             );
             if b_int.is_some() {
-                b_int
+                (b_int, tests)
             } else {
-                unb_int
+                (unb_int, tests)
             }
         }
     }
@@ -310,6 +329,20 @@ impl SceneObjectStorage for BoundingVolumeHierarchy {
     fn rebuild(&mut self) {
         self.rebuild(1)
     }
+
+    fn object_count(&self) -> usize {
+        self.unbounded.len() + self.bounded.len()
+    }
+
+    fn object(&self, index: usize) -> Option<&SceneObject> {
+        self.unbounded.get(index)
+            .or_else(|| self.bounded.get(index - self.unbounded.len()))
+    }
+
+    fn object_index(&self, object: &SceneObject) -> Option<usize> {
+        index_in(&self.unbounded, object)
+            .or_else(|| index_in(&self.bounded, object).map(|index| index + self.unbounded.len()))
+    }
 }
 
 impl Default for BoundingVolumeHierarchy {
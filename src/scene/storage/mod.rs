@@ -1,17 +1,41 @@
 mod bvh;
-pub use bvh::{AxisAlignedBoundingBox, Axis, BoundingVolumeHierarchy};
+pub use bvh::{AxisAlignedBoundingBox, Axis, BoundingVolumeHierarchy, BoundingVolumeHierarchyNode};
 
 use crate::{scene::obj::{SceneObject, SceneObjectIntersection, SELFINTERSECTION_TOLERANCE}, common::Ray};
 pub trait SceneObjectStorage: std::marker::Sync {
-    fn find_intersection(&self, ray: &Ray) -> Option<SceneObjectIntersection>;
+    fn find_intersection(&self, ray: &Ray) -> Option<SceneObjectIntersection<'_>>;
     fn insert_object(&mut self, obj: SceneObject);
     fn rebuild(&mut self);
+    /// Number of objects in the storage
+    fn object_count(&self) -> usize;
+    /// Object at `index`, indices can change when the storage is rebuilt
+    fn object(&self, index: usize) -> Option<&SceneObject>;
+
+    /// Index of `object` in the storage, see `object`
+    fn object_index(&self, object: &SceneObject) -> Option<usize> {
+        (0..self.object_count()).find(|index| self.object(*index).is_some_and(|candidate| std::ptr::eq(candidate, object)))
+    }
+
+    /// Same as `find_intersection`, also returning the number of bounding boxes and objects tested against the ray
+    fn find_intersection_with_tests(&self, ray: &Ray) -> (Option<SceneObjectIntersection<'_>>, usize) {
+        (self.find_intersection(ray), self.object_count())
+    }
+}
+
+/// Index of `object` in `objects`, if it is stored there
+fn index_in(objects: &[SceneObject], object: &SceneObject) -> Option<usize> {
+    let offset = (object as *const SceneObject as usize).checked_sub(objects.as_ptr() as usize)?
+        / std::mem::size_of::<SceneObject>().max(1);
+    objects.get(offset)
+        .filter(|candidate| std::ptr::eq(*candidate, object))
+        .map(|_| offset)
 }
 
 impl SceneObjectStorage for Vec<SceneObject> {
-    fn find_intersection(&self, ray: &Ray) -> Option<SceneObjectIntersection> {
+    fn find_intersection(&self, ray: &Ray) -> Option<SceneObjectIntersection<'_>> {
         self.iter()
-            .filter_map(|obj| obj.intersect(ray).map(|int| (obj, int.0, int.1, int.2)))
+            // Fully transparent parts of the objects can't be hit
+            .filter_map(|obj| obj.intersect_visible(ray).map(|int| (obj, int.0, int.1, int.2)))
             .filter(|(_, _, _, d)| d >= &SELFINTERSECTION_TOLERANCE)
             .min_by(|(_, _, _, rd), (_, _, _, ld)| rd.total_cmp(ld))
             .map(|(obj, hp, n, d)| SceneObjectIntersection::new(obj, hp, n, d))
@@ -22,4 +46,73 @@ impl SceneObjectStorage for Vec<SceneObject> {
     }
 
     fn rebuild(&mut self) {}
+
+    fn object_count(&self) -> usize {
+        self.len()
+    }
+
+    fn object(&self, index: usize) -> Option<&SceneObject> {
+        self.get(index)
+    }
+
+    fn object_index(&self, object: &SceneObject) -> Option<usize> {
+        index_in(self, object)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::scene::{obj::SceneObjectMaterial, texture::{CheckerboardTexture, TextureSpace}};
+
+    #[test]
+    fn transparent_objects_skipped() {
+        let objects = || [
+            // Fully transparent
+            SceneObject::new_sphere(
+                nalgebra_glm::DVec3::from_element(1.),
+                nalgebra_glm::zero(),
+                SceneObjectMaterial::Diffuse,
+                nalgebra_glm::DVec3::new(0.5, 0.5, -3.),
+                1.
+            ).with_opacity(0.),
+            // Transparent where the ray enters, opaque where it leaves
+            SceneObject::new_sphere(
+                nalgebra_glm::DVec3::from_element(1.),
+                nalgebra_glm::zero(),
+                SceneObjectMaterial::Diffuse,
+                nalgebra_glm::DVec3::new(0.5, 0.5, -6.),
+                1.
+            ).with_opacity_texture(Arc::new(CheckerboardTexture::new(
+                nalgebra_glm::DVec3::from_element(1.),
+                nalgebra_glm::zero(),
+                2.,
+                TextureSpace::Position
+            ))),
+            SceneObject::new_plane(
+                nalgebra_glm::DVec3::from_element(1.),
+                nalgebra_glm::zero(),
+                SceneObjectMaterial::Diffuse,
+                nalgebra_glm::DVec3::new(0., 0., -10.),
+                nalgebra_glm::DVec3::new(0., 0., 1.)
+            )
+        ];
+        let mut vec = vec![];
+        let mut bvh = BoundingVolumeHierarchy::new();
+        for (a, b) in objects().into_iter().zip(objects()) {
+            vec.insert_object(a);
+            bvh.insert_object(b);
+        }
+        SceneObjectStorage::rebuild(&mut bvh);
+
+        let ray = Ray::new(nalgebra_glm::DVec3::new(0.5, 0.5, 0.), nalgebra_glm::DVec3::new(0., 0., -1.));
+        for storage in [&vec as &dyn SceneObjectStorage, &bvh] {
+            let hit = storage.find_intersection(&ray).expect("The plane is behind the spheres");
+            approx::assert_relative_eq!(hit.ray_length(), 7., epsilon = 1e-9);
+            approx::assert_relative_eq!(hit.hit_point(), nalgebra_glm::DVec3::new(0.5, 0.5, -7.), epsilon = 1e-9);
+            assert_eq!(hit.opacity(), 1.);
+        }
+    }
 }
\ No newline at end of file
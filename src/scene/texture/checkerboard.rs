@@ -0,0 +1,51 @@
+use super::{Texture, TextureSpace};
+
+/// Alternates between two colors on a grid of squares, or cubes in `TextureSpace::Position`
+#[derive(Debug)]
+pub struct CheckerboardTexture {
+    even: nalgebra_glm::DVec3,
+    odd: nalgebra_glm::DVec3,
+    size: f64,
+    space: TextureSpace
+}
+
+impl CheckerboardTexture {
+    /// Creates a new checkerboard
+    ///
+    /// # Arguments
+    /// * `even` - color of the cell at the origin
+    /// * `odd` - color of the cells next to it
+    /// * `size` - length of the side of a cell
+    /// * `space` - coordinates used to place the cells
+    pub fn new(
+        even: nalgebra_glm::DVec3,
+        odd: nalgebra_glm::DVec3,
+        size: f64,
+        space: TextureSpace
+    ) -> Self {
+        Self {
+            even,
+            odd,
+            size,
+            space
+        }
+    }
+}
+
+impl Texture for CheckerboardTexture {
+    fn evaluate(
+        &self,
+        position: &nalgebra_glm::DVec3,
+        _normal: &nalgebra_glm::DVec3,
+        uv: &nalgebra_glm::DVec2
+    ) -> nalgebra_glm::DVec3 {
+        let cell = self.space.coordinates(position, uv)
+            .map(|c| (c / self.size).floor() as i64)
+            .sum();
+        if cell.rem_euclid(2) == 0 {
+            self.even
+        } else {
+            self.odd
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,57 @@
+use super::Texture;
+
+/// Direction in which a `GradientTexture` changes
+#[derive(Debug, Clone)]
+pub enum GradientAxis {
+    /// Along the `u` surface coordinate
+    U,
+    /// Along the `v` surface coordinate
+    V,
+    /// Along a world space direction, from `origin` to `origin + direction`
+    Direction {
+        origin: nalgebra_glm::DVec3,
+        direction: nalgebra_glm::DVec3
+    }
+}
+
+/// Linear interpolation between two colors
+///
+/// Outside of the `[0, 1]` range of the axis the color of the closest end is used.
+#[derive(Debug)]
+pub struct GradientTexture {
+    start: nalgebra_glm::DVec3,
+    end: nalgebra_glm::DVec3,
+    axis: GradientAxis
+}
+
+impl GradientTexture {
+    pub fn new(
+        start: nalgebra_glm::DVec3,
+        end: nalgebra_glm::DVec3,
+        axis: GradientAxis
+    ) -> Self {
+        Self {
+            start,
+            end,
+            axis
+        }
+    }
+}
+
+impl Texture for GradientTexture {
+    fn evaluate(
+        &self,
+        position: &nalgebra_glm::DVec3,
+        _normal: &nalgebra_glm::DVec3,
+        uv: &nalgebra_glm::DVec2
+    ) -> nalgebra_glm::DVec3 {
+        let t = match &self.axis {
+            GradientAxis::U => uv.x,
+            GradientAxis::V => uv.y,
+            GradientAxis::Direction { origin, direction } => {
+                (position - origin).dot(direction) / direction.magnitude_squared()
+            }
+        };
+        nalgebra_glm::lerp(&self.start, &self.end, t.clamp(0., 1.))
+    }
+}
\ No newline at end of file
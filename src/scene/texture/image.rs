@@ -0,0 +1,363 @@
+use super::{Texture, TextureError};
+
+/// How an `ImageTexture` is sampled outside of the `[0, 1]` UV range
+#[derive(Debug, Clone, Copy)]
+pub enum WrapMode {
+    /// Tiles the image
+    Repeat,
+    /// Extends the pixels on the border
+    Clamp,
+    /// Tiles the image, flipping every other tile
+    Mirror
+}
+
+impl WrapMode {
+    fn wrap(&self, i: i64, size: usize) -> usize {
+        let size = size as i64;
+        let i = match self {
+            WrapMode::Repeat => i.rem_euclid(size),
+            WrapMode::Clamp => i.clamp(0, size - 1),
+            WrapMode::Mirror => {
+                let i = i.rem_euclid(2 * size);
+                if i < size { i } else { 2 * size - 1 - i }
+            }
+        };
+        i as usize
+    }
+}
+
+/// Image mapped on the UV coordinates of the surface with bilinear filtering
+///
+/// `(0, 0)` is the bottom left corner of the image and `(1, 1)` the top right corner.
+#[derive(Debug)]
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<nalgebra_glm::DVec3>,
+    wrap: WrapMode,
+    scale: f64
+}
+
+impl ImageTexture {
+    /// Creates a texture from pixels in rows, from top to bottom
+    pub fn new(
+        width: usize,
+        height: usize,
+        pixels: Vec<nalgebra_glm::DVec3>,
+        wrap: WrapMode
+    ) -> Result<Self, TextureError> {
+        if width == 0 || height == 0 || pixels.len() != width * height {
+            Err(TextureError::ImageSizeError)
+        } else {
+            Ok(
+                Self {
+                    width,
+                    height,
+                    pixels,
+                    wrap,
+                    scale: 1.
+                }
+            )
+        }
+    }
+
+    /// Loads a PPM image, pixels are in `[0, 1]`
+    pub fn from_ppm(path: &str, wrap: WrapMode) -> Result<Self, TextureError> {
+        Self::read_ppm(std::fs::File::open(path)?, wrap)
+    }
+
+    /// Reads a PPM image, either plain (`P3`) or binary (`P6`), pixels are in `[0, 1]`
+    pub fn read_ppm(mut reader: impl std::io::Read, wrap: WrapMode) -> Result<Self, TextureError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        let mut pos = 0;
+        let magic = ppm_token(&bytes, &mut pos).ok_or(TextureError::ImageFormatError)?;
+        let mut header = [0; 3];
+        for value in header.iter_mut() {
+            *value = ppm_token(&bytes, &mut pos)
+                .and_then(|token| token.parse::<usize>().ok())
+                .ok_or(TextureError::ImageFormatError)?;
+        }
+        let [width, height, max] = header;
+        if max == 0 || max > u16::MAX as usize {
+            return Err(TextureError::ImageFormatError);
+        }
+        // Each value takes at least a byte, larger sizes can't be in the data
+        let count = width.checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(3))
+            .filter(|count| *count <= bytes.len().saturating_sub(pos))
+            .ok_or(TextureError::ImageSizeError)?;
+
+        let values = match magic {
+            "P3" => (0..count)
+                .map(|_| ppm_token(&bytes, &mut pos).and_then(|token| token.parse::<usize>().ok()))
+                .collect::<Option<Vec<_>>>()
+                .ok_or(TextureError::ImageFormatError)?,
+            "P6" => {
+                // A single whitespace separates the header from the data
+                let data = bytes.get(pos + 1..).ok_or(TextureError::ImageFormatError)?;
+                let values = if max < 256 {
+                    data.iter().map(|v| *v as usize).collect::<Vec<_>>()
+                } else {
+                    data.chunks_exact(2).map(|v| u16::from_be_bytes([v[0], v[1]]) as usize).collect()
+                };
+                if values.len() < count {
+                    return Err(TextureError::ImageFormatError);
+                }
+                values
+            },
+            _ => return Err(TextureError::ImageFormatError)
+        };
+
+        let pixels = values.chunks_exact(3)
+            .take(count / 3)
+            .map(|rgb| nalgebra_glm::DVec3::new(rgb[0] as f64, rgb[1] as f64, rgb[2] as f64) / max as f64)
+            .collect();
+        Self::new(width, height, pixels, wrap)
+    }
+
+    /// Loads a Radiance HDR image, pixels keep their high dynamic range
+    pub fn from_hdr(path: &str, wrap: WrapMode) -> Result<Self, TextureError> {
+        Self::read_hdr(std::fs::File::open(path)?, wrap)
+    }
+
+    /// Reads a Radiance HDR (RGBE) image, with flat or run length encoded scanlines
+    ///
+    /// Only the usual orientation, `-Y height +X width`, is supported.
+    pub fn read_hdr(mut reader: impl std::io::Read, wrap: WrapMode) -> Result<Self, TextureError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        let mut lines = bytes.split(|b| *b == b'\n');
+        let magic = lines.next().ok_or(TextureError::ImageFormatError)?;
+        if !magic.starts_with(b"#?") {
+            return Err(TextureError::ImageFormatError);
+        }
+        let mut pos = magic.len() + 1;
+        // Header variables end with an empty line
+        for line in lines.by_ref() {
+            pos += line.len() + 1;
+            if line.is_empty() {
+                break;
+            }
+            if line.starts_with(b"FORMAT=") && line != b"FORMAT=32-bit_rle_rgbe" {
+                return Err(TextureError::ImageFormatError);
+            }
+        }
+        let resolution = lines.next()
+            .and_then(|line| std::str::from_utf8(line).ok())
+            .ok_or(TextureError::ImageFormatError)?;
+        pos += resolution.len() + 1;
+        let (height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+            ["-Y", height, "+X", width] => (
+                height.parse::<usize>().map_err(|_| TextureError::ImageFormatError)?,
+                width.parse::<usize>().map_err(|_| TextureError::ImageFormatError)?
+            ),
+            _ => return Err(TextureError::ImageFormatError)
+        };
+
+        let data = bytes.get(pos..).ok_or(TextureError::ImageFormatError)?;
+        // Run length encoded channels take at least 2 bytes for 127 pixels, so a byte holds less than 16 pixels
+        let count = width.checked_mul(height)
+            .filter(|count| *count > 0 && *count / 16 <= data.len())
+            .ok_or(TextureError::ImageSizeError)?;
+        let mut pos = 0;
+        let mut pixels = Vec::with_capacity(count);
+        let mut scanline = vec![[0_u8; 4]; width];
+        for _ in 0..height {
+            let run_length = (8..32_768).contains(&width)
+                && data.get(pos..pos + 4).is_some_and(|head| head[0] == 2 && head[1] == 2 && head[2] & 0x80 == 0);
+            if run_length {
+                if ((data[pos + 2] as usize) << 8 | data[pos + 3] as usize) != width {
+                    return Err(TextureError::ImageFormatError);
+                }
+                pos += 4;
+                // Each channel of the scanline is encoded separately
+                for channel in 0..4 {
+                    let mut x = 0;
+                    while x < width {
+                        let count = *data.get(pos).ok_or(TextureError::ImageFormatError)? as usize;
+                        pos += 1;
+                        if count > 128 {
+                            let value = *data.get(pos).ok_or(TextureError::ImageFormatError)?;
+                            pos += 1;
+                            for pixel in scanline.get_mut(x..x + count - 128).ok_or(TextureError::ImageFormatError)? {
+                                pixel[channel] = value;
+                            }
+                            x += count - 128;
+                        } else {
+                            let values = data.get(pos..pos + count).ok_or(TextureError::ImageFormatError)?;
+                            for (pixel, value) in scanline.get_mut(x..x + count).ok_or(TextureError::ImageFormatError)?.iter_mut().zip(values) {
+                                pixel[channel] = *value;
+                            }
+                            pos += count;
+                            x += count;
+                        }
+                        if count == 0 {
+                            return Err(TextureError::ImageFormatError);
+                        }
+                    }
+                }
+            } else {
+                let values = data.get(pos..).and_then(|rest| rest.get(..width * 4)).ok_or(TextureError::ImageFormatError)?;
+                for (pixel, rgbe) in scanline.iter_mut().zip(values.chunks_exact(4)) {
+                    pixel.copy_from_slice(rgbe);
+                }
+                pos += width * 4;
+            }
+            pixels.extend(
+                scanline.iter().map(
+                    |[r, g, b, e]| {
+                        if *e == 0 {
+                            nalgebra_glm::zero()
+                        } else {
+                            nalgebra_glm::DVec3::new(*r as f64, *g as f64, *b as f64) * 2_f64.powi(*e as i32 - 136)
+                        }
+                    }
+                )
+            );
+        }
+        Self::new(width, height, pixels, wrap)
+    }
+
+    /// Multiplies the pixels, useful to bring `[0, 1]` images to the range of the object colors
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn pixel(&self, x: i64, y: i64) -> nalgebra_glm::DVec3 {
+        self.pixels[self.wrap.wrap(y, self.height) * self.width + self.wrap.wrap(x, self.width)]
+    }
+}
+
+/// Next whitespace separated token of a PPM header, skipping comments
+fn ppm_token<'a>(bytes: &'a [u8], pos: &mut usize) -> Option<&'a str> {
+    loop {
+        match bytes.get(*pos)? {
+            b'#' => {
+                while bytes.get(*pos).is_some_and(|b| *b != b'\n') {
+                    *pos += 1;
+                }
+            },
+            b if b.is_ascii_whitespace() => *pos += 1,
+            _ => break
+        }
+    }
+    let start = *pos;
+    while bytes.get(*pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+        *pos += 1;
+    }
+    std::str::from_utf8(&bytes[start..*pos]).ok()
+}
+
+impl Texture for ImageTexture {
+    fn evaluate(
+        &self,
+        _position: &nalgebra_glm::DVec3,
+        _normal: &nalgebra_glm::DVec3,
+        uv: &nalgebra_glm::DVec2
+    ) -> nalgebra_glm::DVec3 {
+        // Pixel centers are at half integer coordinates
+        let x = uv.x * self.width as f64 - 0.5;
+        let y = (1. - uv.y) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = nalgebra_glm::lerp(&self.pixel(x0, y0), &self.pixel(x0 + 1, y0), fx);
+        let bottom = nalgebra_glm::lerp(&self.pixel(x0, y0 + 1), &self.pixel(x0 + 1, y0 + 1), fx);
+        nalgebra_glm::lerp(&top, &bottom, fy) * self.scale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(texture: &ImageTexture, u: f64, v: f64) -> nalgebra_glm::DVec3 {
+        texture.evaluate(&nalgebra_glm::zero(), &nalgebra_glm::zero(), &nalgebra_glm::DVec2::new(u, v))
+    }
+
+    fn two_by_one(wrap: WrapMode) -> ImageTexture {
+        ImageTexture::new(
+            2,
+            1,
+            vec![nalgebra_glm::DVec3::from_element(0.), nalgebra_glm::DVec3::from_element(1.)],
+            wrap
+        ).unwrap()
+    }
+
+    #[test]
+    fn bilinear_filtering() {
+        let texture = two_by_one(WrapMode::Clamp);
+        approx::assert_abs_diff_eq!(evaluate(&texture, 0.25, 0.5).x, 0.);
+        approx::assert_abs_diff_eq!(evaluate(&texture, 0.5, 0.5).x, 0.5);
+        approx::assert_abs_diff_eq!(evaluate(&texture, 0.75, 0.5).x, 1.);
+        approx::assert_abs_diff_eq!(evaluate(&texture, 0.625, 0.5).x, 0.75);
+    }
+
+    #[test]
+    fn wrap_modes() {
+        approx::assert_abs_diff_eq!(evaluate(&two_by_one(WrapMode::Clamp), 1.25, 0.5).x, 1.);
+        approx::assert_abs_diff_eq!(evaluate(&two_by_one(WrapMode::Repeat), 1.25, 0.5).x, 0.);
+        approx::assert_abs_diff_eq!(evaluate(&two_by_one(WrapMode::Mirror), 1.25, 0.5).x, 1.);
+        approx::assert_abs_diff_eq!(evaluate(&two_by_one(WrapMode::Mirror), 1.75, 0.5).x, 0.);
+        approx::assert_abs_diff_eq!(evaluate(&two_by_one(WrapMode::Repeat), 0., 0.5).x, 0.5);
+        approx::assert_abs_diff_eq!(evaluate(&two_by_one(WrapMode::Clamp), 0., 0.5).x, 0.);
+    }
+
+    #[test]
+    fn read_ppm() {
+        let plain = ImageTexture::read_ppm(
+            "P3\n# comment\n2 1\n255\n255 0 0  0 0 255\n".as_bytes(),
+            WrapMode::Clamp
+        ).unwrap();
+        assert_eq!(plain.dimensions(), (2, 1));
+        approx::assert_abs_diff_eq!(evaluate(&plain, 0.25, 0.5), nalgebra_glm::DVec3::new(1., 0., 0.));
+
+        let mut binary = b"P6 1 2 255\n".to_vec();
+        binary.extend_from_slice(&[0, 255, 0, 51, 51, 51]);
+        let binary = ImageTexture::read_ppm(binary.as_slice(), WrapMode::Clamp).unwrap().with_scale(2.);
+        approx::assert_abs_diff_eq!(evaluate(&binary, 0.5, 0.75), nalgebra_glm::DVec3::new(0., 2., 0.));
+        approx::assert_abs_diff_eq!(evaluate(&binary, 0.5, 0.25), nalgebra_glm::DVec3::from_element(0.4));
+
+        assert!(ImageTexture::read_ppm("P3 2 2 255 0 0 0".as_bytes(), WrapMode::Clamp).is_err());
+        assert!(ImageTexture::new(2, 2, vec![], WrapMode::Clamp).is_err());
+        for huge in ["P3\n18446744073709551615 2\n255\n", "P6 4294967296 4294967296 255\n", "P6 100000 100000 255\n0 0 0"] {
+            assert!(matches!(ImageTexture::read_ppm(huge.as_bytes(), WrapMode::Clamp), Err(TextureError::ImageSizeError)));
+        }
+    }
+
+    #[test]
+    fn read_hdr() {
+        let mut flat = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 1\n".to_vec();
+        flat.extend([128, 64, 0, 129, 128, 128, 128, 128]);
+        let texture = ImageTexture::read_hdr(flat.as_slice(), WrapMode::Clamp).unwrap();
+        assert_eq!(texture.dimensions(), (1, 2));
+        approx::assert_abs_diff_eq!(evaluate(&texture, 0.5, 0.75), nalgebra_glm::DVec3::new(1., 0.5, 0.));
+        approx::assert_abs_diff_eq!(evaluate(&texture, 0.5, 0.25), nalgebra_glm::DVec3::new(0.5, 0.5, 0.5));
+
+        // Runs and literals of each channel
+        let mut encoded = b"#?RGBE\n\n-Y 1 +X 8\n".to_vec();
+        encoded.extend([2, 2, 0, 8]);
+        encoded.extend([136, 128]);
+        encoded.extend([4, 0, 64, 128, 192, 132, 0]);
+        encoded.extend([136, 0]);
+        encoded.extend([136, 129]);
+        let texture = ImageTexture::read_hdr(encoded.as_slice(), WrapMode::Clamp).unwrap();
+        assert_eq!(texture.dimensions(), (8, 1));
+        approx::assert_abs_diff_eq!(evaluate(&texture, 2.5 / 8., 0.5), nalgebra_glm::DVec3::new(1., 1., 0.));
+        approx::assert_abs_diff_eq!(evaluate(&texture, 6.5 / 8., 0.5), nalgebra_glm::DVec3::new(1., 0., 0.));
+
+        assert!(matches!(ImageTexture::read_hdr(b"P3\n1 1\n".as_slice(), WrapMode::Clamp), Err(TextureError::ImageFormatError)));
+        for huge in ["-Y 4294967296 +X 4294967296", "-Y 100000 +X 100000"] {
+            let header = format!("#?RGBE\n\n{huge}\n");
+            assert!(matches!(ImageTexture::read_hdr(header.as_bytes(), WrapMode::Clamp), Err(TextureError::ImageSizeError)));
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,135 @@
+mod checkerboard;
+pub use checkerboard::CheckerboardTexture;
+
+mod gradient;
+pub use gradient::{GradientTexture, GradientAxis};
+
+mod noise;
+pub use noise::{PerlinNoise, NoiseTexture};
+
+mod image;
+pub use image::{ImageTexture, WrapMode};
+
+#[derive(Debug)]
+pub enum TextureError {
+    ImageSizeError,
+    ImageFormatError,
+    ImageReadError(std::io::Error)
+}
+
+impl std::fmt::Display for TextureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let m = match self {
+            TextureError::ImageSizeError => String::from("The image must have at least one pixel and exactly width times height pixels."),
+            TextureError::ImageFormatError => String::from("The image is not a valid PPM (P3 or P6) image."),
+            TextureError::ImageReadError(err) => format!("Could not read the image: {err}")
+        };
+        writeln!(f, "{m}")
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+impl From<std::io::Error> for TextureError {
+    fn from(value: std::io::Error) -> Self {
+        TextureError::ImageReadError(value)
+    }
+}
+
+/// Coordinates used by procedural textures
+#[derive(Debug, Clone, Copy)]
+pub enum TextureSpace {
+    /// Surface coordinates of the geometry
+    Uv,
+    /// World space position of the hit point
+    Position
+}
+
+impl TextureSpace {
+    fn coordinates(
+        &self,
+        position: &nalgebra_glm::DVec3,
+        uv: &nalgebra_glm::DVec2
+    ) -> nalgebra_glm::DVec3 {
+        match self {
+            TextureSpace::Uv => nalgebra_glm::DVec3::new(uv.x, uv.y, 0.),
+            TextureSpace::Position => *position
+        }
+    }
+}
+
+/// Color that varies over the surface of an object
+pub trait Texture: std::fmt::Debug + std::marker::Sync + std::marker::Send {
+    /// Evaluates the texture on a hit point
+    ///
+    /// # Arguments
+    /// * `position` - position of the hit point
+    /// * `normal` - surface normal at the hit point
+    /// * `uv` - surface coordinates of the hit point
+    fn evaluate(
+        &self,
+        position: &nalgebra_glm::DVec3,
+        normal: &nalgebra_glm::DVec3,
+        uv: &nalgebra_glm::DVec2
+    ) -> nalgebra_glm::DVec3;
+}
+
+/// Same color everywhere
+#[derive(Debug)]
+pub struct ConstantTexture(pub nalgebra_glm::DVec3);
+
+impl Texture for ConstantTexture {
+    fn evaluate(
+        &self,
+        _position: &nalgebra_glm::DVec3,
+        _normal: &nalgebra_glm::DVec3,
+        _uv: &nalgebra_glm::DVec2
+    ) -> nalgebra_glm::DVec3 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(texture: &dyn Texture, position: nalgebra_glm::DVec3, u: f64, v: f64) -> nalgebra_glm::DVec3 {
+        texture.evaluate(&position, &nalgebra_glm::DVec3::new(0., 1., 0.), &nalgebra_glm::DVec2::new(u, v))
+    }
+
+    #[test]
+    fn checkerboard_alternates() {
+        let even = nalgebra_glm::DVec3::from_element(1.);
+        let odd = nalgebra_glm::DVec3::from_element(0.);
+        let uv = CheckerboardTexture::new(even, odd, 0.5, TextureSpace::Uv);
+        assert_eq!(evaluate(&uv, nalgebra_glm::zero(), 0.25, 0.25), even);
+        assert_eq!(evaluate(&uv, nalgebra_glm::zero(), 0.75, 0.25), odd);
+        assert_eq!(evaluate(&uv, nalgebra_glm::zero(), 0.75, 0.75), even);
+        assert_eq!(evaluate(&uv, nalgebra_glm::zero(), -0.25, 0.25), odd);
+
+        let solid = CheckerboardTexture::new(even, odd, 1., TextureSpace::Position);
+        assert_eq!(evaluate(&solid, nalgebra_glm::DVec3::new(0.5, 0.5, 0.5), 0., 0.), even);
+        assert_eq!(evaluate(&solid, nalgebra_glm::DVec3::new(0.5, 0.5, 1.5), 0., 0.), odd);
+        assert_eq!(evaluate(&solid, nalgebra_glm::DVec3::new(-0.5, 0.5, 1.5), 0., 0.), even);
+    }
+
+    #[test]
+    fn gradient_interpolates() {
+        let start = nalgebra_glm::DVec3::from_element(0.);
+        let end = nalgebra_glm::DVec3::new(2., 4., 6.);
+        let along_v = GradientTexture::new(start, end, GradientAxis::V);
+        approx::assert_abs_diff_eq!(evaluate(&along_v, nalgebra_glm::zero(), 0.9, 0.5), end / 2.);
+        approx::assert_abs_diff_eq!(evaluate(&along_v, nalgebra_glm::zero(), 0.9, 1.5), end);
+
+        let along_direction = GradientTexture::new(
+            start,
+            end,
+            GradientAxis::Direction {
+                origin: nalgebra_glm::DVec3::new(0., 1., 0.),
+                direction: nalgebra_glm::DVec3::new(0., 4., 0.)
+            }
+        );
+        approx::assert_abs_diff_eq!(evaluate(&along_direction, nalgebra_glm::DVec3::new(7., 2., 3.), 0., 0.), end / 4.);
+        approx::assert_abs_diff_eq!(evaluate(&along_direction, nalgebra_glm::DVec3::new(7., -2., 3.), 0., 0.), start);
+    }
+}
\ No newline at end of file
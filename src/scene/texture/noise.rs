@@ -0,0 +1,180 @@
+use rand::{SeedableRng, seq::SliceRandom};
+
+use super::{Texture, TextureSpace};
+
+/// Frequency multiplier between octaves of fBm
+const LACUNARITY: f64 = 2.;
+/// Amplitude multiplier between octaves of fBm
+const GAIN: f64 = 0.5;
+
+/// Ken Perlin's improved gradient noise
+#[derive(Debug, Clone)]
+pub struct PerlinNoise {
+    permutation: Vec<usize>
+}
+
+impl PerlinNoise {
+    /// Creates the noise, different seeds give different patterns
+    pub fn new(seed: u64) -> Self {
+        let mut permutation = (0..256).collect::<Vec<usize>>();
+        permutation.shuffle(&mut rand::rngs::StdRng::seed_from_u64(seed));
+        // Repeated so that lookups don't need to wrap
+        permutation.extend_from_within(..);
+        Self {
+            permutation
+        }
+    }
+
+    /// Noise at `point`, roughly in `[-1, 1]` and zero on integer coordinates
+    pub fn noise(&self, point: &nalgebra_glm::DVec3) -> f64 {
+        let cell = point.map(f64::floor);
+        let (x, y, z) = (point.x - cell.x, point.y - cell.y, point.z - cell.z);
+        let [cx, cy, cz] = [cell.x, cell.y, cell.z].map(|c| c.rem_euclid(256.) as usize);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let p = &self.permutation;
+        let a = p[cx] + cy;
+        let aa = p[a] + cz;
+        let ab = p[a + 1] + cz;
+        let b = p[cx + 1] + cy;
+        let ba = p[b] + cz;
+        let bb = p[b + 1] + cz;
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, gradient(p[aa], x, y, z), gradient(p[ba], x - 1., y, z)),
+                lerp(u, gradient(p[ab], x, y - 1., z), gradient(p[bb], x - 1., y - 1., z))
+            ),
+            lerp(
+                v,
+                lerp(u, gradient(p[aa + 1], x, y, z - 1.), gradient(p[ba + 1], x - 1., y, z - 1.)),
+                lerp(u, gradient(p[ab + 1], x, y - 1., z - 1.), gradient(p[bb + 1], x - 1., y - 1., z - 1.))
+            )
+        )
+    }
+
+    /// Fractional Brownian motion, sum of `octaves` layers of noise with increasing frequency
+    ///
+    /// The result is normalized to the same range as `noise`.
+    pub fn fbm(&self, point: &nalgebra_glm::DVec3, octaves: usize) -> f64 {
+        let (sum, total, _, _) = (0..octaves.max(1))
+            .fold(
+                (0., 0., 1., 1.),
+                |(sum, total, amplitude, frequency), _| {
+                    (
+                        sum + self.noise(&(point * frequency)) * amplitude,
+                        total + amplitude,
+                        amplitude * GAIN,
+                        frequency * LACUNARITY
+                    )
+                }
+            );
+        sum / total
+    }
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+/// Dot product between the offset and one of 12 gradients chosen by `hash`
+fn gradient(hash: usize, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 { y } else if h == 12 || h == 14 { x } else { z };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+/// Interpolates between two colors using Perlin noise or fBm
+#[derive(Debug)]
+pub struct NoiseTexture {
+    noise: PerlinNoise,
+    low: nalgebra_glm::DVec3,
+    high: nalgebra_glm::DVec3,
+    scale: f64,
+    octaves: usize,
+    space: TextureSpace
+}
+
+impl NoiseTexture {
+    /// Creates a new single octave noise texture
+    ///
+    /// # Arguments
+    /// * `low` - color where the noise is at its lowest
+    /// * `high` - color where the noise is at its highest
+    /// * `scale` - frequency of the noise, bigger values make smaller features
+    /// * `space` - coordinates where the noise is evaluated
+    pub fn new(
+        low: nalgebra_glm::DVec3,
+        high: nalgebra_glm::DVec3,
+        scale: f64,
+        space: TextureSpace
+    ) -> Self {
+        Self {
+            noise: PerlinNoise::new(0),
+            low,
+            high,
+            scale,
+            octaves: 1,
+            space
+        }
+    }
+
+    /// Changes the pattern of the noise
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.noise = PerlinNoise::new(seed);
+        self
+    }
+
+    /// Number of layers of fBm, `1` is plain Perlin noise
+    pub fn with_octaves(mut self, octaves: usize) -> Self {
+        self.octaves = octaves.max(1);
+        self
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn evaluate(
+        &self,
+        position: &nalgebra_glm::DVec3,
+        _normal: &nalgebra_glm::DVec3,
+        uv: &nalgebra_glm::DVec2
+    ) -> nalgebra_glm::DVec3 {
+        let point = self.space.coordinates(position, uv) * self.scale;
+        let t = (self.noise.fbm(&point, self.octaves) * 0.5 + 0.5).clamp(0., 1.);
+        nalgebra_glm::lerp(&self.low, &self.high, t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn noise_is_bounded_and_zero_on_lattice() {
+        let noise = PerlinNoise::new(7);
+        for i in 0..2_000 {
+            let p = nalgebra_glm::DVec3::new(i as f64 * 0.173, i as f64 * -0.291, i as f64 * 0.057);
+            let n = noise.noise(&p);
+            assert!((-1.1..=1.1).contains(&n), "{n}");
+            assert!((-1.1..=1.1).contains(&noise.fbm(&p, 5)));
+        }
+        approx::assert_abs_diff_eq!(noise.noise(&nalgebra_glm::DVec3::new(3., -4., 12.)), 0.);
+    }
+
+    #[test]
+    fn noise_is_continuous_and_seeded() {
+        let noise = PerlinNoise::new(7);
+        let p = nalgebra_glm::DVec3::new(1.37, 2.71, -0.5);
+        let q = p + nalgebra_glm::DVec3::from_element(1e-6);
+        approx::assert_abs_diff_eq!(noise.noise(&p), noise.noise(&q), epsilon = 1e-4);
+        approx::assert_abs_diff_eq!(noise.noise(&p), PerlinNoise::new(7).noise(&p));
+        assert_ne!(noise.noise(&p), PerlinNoise::new(8).noise(&p));
+    }
+}
\ No newline at end of file
@@ -7,4 +7,16 @@ pub use russian_roulette_terminator::RussianRouletteTerminator;
 pub trait Terminator: std::marker::Sync {
     fn terminate(&self, depth: usize) -> bool;
     fn factor(&self, depth: usize) -> f64;
+
+    /// Decides if a path that carries `throughput` continues at `depth`, for tracers that follow it in a loop
+    ///
+    /// Returns the factor that the light of the rest of the path is multiplied by, or `None` if it's terminated.
+    /// Defaults to `terminate` and `factor`.
+    fn continue_path(&self, depth: usize, _throughput: &nalgebra_glm::DVec3) -> Option<f64> {
+        if self.terminate(depth) {
+            None
+        } else {
+            Some(self.factor(depth))
+        }
+    }
 }
\ No newline at end of file
@@ -2,9 +2,13 @@ use crate::common::RandomGen;
 
 use super::*;
 
+/// Maximum probability of stopping a path by its throughput, so dim paths can still continue
+const MAX_THROUGHPUT_STOP_PROBABILITY: f64 = 0.95;
+
 pub struct RussianRouletteTerminator {
     roulette_start_depth: usize,
-    stop_probability: f64 
+    stop_probability: f64,
+    throughput: bool
 }
 
 impl RussianRouletteTerminator {
@@ -14,9 +18,19 @@ impl RussianRouletteTerminator {
     ) -> Self {
         Self {
             roulette_start_depth,
-            stop_probability
+            stop_probability,
+            throughput: false
         }
     }
+
+    /// Stops the paths of tracers that carry their throughput with probability `1 - max(throughput)`, up to `0.95`
+    ///
+    /// Bright paths always continue, and dim paths are stopped often. Tracers that don't carry the throughput,
+    /// see `Terminator::continue_path`, still use `stop_probability`.
+    pub fn with_throughput(mut self) -> Self {
+        self.throughput = true;
+        self
+    }
 }
 
 impl Terminator for RussianRouletteTerminator {
@@ -31,4 +45,15 @@ impl Terminator for RussianRouletteTerminator {
             1.
         }
     }
+
+    fn continue_path(&self, depth: usize, throughput: &nalgebra_glm::DVec3) -> Option<f64> {
+        if !self.throughput {
+            return (!self.terminate(depth)).then(|| self.factor(depth));
+        }
+        if depth < self.roulette_start_depth {
+            return Some(1.);
+        }
+        let stop_probability = (1. - throughput.max()).clamp(0., MAX_THROUGHPUT_STOP_PROBABILITY);
+        (RandomGen::rand2() >= stop_probability).then(|| 1. / (1. - stop_probability))
+    }
 }
\ No newline at end of file
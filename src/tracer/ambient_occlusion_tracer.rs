@@ -0,0 +1,172 @@
+use crate::{
+    scene::Scene,
+    common::{Ray, Frame},
+    sampler::Sampler,
+    renderer::RenderParams,
+    extension::vector_ext::OrthonormalVectorExt
+};
+
+use super::{Tracer, TracerCapabilities};
+
+/// Tracer that shades the first hit by how much of its surroundings are open, known as ambient occlusion
+///
+/// At the first hit, `samples` rays leave in directions distributed by the cosine with the normal, see
+/// `Sampler::cosine_hemisphere`, and the color of the object is scaled by the fraction that doesn't hit anything
+/// closer than `max_distance`. It gives contact shadows for a quick preview or a clay render, and ignores the
+/// lights and materials of the scene.
+pub struct AmbientOcclusionTracer {
+    sampler: Box<dyn Sampler>,
+    samples: usize,
+    max_distance: f64
+}
+
+impl AmbientOcclusionTracer {
+    /// Creates an ambient occlusion tracer
+    ///
+    /// # Arguments
+    /// * `samples` - number of rays cast at each hit
+    /// * `max_distance` - distance after which objects don't occlude the hit
+    pub fn new(sampler: Box<dyn Sampler>, samples: usize, max_distance: f64) -> Self {
+        Self {
+            sampler,
+            samples: samples.max(1),
+            max_distance
+        }
+    }
+
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    pub fn max_distance(&self) -> f64 {
+        self.max_distance
+    }
+
+    /// If a ray from `origin` reaches `max_distance` without hitting anything, crossing the surfaces that
+    /// camera rays also cross
+    fn is_open(&self, scene: &Scene, origin: nalgebra_glm::DVec3, direction: nalgebra_glm::DVec3) -> bool {
+        let mut origin = origin;
+        let mut travelled = 0.;
+        loop {
+            let Some(hit) = scene.find_intersection(&Ray::new(origin, direction)) else {
+                return true;
+            };
+            travelled += hit.ray_length();
+            if travelled >= self.max_distance {
+                return true;
+            }
+            if !hit.passes_through(hit.object().material().select(&hit)) {
+                return false;
+            }
+            origin = hit.hit_point();
+        }
+    }
+}
+
+impl Tracer for AmbientOcclusionTracer {
+    fn trace(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        _render_params: &RenderParams,
+        _depth: usize
+    ) -> nalgebra_glm::DVec3 {
+        let mut ray = ray;
+        let inter = loop {
+            let Some(inter) = scene.find_intersection(&ray) else {
+                return scene.environment_radiance(ray.direction());
+            };
+            if !inter.passes_through(inter.object().material().select(&inter)) {
+                break inter;
+            }
+            // Crosses interfaces and the transparent parts of surfaces
+            ray = Ray::new(inter.hit_point(), *ray.direction());
+        };
+        // Occlusion is measured on the side the ray arrived from
+        let normal = inter.shading_normal(ray.direction());
+        let normal = if normal.dot(ray.direction()) > 0. { -normal } else { normal };
+        let frame = Frame::new(normal, normal.orthonormal().0);
+        let hp = inter.hit_point();
+        let open = (0..self.samples)
+            .filter(
+                |_| {
+                    let direction = frame.to_world(&self.sampler.cosine_hemisphere());
+                    !inter.is_leaking(&direction, &normal) && self.is_open(scene, hp, direction)
+                }
+            )
+            .count();
+        inter.color() * (open as f64 / self.samples as f64)
+    }
+
+    fn capabilities() -> TracerCapabilities {
+        TracerCapabilities {
+            caustics: false,
+            fresnel: false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scene::obj::{SceneObject, SceneObjectMaterial}, sampler::RandomSampler};
+
+    #[test]
+    fn occlusion_of_corner() {
+        let mut scene = Scene::new_with_vec_storage();
+        scene.insert_object(SceneObject::new_plane(
+            nalgebra_glm::DVec3::new(0.5, 0.5, 0.5),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::new(0., 1., 0.)
+        ));
+        let params = RenderParams { refraction_index: 1.5, samples_per_pixel: 1 };
+        let ray = Ray::new(nalgebra_glm::DVec3::new(0.001, 1., 0.), nalgebra_glm::DVec3::new(0., -1., 0.));
+        let tracer = AmbientOcclusionTracer::new(Box::new(RandomSampler::new()), 100_000, f64::INFINITY);
+        // Nothing occludes the floor
+        approx::assert_relative_eq!(tracer.trace(ray.clone(), &scene, &params, 0), nalgebra_glm::DVec3::from_element(0.5));
+
+        scene.insert_object(SceneObject::new_plane(
+            nalgebra_glm::DVec3::new(0.5, 0.5, 0.5),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::new(1., 0., 0.)
+        ));
+        // A wall next to the hit covers half of the cosine weighted hemisphere
+        approx::assert_relative_eq!(tracer.trace(ray.clone(), &scene, &params, 0), nalgebra_glm::DVec3::from_element(0.25), max_relative = 0.02);
+        // Unless it's further than the maximum distance
+        let tracer = AmbientOcclusionTracer::new(Box::new(RandomSampler::new()), 16, 0.0005);
+        approx::assert_relative_eq!(tracer.trace(ray, &scene, &params, 0), nalgebra_glm::DVec3::from_element(0.5));
+    }
+
+    #[test]
+    fn occlusion_through_cutout() {
+        let params = RenderParams { refraction_index: 1.5, samples_per_pixel: 1 };
+        let ray = Ray::new(nalgebra_glm::DVec3::new(0.001, 1., 0.), nalgebra_glm::DVec3::new(0., -1., 0.));
+        let tracer = AmbientOcclusionTracer::new(Box::new(RandomSampler::new()), 100_000, f64::INFINITY);
+        let occlusion = |wall: SceneObject| {
+            let mut scene = Scene::new_with_vec_storage();
+            scene.insert_object(SceneObject::new_plane(
+                nalgebra_glm::DVec3::from_element(1.),
+                nalgebra_glm::zero(),
+                SceneObjectMaterial::Diffuse,
+                nalgebra_glm::zero(),
+                nalgebra_glm::DVec3::new(0., 1., 0.)
+            ));
+            scene.insert_object(wall);
+            tracer.trace(ray.clone(), &scene, &params, 0)
+        };
+        let wall = |material: SceneObjectMaterial| SceneObject::new_plane(
+            nalgebra_glm::DVec3::from_element(1.),
+            nalgebra_glm::zero(),
+            material,
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::new(1., 0., 0.)
+        );
+        // Half of the rays towards the wall go through it
+        approx::assert_relative_eq!(occlusion(wall(SceneObjectMaterial::Diffuse).with_opacity(0.5)), nalgebra_glm::DVec3::from_element(0.75), max_relative = 0.02);
+        approx::assert_relative_eq!(occlusion(wall(SceneObjectMaterial::Interface)), nalgebra_glm::DVec3::from_element(1.));
+    }
+}
\ No newline at end of file
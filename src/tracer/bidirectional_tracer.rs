@@ -0,0 +1,746 @@
+use crate::{
+    scene::{Scene, obj::{SceneObjectMaterial, SceneObjectIntersection}, material::subsurface, light::Emitter},
+    common::{Ray, RandomGen},
+    sampler::Sampler,
+    renderer::{RenderParams, Splats},
+    terminator::Terminator,
+    camera::Camera
+};
+
+use super::{Tracer, TracerCapabilities, MisHeuristic, direct_light::{trace_shadow, sample_environment, shading_normal_correction}};
+
+enum VertexKind<'a> {
+    /// Pinhole of the camera
+    Camera,
+    /// Start of a path on a light
+    Light(Emitter<'a>),
+    Surface {
+        inter: SceneObjectIntersection<'a>,
+        material: &'a SceneObjectMaterial,
+        /// Direction of the ray that arrived at the vertex
+        incoming: nalgebra_glm::DVec3,
+        shading_normal: nalgebra_glm::DVec3
+    }
+}
+
+/// Vertex of a camera or light subpath
+struct Vertex<'a> {
+    kind: VertexKind<'a>,
+    point: nalgebra_glm::DVec3,
+    /// Geometric normal, zero for the camera and lights without area
+    normal: nalgebra_glm::DVec3,
+    /// Light, or importance, carried from the start of the subpath divided by the density of sampling it
+    beta: nalgebra_glm::DVec3,
+    /// If the vertex scatters with a delta distribution, so it can't be connected to another vertex
+    delta: bool,
+    /// Area density of sampling the vertex from the previous vertex of its subpath
+    pdf_fwd: f64,
+    /// Area density of sampling the vertex from the next vertex of its subpath, going the other way
+    pdf_rev: f64
+}
+
+/// Converts a solid angle density around `from` into an area density at `to`
+fn area_density(pdf: f64, from: &nalgebra_glm::DVec3, to: &nalgebra_glm::DVec3, normal: &nalgebra_glm::DVec3) -> f64 {
+    let offset = to - from;
+    let distance_squared = offset.norm_squared();
+    if distance_squared <= 0. {
+        return 0.;
+    }
+    // Points without area, like the camera, have no cosine
+    let cos = if *normal == nalgebra_glm::DVec3::zeros() {
+        1.
+    } else {
+        normal.dot(&offset).abs() / distance_squared.sqrt()
+    };
+    pdf * cos / distance_squared
+}
+
+impl Vertex<'_> {
+    /// Area density of this vertex sampling `next`, when it was reached from `previous`
+    fn pdf(&self, camera: Option<&dyn Camera>, previous: Option<&Vertex>, next: &Vertex) -> f64 {
+        let direction = (next.point - self.point).normalize();
+        let pdf = match &self.kind {
+            VertexKind::Camera => camera.map_or(0., |camera| camera.importance(&direction)),
+            VertexKind::Light(emitter) => emitter.emission_pdf(&self.normal, &direction),
+            VertexKind::Surface { inter, material, .. } => match previous {
+                Some(previous) => material.pdf(inter, &(self.point - previous.point).normalize(), &direction),
+                None => 0.
+            }
+        };
+        area_density(pdf, &self.point, &next.point, &next.normal)
+    }
+
+    /// Area density of a light path starting at this vertex, if it lies on a light
+    fn pdf_light_origin(&self, scene: &Scene) -> f64 {
+        match &self.kind {
+            VertexKind::Surface { inter, .. } if inter.object().is_light() => {
+                scene.emitter_selection_pdf() / inter.object().area()
+            },
+            _ => 0.
+        }
+    }
+
+    /// Area density of a light path leaving this vertex towards `next`, if it lies on a light
+    fn pdf_light(&self, next: &Vertex) -> f64 {
+        match &self.kind {
+            VertexKind::Surface { inter, .. } => {
+                let pdf = inter.object().emission_pdf(&self.normal, &(next.point - self.point));
+                area_density(pdf, &self.point, &next.point, &next.normal)
+            },
+            _ => 0.
+        }
+    }
+
+    /// Light scattered, or emitted, towards `direction`, times the cosine on the surface
+    ///
+    /// # Arguments
+    /// * `light_path` - if the vertex is part of a light subpath
+    fn response(&self, direction: &nalgebra_glm::DVec3, light_path: bool) -> nalgebra_glm::DVec3 {
+        match &self.kind {
+            VertexKind::Camera | VertexKind::Light(Emitter::Delta(_)) => nalgebra_glm::zero(),
+            VertexKind::Light(Emitter::Object(object)) => {
+                SceneObjectIntersection::new(object, self.point, self.normal, 0.).sampled_emission(&-direction)
+                    * self.normal.dot(direction).abs()
+            },
+            VertexKind::Surface { inter, material, incoming, shading_normal } => {
+                if inter.is_leaking(direction, shading_normal) {
+                    return nalgebra_glm::zero();
+                }
+                let response = material.eval(inter, incoming, direction) * shading_normal.dot(direction).abs();
+                if light_path {
+                    response * shading_normal_correction(&self.normal, shading_normal, &-incoming, direction)
+                } else {
+                    response
+                }
+            }
+        }
+    }
+
+    fn object_is(&self, other: &Vertex) -> bool {
+        match (&self.kind, &other.kind) {
+            (VertexKind::Surface { inter, .. }, VertexKind::Surface { inter: other, .. }) => std::ptr::eq(inter.object(), other.object()),
+            _ => false
+        }
+    }
+}
+
+/// Tracer that connects paths from the camera with paths from the lights, known as bidirectional path tracing
+///
+/// Each camera ray builds a subpath from the camera and another from a light chosen by `Scene::sample_emitter`,
+/// and connects every pair of their vertices with a shadow ray. The paths are weighted by the `MisHeuristic` over
+/// all the ways of building them, so caustics seen through mirrors and glass are found by the light subpaths.
+/// Light subpaths that reach the camera are added to other pixels with `Tracer::trace_pixel`, which `trace` can't do.
+///
+/// The environment is sampled and weighted like the `MisTracer`, and media are ignored like the `NextEventTracer`.
+/// The `Terminator` ends each subpath separately, so a `DepthTerminator` darkens the longest paths, which are
+/// weighted as if every way of building them was possible.
+pub struct BidirectionalTracer(Box<dyn Terminator>, Box<dyn Sampler>, MisHeuristic);
+
+impl BidirectionalTracer {
+    pub fn new(terminator: Box<dyn Terminator>, sampler: Box<dyn Sampler>, heuristic: MisHeuristic) -> Self {
+        Self(
+            terminator,
+            sampler,
+            heuristic
+        )
+    }
+
+    /// Follows a ray, adding a vertex for every bounce
+    ///
+    /// Returns the direction, the carried light, and the density of the last bounce if the path left the scene.
+    ///
+    /// # Arguments
+    /// * `pdf` - solid angle density of `ray` from the last vertex
+    /// * `light_path` - if the subpath started on a light
+    #[allow(clippy::too_many_arguments)]
+    fn random_walk<'a>(
+        &self,
+        scene: &'a Scene,
+        render_params: &RenderParams,
+        mut ray: Ray,
+        mut beta: nalgebra_glm::DVec3,
+        mut pdf: f64,
+        depth: usize,
+        light_path: bool,
+        vertices: &mut Vec<Vertex<'a>>
+    ) -> Option<(nalgebra_glm::DVec3, nalgebra_glm::DVec3, f64)> {
+        for depth in depth.. {
+            if self.0.terminate(depth) {
+                return None;
+            }
+            beta *= self.0.factor(depth);
+            let (inter, material) = loop {
+                let Some(inter) = scene.find_intersection(&ray) else {
+                    return Some((*ray.direction(), beta, pdf));
+                };
+                let material = inter.object().material().select(&inter);
+                if !inter.passes_through(material) {
+                    break (inter, material);
+                }
+                // Crosses the surface without bouncing
+                ray = Ray::new(inter.hit_point(), *ray.direction());
+            };
+            let previous = vertices.last()?;
+            let incoming = *ray.direction();
+            let hp = inter.hit_point();
+            let normal = inter.normal();
+            let shading_normal = inter.shading_normal(&incoming);
+            let pdf_fwd = area_density(pdf, &previous.point, &hp, &normal);
+
+            let bounce = match material {
+                SceneObjectMaterial::Subsurface { mean_free_path, albedo } => {
+                    // Light leaves from another point of the surface, which can't be connected to
+                    subsurface::scatter(&inter, &incoming, mean_free_path, albedo, render_params.refraction_index)
+                        .map(|(bounce, weight)| (bounce, weight, 0., true))
+                },
+                _ => match material.sample(&inter, &incoming, render_params.refraction_index, self.1.as_ref()) {
+                    Some(sample) if !inter.is_leaking(&sample.direction, &shading_normal) => {
+                        let weight = if light_path {
+                            sample.weight * shading_normal_correction(&normal, &shading_normal, &-incoming, &sample.direction)
+                        } else {
+                            sample.weight
+                        };
+                        Some((Ray::new(hp, sample.direction), weight, sample.pdf, sample.delta))
+                    },
+                    _ => None
+                }
+            };
+            let pdf_rev = match &bounce {
+                Some((bounce, _, _, false)) => material.pdf(&inter, &-bounce.direction(), &-incoming),
+                _ => 0.
+            };
+            let previous_index = vertices.len() - 1;
+            vertices.push(
+                Vertex {
+                    kind: VertexKind::Surface { inter, material, incoming, shading_normal },
+                    point: hp,
+                    normal,
+                    beta,
+                    delta: material.is_delta() || matches!(material, SceneObjectMaterial::Subsurface { .. }),
+                    pdf_fwd,
+                    pdf_rev: 0.
+                }
+            );
+            let (bounce, weight, bounce_pdf, delta) = bounce?;
+            let previous = &vertices[previous_index];
+            vertices[previous_index].pdf_rev = area_density(pdf_rev, &hp, &previous.point, &previous.normal);
+
+            beta = beta.component_mul(&weight);
+            if beta.max() <= 0. {
+                return None;
+            }
+            pdf = if delta { 0. } else { bounce_pdf };
+            ray = bounce;
+        }
+        None
+    }
+
+    /// Builds a subpath starting on one of the lights
+    fn light_subpath<'a>(&self, scene: &'a Scene, render_params: &RenderParams) -> Vec<Vertex<'a>> {
+        let mut vertices = Vec::new();
+        let Some((emitter, selection_pdf)) = scene.sample_emitter(RandomGen::rand2()) else {
+            return vertices;
+        };
+        let Some(emission) = emitter.sample_emission(
+            &nalgebra_glm::DVec2::new(RandomGen::rand2(), RandomGen::rand2()),
+            &nalgebra_glm::DVec2::new(RandomGen::rand2(), RandomGen::rand2())
+        ) else {
+            // Like directional lights, only reached with shadow rays
+            return vertices;
+        };
+        let pdf_position = selection_pdf * emission.pdf_position;
+        vertices.push(
+            Vertex {
+                kind: VertexKind::Light(emitter),
+                point: emission.origin,
+                normal: emission.normal,
+                beta: nalgebra_glm::DVec3::from_element(1. / pdf_position),
+                delta: false,
+                pdf_fwd: pdf_position,
+                pdf_rev: 0.
+            }
+        );
+        let cos = if emission.normal == nalgebra_glm::DVec3::zeros() {
+            1.
+        } else {
+            emission.normal.dot(&emission.direction).abs()
+        };
+        let beta = emission.radiance * (cos / (pdf_position * emission.pdf_direction));
+        self.random_walk(
+            scene,
+            render_params,
+            Ray::new(emission.origin, emission.direction),
+            beta,
+            emission.pdf_direction,
+            0,
+            true,
+            &mut vertices
+        );
+        vertices
+    }
+
+    /// Weight of the path made of the first `s` light vertices and the first `t` camera vertices
+    ///
+    /// # Arguments
+    /// * `sampled` - light vertex sampled for the connection, when `s` is `1`
+    #[allow(clippy::too_many_arguments)]
+    fn mis_weight(
+        &self,
+        scene: &Scene,
+        camera: Option<&dyn Camera>,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        sampled: Option<&Vertex>,
+        s: usize,
+        t: usize
+    ) -> f64 {
+        if s + t == 2 {
+            return 1.;
+        }
+        let mut light = light_path[..s.min(light_path.len())].iter()
+            .map(|vertex| (vertex.pdf_fwd, vertex.pdf_rev, vertex.delta))
+            .collect::<Vec<_>>();
+        let mut cam = camera_path[..t].iter()
+            .map(|vertex| (vertex.pdf_fwd, vertex.pdf_rev, vertex.delta))
+            .collect::<Vec<_>>();
+        let qs = if s == 1 { sampled } else { s.checked_sub(1).map(|i| &light_path[i]) };
+        if let (1, Some(sampled)) = (s, sampled) {
+            light = vec![(sampled.pdf_fwd, 0., false)];
+        }
+        let qs_minus = s.checked_sub(2).map(|i| &light_path[i]);
+        let pt = &camera_path[t - 1];
+        let pt_minus = t.checked_sub(2).map(|i| &camera_path[i]);
+
+        // Densities of building the connected vertices from the other subpath
+        cam[t - 1].1 = match qs {
+            Some(qs) => qs.pdf(camera, qs_minus, pt),
+            None => pt.pdf_light_origin(scene)
+        };
+        cam[t - 1].2 = false;
+        if let Some(pt_minus) = pt_minus {
+            cam[t - 2].1 = match qs {
+                Some(qs) => pt.pdf(camera, Some(qs), pt_minus),
+                None => pt.pdf_light(pt_minus)
+            };
+        }
+        if let Some(qs) = qs {
+            light[s - 1].1 = pt.pdf(camera, pt_minus, qs);
+            light[s - 1].2 = false;
+        }
+        if let (Some(qs), Some(qs_minus)) = (qs, qs_minus) {
+            light[s - 2].1 = qs.pdf(camera, Some(pt), qs_minus);
+        }
+
+        let remap = |pdf: f64| if pdf != 0. { pdf } else { 1. };
+        let ratio = |rev: f64, fwd: f64| {
+            let ratio = remap(rev) / remap(fwd);
+            match self.2 {
+                MisHeuristic::Balance => ratio,
+                MisHeuristic::Power => ratio * ratio
+            }
+        };
+        let mut sum = 0.;
+        let mut r = 1.;
+        for i in (1..t).rev() {
+            r *= ratio(cam[i].1, cam[i].0);
+            if !cam[i].2 && !cam[i - 1].2 {
+                sum += r;
+            }
+        }
+        let delta_light = qs.or(light_path.first())
+            .is_some_and(|vertex| matches!(vertex.kind, VertexKind::Light(Emitter::Delta(_))));
+        let mut r = 1.;
+        for i in (0..s).rev() {
+            r *= ratio(light[i].1, light[i].0);
+            let previous_delta = if i > 0 { light[i - 1].2 } else { delta_light };
+            if !light[i].2 && !previous_delta {
+                sum += r;
+            }
+        }
+        1. / (1. + sum)
+    }
+
+    /// Connects the camera vertex `pt` to a point sampled on one of the lights
+    fn connect_to_light(
+        &self,
+        scene: &Scene,
+        camera: Option<&dyn Camera>,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        t: usize
+    ) -> nalgebra_glm::DVec3 {
+        let zero = nalgebra_glm::zero();
+        let pt = &camera_path[t - 1];
+        let Some((emitter, selection_pdf)) = scene.sample_emitter(RandomGen::rand2()) else {
+            return zero;
+        };
+        let (radiance, sampled) = match emitter {
+            Emitter::Object(object) => {
+                let Some(sample) = object.sample_light(&pt.point, &nalgebra_glm::DVec2::new(RandomGen::rand2(), RandomGen::rand2())) else {
+                    return zero;
+                };
+                let response = pt.response(&sample.direction, false);
+                if response.max() <= 0. {
+                    return zero;
+                }
+                let Some((_, Some(hit))) = trace_shadow(scene, pt.point, &sample.direction, sample.distance, Some(object), None) else {
+                    return zero;
+                };
+                (
+                    response.component_mul(&hit.sampled_emission(&sample.direction)) / (selection_pdf * sample.pdf),
+                    Vertex {
+                        kind: VertexKind::Light(emitter),
+                        point: sample.point,
+                        normal: sample.normal,
+                        beta: zero,
+                        delta: false,
+                        pdf_fwd: selection_pdf / object.area(),
+                        pdf_rev: 0.
+                    }
+                )
+            },
+            Emitter::Delta(light) => {
+                let Some(sample) = light.illuminate(&pt.point) else {
+                    return zero;
+                };
+                let response = pt.response(&sample.direction, false);
+                if response.max() <= 0. {
+                    return zero;
+                }
+                if trace_shadow(scene, pt.point, &sample.direction, sample.distance, None, None).is_none() {
+                    return zero;
+                }
+                let radiance = response.component_mul(&sample.radiance) / selection_pdf;
+                if light.emission_pdf(&-sample.direction) <= 0. {
+                    // Lights that can't start paths, like directional lights, are only reached this way
+                    return radiance.component_mul(&pt.beta);
+                }
+                (
+                    radiance,
+                    Vertex {
+                        kind: VertexKind::Light(emitter),
+                        point: pt.point + sample.direction * sample.distance,
+                        normal: zero,
+                        beta: zero,
+                        delta: false,
+                        pdf_fwd: selection_pdf,
+                        pdf_rev: 0.
+                    }
+                )
+            }
+        };
+        radiance.component_mul(&pt.beta) * self.mis_weight(scene, camera, light_path, camera_path, Some(&sampled), 1, t)
+    }
+
+    /// Light arriving at the camera through `ray`
+    ///
+    /// Light subpaths that reach the camera are added to `splats`, if there is a camera.
+    fn trace_bidirectional(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        render_params: &RenderParams,
+        depth: usize,
+        camera: Option<(&dyn Camera, &Splats)>
+    ) -> nalgebra_glm::DVec3 {
+        let camera_pdf = camera.map_or(0., |(camera, _)| camera.importance(ray.direction()));
+        let mut camera_path = vec![
+            Vertex {
+                kind: VertexKind::Camera,
+                point: *ray.origin(),
+                normal: nalgebra_glm::zero(),
+                beta: nalgebra_glm::DVec3::from_element(1.),
+                // Light subpaths can't reach a camera without importance
+                delta: camera_pdf <= 0.,
+                pdf_fwd: 1.,
+                pdf_rev: 0.
+            }
+        ];
+        let escaped = self.random_walk(
+            scene,
+            render_params,
+            ray,
+            nalgebra_glm::DVec3::from_element(1.),
+            camera_pdf,
+            depth,
+            false,
+            &mut camera_path
+        );
+        let light_path = self.light_subpath(scene, render_params);
+        let camera = camera.filter(|_| camera_pdf > 0.);
+        let camera_model = camera.map(|(camera, _)| camera);
+        let mut radiance = nalgebra_glm::zero();
+
+        if let Some(environment) = scene.environment() {
+            // Weighted between sampling the environment and the bounces, like the `MisTracer`
+            if let Some((direction, beta, pdf)) = escaped {
+                let last = &camera_path[camera_path.len() - 1];
+                let weight = if matches!(last.kind, VertexKind::Camera) || last.delta {
+                    1.
+                } else {
+                    self.2.weight(pdf, environment.pdf(&direction))
+                };
+                radiance += environment.radiance(&direction).component_mul(&beta) * weight;
+            }
+            for vertex in camera_path.iter().filter(|vertex| !vertex.delta) {
+                if let VertexKind::Surface { inter, material, incoming, shading_normal } = &vertex.kind {
+                    if let Some(light) = sample_environment(inter, incoming, shading_normal, material, scene) {
+                        radiance += light.radiance.component_mul(&vertex.beta) * self.2.weight(light.light_pdf, light.bsdf_pdf);
+                    }
+                }
+            }
+        }
+
+        for t in 1..=camera_path.len() {
+            for s in 0..=light_path.len().max(1) {
+                if (s == 1 && t == 1) || s + t < 2 {
+                    continue;
+                }
+                let pt = &camera_path[t - 1];
+                if s == 0 {
+                    // The camera subpath hit a light
+                    let VertexKind::Surface { inter, incoming, .. } = &pt.kind else {
+                        continue;
+                    };
+                    let emission = inter.emission(incoming);
+                    if emission.max() <= 0. {
+                        continue;
+                    }
+                    let weight = if inter.object().is_light() {
+                        self.mis_weight(scene, camera_model, &light_path, &camera_path, None, 0, t)
+                    } else {
+                        // Emissive objects that can't be sampled are only found by the camera
+                        1.
+                    };
+                    radiance += emission.component_mul(&pt.beta) * weight;
+                    continue;
+                }
+                if pt.delta {
+                    continue;
+                }
+                if s == 1 {
+                    radiance += self.connect_to_light(scene, camera_model, &light_path, &camera_path, t);
+                    continue;
+                }
+                let qs = &light_path[s - 1];
+                if qs.delta {
+                    continue;
+                }
+                let offset = pt.point - qs.point;
+                let distance = offset.magnitude();
+                if distance <= 0. {
+                    continue;
+                }
+                let direction = offset / distance;
+                if t == 1 {
+                    // The light subpath reaches the camera
+                    let Some((camera, splats)) = camera else {
+                        continue;
+                    };
+                    let Some((x, y)) = camera.pixel(&-direction) else {
+                        continue;
+                    };
+                    let light = qs.beta.component_mul(&qs.response(&direction, true))
+                        * (camera.importance(&-direction) / (distance * distance));
+                    if light.max() <= 0. || trace_shadow(scene, qs.point, &direction, distance, None, None).is_none() {
+                        continue;
+                    }
+                    splats.add(x, y, light * self.mis_weight(scene, camera_model, &light_path, &camera_path, None, s, t));
+                    continue;
+                }
+                let light = qs.beta.component_mul(&qs.response(&direction, true))
+                    .component_mul(&pt.response(&-direction, false))
+                    .component_mul(&pt.beta) / (distance * distance);
+                if light.max() <= 0. || qs.object_is(pt) && distance <= f64::EPSILON {
+                    continue;
+                }
+                let VertexKind::Surface { inter, .. } = &qs.kind else {
+                    continue;
+                };
+                if trace_shadow(scene, pt.point, &-direction, distance, Some(inter.object()), None).is_none() {
+                    continue;
+                }
+                radiance += light * self.mis_weight(scene, camera_model, &light_path, &camera_path, None, s, t);
+            }
+        }
+        radiance
+    }
+}
+
+impl Tracer for BidirectionalTracer {
+    fn trace(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        render_params: &RenderParams,
+        depth: usize
+    ) -> nalgebra_glm::DVec3 {
+        self.trace_bidirectional(ray, scene, render_params, depth, None)
+    }
+
+    fn trace_pixel(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        render_params: &RenderParams,
+        camera: &dyn Camera,
+        _pixel: (usize, usize),
+        splats: &Splats
+    ) -> nalgebra_glm::DVec3 {
+        self.trace_bidirectional(ray, scene, render_params, 0, Some((camera, splats)))
+    }
+
+    fn capabilities() -> TracerCapabilities {
+        TracerCapabilities {
+            caustics: true,
+            fresnel: true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scene::obj::SceneObject, sampler::RandomSampler, terminator::DepthTerminator};
+
+    #[test]
+    fn direct_light_from_sphere() {
+        let mut scene = Scene::new_with_vec_storage();
+        scene.insert_object(SceneObject::new_plane(
+            nalgebra_glm::DVec3::from_element(1.),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::new(0., 1., 0.)
+        ));
+        // Black, so only the paths bouncing once on the plane carry light
+        scene.insert_object(SceneObject::new_sphere(
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::from_element(100.),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::DVec3::new(0., 4., 0.),
+            1.
+        ));
+
+        let params = RenderParams { refraction_index: 1.5, samples_per_pixel: 1 };
+        let ray = Ray::new(nalgebra_glm::DVec3::new(0., 1., 0.), nalgebra_glm::DVec3::new(0., -1., 0.));
+        const SAMPLES: usize = 8_000;
+        // Same as the `NextEventTracer`, the light is found by the bounce and by sampling it
+        let expected = 0.1 / 2. * 100. / 16.;
+        for heuristic in [MisHeuristic::Balance, MisHeuristic::Power] {
+            let tracer = BidirectionalTracer::new(Box::new(DepthTerminator::new(2)), Box::new(RandomSampler::new()), heuristic);
+            let radiance = (0..SAMPLES)
+                .map(|_| tracer.trace(ray.clone(), &scene, &params, 0))
+                .sum::<nalgebra_glm::DVec3>() / SAMPLES as f64;
+            approx::assert_relative_eq!(radiance, nalgebra_glm::DVec3::from_element(expected), max_relative = 0.03);
+        }
+    }
+
+    #[test]
+    fn point_light() {
+        use crate::scene::light::PointLight;
+
+        let mut scene = Scene::new_with_vec_storage();
+        scene.insert_object(SceneObject::new_plane(
+            nalgebra_glm::DVec3::from_element(1.),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::new(0., 1., 0.)
+        ));
+        scene.insert_delta_light(Box::new(PointLight::new(nalgebra_glm::DVec3::new(0., 2., 0.), nalgebra_glm::DVec3::from_element(100.))));
+
+        let tracer = BidirectionalTracer::new(Box::new(DepthTerminator::new(1)), Box::new(RandomSampler::new()), MisHeuristic::Power);
+        let params = RenderParams { refraction_index: 1.5, samples_per_pixel: 1 };
+        let ray = Ray::new(nalgebra_glm::DVec3::new(0.5, 1., 0.), nalgebra_glm::DVec3::new(-0.5, -1., 0.).normalize());
+        // Without a camera, the delta light can only be reached with a shadow ray
+        let expected = 0.1 / (2. * std::f64::consts::PI) * 100. / 4.;
+        approx::assert_relative_eq!(tracer.trace(ray, &scene, &params, 0), nalgebra_glm::DVec3::from_element(expected), max_relative = 1e-9);
+    }
+
+    #[test]
+    fn splats_light_subpaths() {
+        use crate::{
+            renderer::Renderer,
+            camera::SimpleCamera,
+            terminator::RussianRouletteTerminator,
+            tracer::MisTracer
+        };
+
+        // Closed room with the light outside of the view, the light subpaths reach the camera from the walls
+        let mut scene = Scene::new_with_vec_storage();
+        for (point, normal) in [
+            (nalgebra_glm::DVec3::new(0., -2., 0.), nalgebra_glm::DVec3::new(0., 1., 0.)),
+            (nalgebra_glm::DVec3::new(0., 3., 0.), nalgebra_glm::DVec3::new(0., -1., 0.)),
+            (nalgebra_glm::DVec3::new(-3., 0., 0.), nalgebra_glm::DVec3::new(1., 0., 0.)),
+            (nalgebra_glm::DVec3::new(3., 0., 0.), nalgebra_glm::DVec3::new(-1., 0., 0.)),
+            (nalgebra_glm::DVec3::new(0., 0., -8.), nalgebra_glm::DVec3::new(0., 0., 1.)),
+            (nalgebra_glm::DVec3::new(0., 0., 1.), nalgebra_glm::DVec3::new(0., 0., -1.))
+        ] {
+            scene.insert_object(SceneObject::new_plane(
+                nalgebra_glm::DVec3::from_element(0.5),
+                nalgebra_glm::zero(),
+                SceneObjectMaterial::Diffuse,
+                point,
+                normal
+            ));
+        }
+        scene.insert_object(SceneObject::new_sphere(
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::new(4., 3., 2.),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::DVec3::new(0., 1.7, 0.3),
+            1.2
+        ));
+
+        // The light subpaths that land in view are added to the pixels that see them
+        let camera = SimpleCamera::new(4., 4.);
+        let params = RenderParams { refraction_index: 1.5, samples_per_pixel: 1 };
+        let tracer = BidirectionalTracer::new(Box::new(DepthTerminator::new(1)), Box::new(RandomSampler::new()), MisHeuristic::Power);
+        let splats = Splats::new(4, 4);
+        for _ in 0..256 {
+            let ray = Ray::new(nalgebra_glm::zero(), camera.view_with_filtering(1., 1.).normalize());
+            tracer.trace_pixel(ray, &scene, &params, &camera, (1, 1), &splats);
+        }
+        assert!(splats.into_image().iter().any(|splat| splat.max() > 0.));
+
+        const SIZE: usize = 8;
+        let camera = SimpleCamera::new(SIZE as f64, SIZE as f64);
+        let mean = |tracer: &dyn Tracer| {
+            let mut renderer = Renderer::new(SIZE, SIZE, 1.5, 256);
+            renderer.render(tracer, &camera, &scene).unwrap();
+            let (image, _) = renderer.get_image();
+            image.iter().sum::<nalgebra_glm::DVec3>() / image.len() as f64
+        };
+        let mis = mean(&MisTracer::new(Box::new(RussianRouletteTerminator::new(3, 0.5)), Box::new(RandomSampler::new()), MisHeuristic::Power));
+        // Without the splats, the light subpaths that reach the camera are missing
+        let bidirectional = mean(&BidirectionalTracer::new(Box::new(RussianRouletteTerminator::new(3, 0.5)), Box::new(RandomSampler::new()), MisHeuristic::Power));
+        approx::assert_relative_eq!(bidirectional, mis, max_relative = 0.02);
+    }
+
+    #[cfg(feature = "sample-scenes")]
+    #[test]
+    fn matches_mis_tracer() {
+        use crate::{
+            renderer::Renderer,
+            camera::SimpleCamera,
+            terminator::RussianRouletteTerminator,
+            scene::sample::{SampleScene, ThreeCylindersWithLightsSampleScene},
+            tracer::MisTracer
+        };
+
+        const SIZE: usize = 8;
+        let scene = ThreeCylindersWithLightsSampleScene::build_sample_scene();
+        let camera = SimpleCamera::new(SIZE as f64, SIZE as f64);
+        let mean = |tracer: &dyn Tracer| {
+            let mut renderer = Renderer::new(SIZE, SIZE, 1.5, 128);
+            renderer.render(tracer, &camera, &scene).unwrap();
+            let (image, _) = renderer.get_image();
+            image.iter().sum::<nalgebra_glm::DVec3>() / image.len() as f64
+        };
+        let mis = mean(&MisTracer::new(Box::new(RussianRouletteTerminator::new(5, 0.1)), Box::new(RandomSampler::new()), MisHeuristic::Power));
+        // Includes the light subpaths that are splatted on the image
+        let bidirectional = mean(&BidirectionalTracer::new(Box::new(RussianRouletteTerminator::new(5, 0.1)), Box::new(RandomSampler::new()), MisHeuristic::Power));
+        approx::assert_relative_eq!(bidirectional, mis, max_relative = 0.05);
+    }
+}
\ No newline at end of file
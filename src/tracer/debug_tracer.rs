@@ -0,0 +1,155 @@
+use crate::{
+    scene::{Scene, obj::SceneObjectMaterial},
+    common::Ray,
+    renderer::RenderParams
+};
+
+use super::{Tracer, TracerCapabilities};
+
+/// Property of the first hit shown by the `DebugTracer`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugView {
+    /// Normal used for shading, on the side the ray arrived from, mapped from `[-1, 1]` to `[0, 1]`
+    ShadingNormal,
+    /// Normal of the geometry, mapped from `[-1, 1]` to `[0, 1]`
+    GeometricNormal,
+    /// Distance along the ray, divided by `max_distance`
+    Depth { max_distance: f64 },
+    /// Color picked by the index of the object in the storage of the scene
+    ObjectId,
+    /// Color of the kind of material, see `DebugTracer::material_color`
+    Material,
+    /// Texture coordinates in red and green
+    Uv,
+    /// Number of bounding boxes and objects tested to find the hit, from blue at none to red at `max_tests`
+    ///
+    /// Also shown for rays that miss everything.
+    IntersectionTests { max_tests: usize }
+}
+
+/// Tracer that shows a property of the first hit instead of light, to inspect a scene
+///
+/// Rays that miss everything are black, and materials and transparency are ignored.
+pub struct DebugTracer(DebugView);
+
+impl DebugTracer {
+    pub fn new(view: DebugView) -> Self {
+        Self(view)
+    }
+
+    pub fn view(&self) -> DebugView {
+        self.0
+    }
+
+    /// Color shown for `material` by `DebugView::Material`
+    pub fn material_color(material: &SceneObjectMaterial) -> nalgebra_glm::DVec3 {
+        match material {
+            SceneObjectMaterial::Diffuse => nalgebra_glm::DVec3::new(0.8, 0.8, 0.8),
+            SceneObjectMaterial::Specular => nalgebra_glm::DVec3::new(0.2, 0.4, 1.),
+            SceneObjectMaterial::Refractive => nalgebra_glm::DVec3::new(0.2, 1., 1.),
+            SceneObjectMaterial::Mix { .. } => nalgebra_glm::DVec3::new(1., 0., 1.),
+            SceneObjectMaterial::ThinFilm { .. } => nalgebra_glm::DVec3::new(1., 1., 0.),
+            SceneObjectMaterial::Anisotropic { .. } => nalgebra_glm::DVec3::new(1., 0.5, 0.),
+            SceneObjectMaterial::Principled(_) => nalgebra_glm::DVec3::new(0.2, 1., 0.2),
+            SceneObjectMaterial::Interface => nalgebra_glm::DVec3::new(0.3, 0.3, 0.3),
+            SceneObjectMaterial::Subsurface { .. } => nalgebra_glm::DVec3::new(1., 0.2, 0.2)
+        }
+    }
+}
+
+/// Fully saturated color of `hue`, in turns
+fn hue_color(hue: f64) -> nalgebra_glm::DVec3 {
+    let channel = |offset: f64| {
+        let distance = ((hue - offset).rem_euclid(1.) * 6. - 3.).abs();
+        (distance - 1.).clamp(0., 1.)
+    };
+    nalgebra_glm::DVec3::new(channel(0.), channel(1. / 3.), channel(2. / 3.))
+}
+
+impl Tracer for DebugTracer {
+    fn trace(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        _render_params: &RenderParams,
+        _depth: usize
+    ) -> nalgebra_glm::DVec3 {
+        let map_normal = |normal: nalgebra_glm::DVec3| (normal.normalize() + nalgebra_glm::DVec3::from_element(1.)) / 2.;
+        if let DebugView::IntersectionTests { max_tests } = self.0 {
+            let (_, tests) = scene.find_intersection_with_tests(&ray);
+            // Blue to red, over two thirds of the hues
+            return hue_color((1. - tests as f64 / max_tests.max(1) as f64).max(0.) * 2. / 3.);
+        }
+        let Some(inter) = scene.find_intersection(&ray) else {
+            return nalgebra_glm::zero();
+        };
+        match self.0 {
+            DebugView::ShadingNormal => map_normal(inter.shading_normal(ray.direction())),
+            DebugView::GeometricNormal => map_normal(inter.normal()),
+            DebugView::Depth { max_distance } => nalgebra_glm::DVec3::from_element(inter.ray_length() / max_distance),
+            DebugView::ObjectId => scene.object_index(inter.object())
+                // Golden ratio steps keep the hues of neighbouring indices apart
+                .map_or(nalgebra_glm::zero(), |index| hue_color(index as f64 * 0.618_033_988_749_895)),
+            DebugView::Material => Self::material_color(inter.object().material()),
+            DebugView::Uv => {
+                let uv = inter.uv();
+                nalgebra_glm::DVec3::new(uv.x, uv.y, 0.)
+            },
+            DebugView::IntersectionTests { .. } => nalgebra_glm::zero()
+        }
+    }
+
+    fn capabilities() -> TracerCapabilities {
+        TracerCapabilities {
+            caustics: false,
+            fresnel: false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::obj::SceneObject;
+
+    #[test]
+    fn views_of_sphere() {
+        let mut scene = Scene::new_with_bounding_volume_hierarchy();
+        for x in 0..32 {
+            scene.insert_object(SceneObject::new_sphere(
+                nalgebra_glm::DVec3::from_element(1.),
+                nalgebra_glm::zero(),
+                if x == 3 { SceneObjectMaterial::Specular } else { SceneObjectMaterial::Diffuse },
+                nalgebra_glm::DVec3::new(x as f64 * 3., 0., -5.),
+                1.
+            ));
+        }
+        scene.rebuild_storage();
+        let params = RenderParams { refraction_index: 1.5, samples_per_pixel: 1 };
+        let ray = Ray::new(nalgebra_glm::DVec3::new(9., 0., 0.), nalgebra_glm::DVec3::new(0., 0., -1.));
+        let trace = |view: DebugView, ray: Ray| DebugTracer::new(view).trace(ray, &scene, &params, 0);
+
+        approx::assert_relative_eq!(trace(DebugView::GeometricNormal, ray.clone()), nalgebra_glm::DVec3::new(0.5, 0.5, 1.), epsilon = 1e-9);
+        approx::assert_relative_eq!(trace(DebugView::ShadingNormal, ray.clone()), nalgebra_glm::DVec3::new(0.5, 0.5, 1.), epsilon = 1e-9);
+        approx::assert_relative_eq!(trace(DebugView::Depth { max_distance: 8. }, ray.clone()), nalgebra_glm::DVec3::from_element(0.5), epsilon = 1e-9);
+        assert_eq!(trace(DebugView::Material, ray.clone()), DebugTracer::material_color(&SceneObjectMaterial::Specular));
+
+        // Every sphere has its own color
+        let colors = (0..8)
+            .map(|x| trace(DebugView::ObjectId, Ray::new(nalgebra_glm::DVec3::new(x as f64 * 3., 0., 0.), nalgebra_glm::DVec3::new(0., 0., -1.))))
+            .collect::<Vec<_>>();
+        for (i, color) in colors.iter().enumerate() {
+            assert!(color.max() > 0.);
+            assert!(colors[i + 1..].iter().all(|other| (other - color).norm() > 0.1));
+        }
+
+        // The hierarchy skips most of the spheres
+        let (hit, tests) = scene.find_intersection_with_tests(&ray);
+        assert!(hit.is_some());
+        assert!(tests < 16, "{tests} tests");
+        assert_eq!(trace(DebugView::IntersectionTests { max_tests: 0 }, ray), nalgebra_glm::DVec3::new(1., 0., 0.));
+        let miss = Ray::new(nalgebra_glm::DVec3::new(0., 10., 0.), nalgebra_glm::DVec3::new(0., 1., 0.));
+        assert_eq!(trace(DebugView::IntersectionTests { max_tests: 100 }, miss.clone()).z, 1.);
+        assert_eq!(trace(DebugView::Uv, miss), nalgebra_glm::DVec3::zeros());
+    }
+}
\ No newline at end of file
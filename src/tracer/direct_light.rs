@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use crate::{
+    scene::{Scene, obj::{SceneObject, SceneObjectMaterial, SceneObjectIntersection}, medium::Medium},
+    common::{Ray, RandomGen}
+};
+
+/// Relative difference between the length of a shadow ray and the distance to the sampled light for it to be unoccluded
+const SHADOW_TOLERANCE: f64 = 1e-4;
+
+/// Follows a shadow ray towards a light
+///
+/// The shadow ray crosses the surfaces that bounces would also cross without scattering, interfaces and
+/// transparent parts of objects, changing medium like the `VolumetricTracer`.
+/// Returns the transmittance of the media along the ray, and the hit on `light` if it's an object.
+/// Returns `None` if the light is occluded.
+///
+/// # Arguments
+/// * `light` - object the shadow ray should hit at `distance`, `None` for lights without area
+/// * `medium` - medium the shadow ray starts in
+pub(super) fn trace_shadow<'a>(
+    scene: &'a Scene,
+    origin: nalgebra_glm::DVec3,
+    direction: &nalgebra_glm::DVec3,
+    distance: f64,
+    light: Option<&SceneObject>,
+    mut medium: Option<&'a Arc<dyn Medium>>
+) -> Option<(nalgebra_glm::DVec3, Option<SceneObjectIntersection<'a>>)> {
+    let mut origin = origin;
+    let mut travelled = 0.;
+    let mut transmittance = nalgebra_glm::DVec3::from_element(1.);
+    loop {
+        let ray = Ray::new(origin, *direction);
+        let remaining = distance - travelled;
+        let hit = scene.find_intersection(&ray);
+        let segment = hit.as_ref().map_or(remaining, |hit| hit.ray_length().min(remaining));
+        if let Some(medium) = medium {
+            transmittance = transmittance.component_mul(&medium.transmittance(&ray, segment));
+        }
+        let Some(hit) = hit else {
+            // Lights without area are reached by missing everything
+            return light.is_none().then_some((transmittance, None));
+        };
+        let reached = light.is_some_and(|light| std::ptr::eq(hit.object(), light))
+            && (hit.ray_length() - remaining).abs() <= SHADOW_TOLERANCE * distance.max(1.);
+        if reached {
+            return Some((transmittance, Some(hit)));
+        }
+        if hit.ray_length() >= remaining {
+            return light.is_none().then_some((transmittance, None));
+        }
+        if !hit.passes_through(hit.object().material().select(&hit)) {
+            return None;
+        }
+        medium = if direction.dot(&hit.normal()) < 0. {
+            hit.object().interior_medium()
+        } else {
+            scene.medium()
+        };
+        origin = hit.hit_point();
+        travelled += hit.ray_length();
+    }
+}
+
+/// Factor that keeps light paths symmetric with camera paths on surfaces with a shading normal
+///
+/// # Arguments
+/// * `previous` - direction towards the previous vertex of the light path
+/// * `next` - direction towards the next vertex
+pub(super) fn shading_normal_correction(
+    normal: &nalgebra_glm::DVec3,
+    shading_normal: &nalgebra_glm::DVec3,
+    previous: &nalgebra_glm::DVec3,
+    next: &nalgebra_glm::DVec3
+) -> f64 {
+    let denominator = previous.dot(normal).abs() * next.dot(shading_normal).abs();
+    if denominator > 0. {
+        previous.dot(shading_normal).abs() * next.dot(normal).abs() / denominator
+    } else {
+        0.
+    }
+}
+
+/// Light arriving from a point sampled on one of the lights of the `Scene`
+pub(super) struct DirectLight {
+    /// Emission of the light times the BSDF and cosine, divided by `light_pdf`
+    pub radiance: nalgebra_glm::DVec3,
+    /// Solid angle density of sampling the direction to the light, including the choice of the light
+    pub light_pdf: f64,
+    /// Solid angle density of the BSDF sampling the same direction
+    pub bsdf_pdf: f64
+}
+
+/// Samples a point on one of the lights and sends a shadow ray towards it
+///
+/// Returns `None` if the light is occluded, or if the BSDF doesn't scatter light in its direction.
+pub(super) fn sample_direct_light(
+    inter: &SceneObjectIntersection,
+    incoming: &nalgebra_glm::DVec3,
+    shading_normal: &nalgebra_glm::DVec3,
+    material: &SceneObjectMaterial,
+    scene: &Scene
+) -> Option<DirectLight> {
+    let (light, selection_pdf) = scene.sample_light(RandomGen::rand2())?;
+    let sample = light.sample_light(
+        &inter.hit_point(),
+        &nalgebra_glm::DVec2::new(RandomGen::rand2(), RandomGen::rand2())
+    )?;
+    if inter.is_leaking(&sample.direction, shading_normal) {
+        return None;
+    }
+    let bsdf = material.eval(inter, incoming, &sample.direction);
+    if bsdf.max() <= 0. {
+        return None;
+    }
+    let (_, shadow) = trace_shadow(scene, inter.hit_point(), &sample.direction, sample.distance, Some(light), None)?;
+    let emission = shadow?.sampled_emission(&sample.direction);
+    let light_pdf = selection_pdf * sample.pdf;
+    Some(
+        DirectLight {
+            radiance: bsdf.component_mul(&emission) * (shading_normal.dot(&sample.direction).abs() / light_pdf),
+            light_pdf,
+            bsdf_pdf: material.pdf(inter, incoming, &sample.direction)
+        }
+    )
+}
+
+/// Samples a direction towards the environment of the `Scene` and sends a shadow ray that must leave the scene
+///
+/// Returns `None` without an environment, if the shadow ray is occluded, or if the BSDF doesn't scatter light in
+/// its direction.
+pub(super) fn sample_environment(
+    inter: &SceneObjectIntersection,
+    incoming: &nalgebra_glm::DVec3,
+    shading_normal: &nalgebra_glm::DVec3,
+    material: &SceneObjectMaterial,
+    scene: &Scene
+) -> Option<DirectLight> {
+    let (direction, radiance, light_pdf) = scene.environment()?
+        .sample(&nalgebra_glm::DVec2::new(RandomGen::rand2(), RandomGen::rand2()))?;
+    if inter.is_leaking(&direction, shading_normal) {
+        return None;
+    }
+    let bsdf = material.eval(inter, incoming, &direction);
+    if bsdf.max() <= 0. || radiance.max() <= 0. {
+        return None;
+    }
+    let (transmittance, _) = trace_shadow(scene, inter.hit_point(), &direction, f64::INFINITY, None, None)?;
+    Some(
+        DirectLight {
+            radiance: bsdf.component_mul(&radiance).component_mul(&transmittance) * (shading_normal.dot(&direction).abs() / light_pdf),
+            light_pdf,
+            bsdf_pdf: material.pdf(inter, incoming, &direction)
+        }
+    )
+}
+
+/// Light arriving at `point` from every delta light of the `Scene`
+///
+/// # Arguments
+/// * `response` - fraction of the light arriving from a direction that is scattered, like the BSDF times the cosine
+/// * `medium` - medium the shadow ray starts in towards a direction, `None` for vacuum
+pub(super) fn sample_delta_lights<'a>(
+    scene: &'a Scene,
+    point: &nalgebra_glm::DVec3,
+    response: impl Fn(&nalgebra_glm::DVec3) -> nalgebra_glm::DVec3,
+    medium: impl Fn(&nalgebra_glm::DVec3) -> Option<&'a Arc<dyn Medium>>
+) -> nalgebra_glm::DVec3 {
+    scene.delta_lights()
+        .iter()
+        .filter_map(|light| light.illuminate(point))
+        .filter_map(
+            |sample| {
+                let response = response(&sample.direction);
+                if response.max() <= 0. {
+                    return None;
+                }
+                trace_shadow(scene, *point, &sample.direction, sample.distance, None, medium(&sample.direction))
+                    .map(|(transmittance, _)| response.component_mul(&sample.radiance).component_mul(&transmittance))
+            }
+        )
+        .sum()
+}
+
+/// Response of a surface to light arriving from `direction`, the BSDF times the cosine
+pub(super) fn surface_response(
+    inter: &SceneObjectIntersection,
+    incoming: &nalgebra_glm::DVec3,
+    shading_normal: &nalgebra_glm::DVec3,
+    material: &SceneObjectMaterial,
+    direction: &nalgebra_glm::DVec3
+) -> nalgebra_glm::DVec3 {
+    if inter.is_leaking(direction, shading_normal) {
+        nalgebra_glm::zero()
+    } else {
+        material.eval(inter, incoming, direction) * shading_normal.dot(direction).abs()
+    }
+}
\ No newline at end of file
@@ -1,3 +1,5 @@
+use crate::common::Ray;
+
 use super::{Tracer, TracerCapabilities};
 
 pub struct FlatTracer;
@@ -17,17 +19,26 @@ impl Default for FlatTracer {
 impl Tracer for FlatTracer {
     fn trace(
             &self,
-            ray: crate::common::Ray,
+            ray: Ray,
             scene: &crate::Scene,
             _render_params: &crate::renderer::RenderParams,
             _depth: usize
         ) -> nalgebra_glm::DVec3 {
-        let intersection = scene.find_intersection(&ray);
-        if let Some(int) = intersection {
-            int.object().color() * (8. * int.normal().dot(ray.direction()) * -1.)
-        } else {
-            nalgebra_glm::DVec3::from_element(0.)
-        }
+        let mut ray = ray;
+        let int = loop {
+            let Some(int) = scene.find_intersection(&ray) else {
+                return scene.environment_radiance(ray.direction());
+            };
+            if !int.passes_through(int.object().material().select(&int)) {
+                break int;
+            }
+            // Crosses interfaces and the transparent parts of surfaces
+            ray = Ray::new(int.hit_point(), *ray.direction());
+        };
+        // Shading normal kept on the side of the geometric normal, so back faces stay dark
+        let normal = int.shading_normal(ray.direction());
+        let normal = if normal.dot(&int.normal()) < 0. { -normal } else { normal };
+        int.color() * -(8. * normal.dot(ray.direction()))
     }
 
     fn capabilities() -> TracerCapabilities where Self: Sized {
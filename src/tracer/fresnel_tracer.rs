@@ -1,5 +1,5 @@
 use crate::{
-    scene::{Scene, obj::SceneObjectMaterial},
+    scene::{Scene, obj::SceneObjectMaterial, material::{thin_film, subsurface}},
     common::{Ray, RandomGen},
     sampler::{Sampler},
     renderer::RenderParams,
@@ -7,7 +7,7 @@ use crate::{
     extension::vector_ext::OrthonormalVectorExt
 };
 
-use super::{Tracer, TracerCapabilities};
+use super::{Tracer, TracerCapabilities, direct_light::{sample_delta_lights, surface_response}};
 
 /// Simple tracer with Fresnel equation
 pub struct FresnelTracer(Box<dyn Terminator>, Box<dyn Sampler>);
@@ -22,6 +22,7 @@ impl FresnelTracer {
 }
 
 impl Tracer for FresnelTracer {
+    /// Follows the path in a loop, carrying the throughput of its bounces
     fn trace(
         &self,
         ray: Ray,
@@ -29,91 +30,120 @@ impl Tracer for FresnelTracer {
         render_params: &RenderParams,
         depth: usize
     ) -> nalgebra_glm::DVec3 {
-        let zero = nalgebra_glm::zero();
-        if self.0.terminate(depth) {
-            zero
-        } else {
-            let intersection = scene.find_intersection(&ray);
-            let rr_factor = self.0.factor(depth); 
+        let mut ray = ray;
+        let mut depth = depth;
+        let mut radiance = nalgebra_glm::DVec3::zeros();
+        let mut throughput = nalgebra_glm::DVec3::from_element(1.);
 
-            if let Some(inter) = intersection {
-                // Travel the ray to the hit point where the closest object lies and compute the surface normal there.
-                let hp = inter.hit_point();
-                let normal = inter.normal();
-
-                let emission_color = nalgebra_glm::DVec3::from_element(inter.object().emission()) * rr_factor;
-
-                let (normal, refr) = {
-                    let internal_inter_test = normal.dot(ray.direction());
-                    if internal_inter_test > 0. {
-                        (normal * -1., render_params.refraction_index)
-                    } else {
-                        (normal, 1. / render_params.refraction_index)
-                    }
+        while let Some(rr_factor) = self.0.continue_path(depth, &throughput) {
+            // Crosses interfaces and the transparent parts of surfaces without bouncing, at the same depth
+            let hit = loop {
+                let Some(inter) = scene.find_intersection(&ray) else {
+                    break None;
                 };
+                let material = inter.object().material().select(&inter);
+                if !inter.passes_through(material) {
+                    break Some((inter, material));
+                }
+                ray = Ray::new(inter.hit_point(), *ray.direction());
+            };
+            let Some((inter, material)) = hit else {
+                radiance += scene.environment_radiance(ray.direction()).component_mul(&throughput) * rr_factor;
+                break;
+            };
+            // Travel the ray to the hit point where the closest object lies and compute the surface normal there.
+            let hp = inter.hit_point();
+            radiance += inter.emission(ray.direction()).component_mul(&throughput) * rr_factor;
+            throughput *= rr_factor;
 
-                let material_color = match inter.object().material() {
-                    SceneObjectMaterial::Diffuse => {
-                        let (orth_a, orth_b) = normal.orthonormal();
-                        let hemi_sample = self.1.hemisphere().normalize();
-                        let rotated = nalgebra_glm::DVec3::new(
-                            nalgebra_glm::DVec3::new(orth_a.x, orth_b.x, normal.x).dot(&hemi_sample),
-                            nalgebra_glm::DVec3::new(orth_a.y, orth_b.y, normal.y).dot(&hemi_sample),
-                            nalgebra_glm::DVec3::new(orth_a.z, orth_b.z, normal.z).dot(&hemi_sample),
-                        );
-                        let cost = rotated.dot(&normal);
-                        let bounce = Ray::new(
-                            hp,
-                            rotated
-                        );
-                        let diffuse_color = self.trace(
-                            bounce,
-                            scene,
-                            render_params,
-                            depth + 1
-                        );
-                        ((diffuse_color.component_mul(inter.object().color())) * cost) * 0.1 * rr_factor
-                    },
-                    SceneObjectMaterial::Specular => {
-                        let cost = ray.direction().dot(&normal);
-                        let bounce = Ray::new(
-                            hp,
-                            (ray.direction() - normal * (cost * 2.)).normalize()
-                        );
-                        self.trace(
-                            bounce,
-                            scene,
-                            render_params,
-                            depth + 1
-                        ) * rr_factor
-                    },
-                    SceneObjectMaterial::Refractive => {
-                        let refr_ind = render_params.refraction_index;
-                        let cost1 = normal.dot(ray.direction()) * -1.;
-                        let cost2 = 1.0 - refr.powi(2) * (1. - cost1.powi(2));
-                        let r0 = ((1. - refr_ind) / (1. + refr_ind)).powi(2);
-                        let refr_prob = r0 + (1. - r0) * (1. - cost1).powi(5);
-                        let bounce = Ray::new(
-                            hp,
-                            if cost2 > 0. && RandomGen::rand2() > refr_prob {
-                                (ray.direction() * refr + (normal * (refr * cost1 - cost2.sqrt()))).normalize()
-                            } else {
-                                (ray.direction() + normal * (cost1 * 2.)).normalize()
-                            }
-                        );
-                        self.trace(
-                            bounce,
-                            scene,
-                            render_params,
-                            depth + 1
-                        ) * rr_factor
-                    }
-                };
-                emission_color + material_color
-            } else {
-                zero
+            // The geometric normal tells if the ray is inside the object, shading uses the perturbed normal
+            let refr = {
+                let internal_inter_test = inter.normal().dot(ray.direction());
+                if internal_inter_test > 0. {
+                    render_params.refraction_index
+                } else {
+                    1. / render_params.refraction_index
+                }
+            };
+            let normal = inter.shading_normal(ray.direction());
+
+            if !material.is_delta() {
+                radiance += sample_delta_lights(
+                    scene,
+                    &hp,
+                    |direction| surface_response(&inter, ray.direction(), &normal, material, direction),
+                    |_| None
+                ).component_mul(&throughput);
             }
+
+            let bounce = match material {
+                SceneObjectMaterial::Diffuse => {
+                    let (orth_a, orth_b) = normal.orthonormal();
+                    let hemi_sample = self.1.hemisphere().normalize();
+                    let rotated = nalgebra_glm::DVec3::new(
+                        nalgebra_glm::DVec3::new(orth_a.x, orth_b.x, normal.x).dot(&hemi_sample),
+                        nalgebra_glm::DVec3::new(orth_a.y, orth_b.y, normal.y).dot(&hemi_sample),
+                        nalgebra_glm::DVec3::new(orth_a.z, orth_b.z, normal.z).dot(&hemi_sample),
+                    );
+                    let cost = rotated.dot(&normal);
+                    Some((rotated, inter.color() * (cost * 0.1)))
+                },
+                SceneObjectMaterial::Specular => {
+                    let cost = ray.direction().dot(&normal);
+                    Some(((ray.direction() - normal * (cost * 2.)).normalize(), nalgebra_glm::DVec3::from_element(1.)))
+                },
+                SceneObjectMaterial::Refractive => {
+                    let refr_ind = render_params.refraction_index;
+                    let cost1 = -normal.dot(ray.direction());
+                    let cost2 = 1.0 - refr.powi(2) * (1. - cost1.powi(2));
+                    let r0 = ((1. - refr_ind) / (1. + refr_ind)).powi(2);
+                    let refr_prob = r0 + (1. - r0) * (1. - cost1).powi(5);
+                    let bounce_dir = if cost2 > 0. && RandomGen::rand2() > refr_prob {
+                        (ray.direction() * refr + (normal * (refr * cost1 - cost2.sqrt()))).normalize()
+                    } else {
+                        (ray.direction() + normal * (cost1 * 2.)).normalize()
+                    };
+                    Some((bounce_dir, nalgebra_glm::DVec3::from_element(1.)))
+                },
+                SceneObjectMaterial::ThinFilm { base, thickness, ior } => {
+                    Some(
+                        thin_film::scatter(
+                            base,
+                            *thickness,
+                            *ior,
+                            ray.direction(),
+                            &normal,
+                            inter.normal().dot(ray.direction()) < 0.,
+                            render_params.refraction_index
+                        )
+                    )
+                },
+                SceneObjectMaterial::Anisotropic { .. } | SceneObjectMaterial::Principled(_) => {
+                    material.sample(&inter, ray.direction(), render_params.refraction_index, self.1.as_ref())
+                        .map(|sample| (sample.direction, sample.weight))
+                },
+                SceneObjectMaterial::Subsurface { mean_free_path, albedo } => {
+                    // Leaves from another point of the surface, which can't leak
+                    let Some((bounce, weight)) = subsurface::scatter(&inter, ray.direction(), mean_free_path, albedo, render_params.refraction_index) else {
+                        break;
+                    };
+                    throughput = throughput.component_mul(&weight);
+                    ray = bounce;
+                    depth += 1;
+                    continue;
+                },
+                SceneObjectMaterial::Interface => unreachable!("Interfaces are crossed without bouncing"),
+                SceneObjectMaterial::Mix { .. } => unreachable!("`select` resolves mixed materials")
+            };
+            // Bounces that cross the geometric surface on the other side of the shading normal would leak light
+            let Some((direction, weight)) = bounce.filter(|(direction, _)| !inter.is_leaking(direction, &normal)) else {
+                break;
+            };
+            throughput = throughput.component_mul(&weight);
+            ray = Ray::new(hp, direction);
+            depth += 1;
         }
+        radiance
     }
 
     fn capabilities() -> TracerCapabilities {
@@ -122,4 +152,34 @@ impl Tracer for FresnelTracer {
             fresnel: true
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scene::obj::SceneObject, sampler::RandomSampler, terminator::DepthTerminator};
+
+    #[test]
+    fn deep_paths() {
+        let mut scene = Scene::new_with_vec_storage();
+        // Two glowing mirrors facing each other
+        for (position, normal) in [(0., 1.), (1., -1.)] {
+            scene.insert_object(SceneObject::new_plane(
+                nalgebra_glm::zero(),
+                nalgebra_glm::DVec3::from_element(1.),
+                SceneObjectMaterial::Specular,
+                nalgebra_glm::DVec3::new(0., 0., position),
+                nalgebra_glm::DVec3::new(0., 0., normal)
+            ));
+        }
+        let params = RenderParams { refraction_index: 1.5, samples_per_pixel: 1 };
+        let ray = Ray::new(nalgebra_glm::DVec3::new(0., 0., 0.5), nalgebra_glm::DVec3::new(0., 0., 1.));
+        let emission = FresnelTracer::new(Box::new(DepthTerminator::new(1)), Box::new(RandomSampler::new()))
+            .trace(ray.clone(), &scene, &params, 0);
+        assert!(emission.max() > 0.);
+        // Deeper than the stack of a thread allows with a call per bounce
+        const DEPTH: usize = 200_000;
+        let tracer = FresnelTracer::new(Box::new(DepthTerminator::new(DEPTH)), Box::new(RandomSampler::new()));
+        approx::assert_relative_eq!(tracer.trace(ray, &scene, &params, 0), emission * DEPTH as f64, max_relative = 1e-9);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,254 @@
+use crate::{
+    scene::{Scene, obj::{SceneObjectMaterial, SceneObjectIntersection}, material::subsurface, light::Emitter},
+    common::{Ray, RandomGen},
+    sampler::Sampler,
+    renderer::{RenderParams, Splats},
+    terminator::Terminator,
+    camera::Camera
+};
+
+use super::{Tracer, TracerCapabilities, direct_light::{trace_shadow, shading_normal_correction}};
+
+/// Tracer that follows paths from the lights and connects every vertex to the camera, known as light tracing
+///
+/// For every pixel traced by the `Renderer`, a path leaves a light chosen by `Scene::sample_emitter`, and the light
+/// of each of its non-delta vertices is splatted on the pixel that sees it, if nothing is in the way. Caustics seen
+/// directly by the camera, like the light focused by glass on a floor, converge much faster than with the tracers
+/// that start from the camera. It needs a `Camera` that maps directions back to pixels (`Camera::pixel` and
+/// `Camera::importance`).
+///
+/// Paths can't reach the camera through a delta bounce, so mirrors and glass look black, and lights without area
+/// aren't seen. The rays of the pixels only add what light paths can't reach, the environment and the emissive
+/// objects that can't be sampled, which is all `Tracer::trace` returns. Media are ignored.
+pub struct LightTracer(Box<dyn Terminator>, Box<dyn Sampler>);
+
+impl LightTracer {
+    pub fn new(terminator: Box<dyn Terminator>, sampler: Box<dyn Sampler>) -> Self {
+        Self(
+            terminator,
+            sampler
+        )
+    }
+
+    /// Follows a path from one of the lights, splatting the light of its vertices on the pixels of `camera` at `eye`
+    fn trace_light_path(
+        &self,
+        scene: &Scene,
+        render_params: &RenderParams,
+        camera: &dyn Camera,
+        eye: &nalgebra_glm::DVec3,
+        splats: &Splats
+    ) {
+        let Some((emitter, selection_pdf)) = scene.sample_emitter(RandomGen::rand2()) else {
+            return;
+        };
+        let Some(emission) = emitter.sample_emission(
+            &nalgebra_glm::DVec2::new(RandomGen::rand2(), RandomGen::rand2()),
+            &nalgebra_glm::DVec2::new(RandomGen::rand2(), RandomGen::rand2())
+        ) else {
+            return;
+        };
+        let pdf_position = selection_pdf * emission.pdf_position;
+        if let Emitter::Object(object) = emitter {
+            // The light itself
+            let light = SceneObjectIntersection::new(object, emission.origin, emission.normal, 0.);
+            splat(
+                scene,
+                camera,
+                eye,
+                &emission.origin,
+                |direction| light.sampled_emission(&-direction) * (emission.normal.dot(direction).abs() / pdf_position),
+                splats
+            );
+        }
+
+        let cos = if emission.normal == nalgebra_glm::DVec3::zeros() {
+            1.
+        } else {
+            emission.normal.dot(&emission.direction).abs()
+        };
+        let mut beta = emission.radiance * (cos / (pdf_position * emission.pdf_direction));
+        let mut ray = Ray::new(emission.origin, emission.direction);
+
+        for depth in 0.. {
+            if self.0.terminate(depth) {
+                return;
+            }
+            beta *= self.0.factor(depth);
+            let (inter, material) = loop {
+                let Some(inter) = scene.find_intersection(&ray) else {
+                    return;
+                };
+                let material = inter.object().material().select(&inter);
+                if !inter.passes_through(material) {
+                    break (inter, material);
+                }
+                ray = Ray::new(inter.hit_point(), *ray.direction());
+            };
+            let hp = inter.hit_point();
+            let incoming = *ray.direction();
+            let normal = inter.normal();
+            if let SceneObjectMaterial::Subsurface { mean_free_path, albedo } = material {
+                let Some((bounce, weight)) = subsurface::scatter(&inter, &incoming, mean_free_path, albedo, render_params.refraction_index) else {
+                    return;
+                };
+                beta = beta.component_mul(&weight);
+                ray = bounce;
+                continue;
+            }
+
+            let shading_normal = inter.shading_normal(&incoming);
+            if !material.is_delta() {
+                splat(
+                    scene,
+                    camera,
+                    eye,
+                    &hp,
+                    |direction| {
+                        if inter.is_leaking(direction, &shading_normal) {
+                            return nalgebra_glm::zero();
+                        }
+                        material.eval(&inter, &incoming, direction).component_mul(&beta)
+                            * (shading_normal.dot(direction).abs() * shading_normal_correction(&normal, &shading_normal, &-incoming, direction))
+                    },
+                    splats
+                );
+            }
+
+            match material.sample(&inter, &incoming, render_params.refraction_index, self.1.as_ref()) {
+                Some(sample) if !inter.is_leaking(&sample.direction, &shading_normal) => {
+                    let correction = shading_normal_correction(&normal, &shading_normal, &-incoming, &sample.direction);
+                    beta = beta.component_mul(&sample.weight) * correction;
+                    ray = Ray::new(hp, sample.direction);
+                },
+                _ => return
+            }
+            if beta.max() <= 0. {
+                return;
+            }
+        }
+    }
+}
+
+/// Adds the light leaving `point` towards the camera at `eye` to the pixel that sees it
+///
+/// # Arguments
+/// * `response` - light leaving `point` towards a direction, times the cosine on the surface
+fn splat(
+    scene: &Scene,
+    camera: &dyn Camera,
+    eye: &nalgebra_glm::DVec3,
+    point: &nalgebra_glm::DVec3,
+    response: impl Fn(&nalgebra_glm::DVec3) -> nalgebra_glm::DVec3,
+    splats: &Splats
+) {
+    let offset = eye - point;
+    let distance = offset.magnitude();
+    if distance <= 0. {
+        return;
+    }
+    let direction = offset / distance;
+    let Some((x, y)) = camera.pixel(&-direction) else {
+        return;
+    };
+    let light = response(&direction) * (camera.importance(&-direction) / (distance * distance));
+    if light.max() <= 0. || trace_shadow(scene, *point, &direction, distance, None, None).is_none() {
+        return;
+    }
+    splats.add(x, y, light);
+}
+
+impl Tracer for LightTracer {
+    fn trace(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        _render_params: &RenderParams,
+        _depth: usize
+    ) -> nalgebra_glm::DVec3 {
+        match scene.find_intersection(&ray) {
+            // Lights are seen by the light paths
+            Some(inter) if !inter.object().is_light() => inter.emission(ray.direction()),
+            Some(_) => nalgebra_glm::zero(),
+            None => scene.environment_radiance(ray.direction())
+        }
+    }
+
+    /// Traces a path from the lights, and the light of the ray that light paths can't reach
+    ///
+    /// The `Renderer` traces one light path per pixel, which the importance of the camera is normalized to.
+    fn trace_pixel(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        render_params: &RenderParams,
+        camera: &dyn Camera,
+        _pixel: (usize, usize),
+        splats: &Splats
+    ) -> nalgebra_glm::DVec3 {
+        self.trace_light_path(scene, render_params, camera, ray.origin(), splats);
+        self.trace(ray, scene, render_params, 0)
+    }
+
+    fn capabilities() -> TracerCapabilities {
+        TracerCapabilities {
+            caustics: true,
+            fresnel: true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        renderer::Renderer,
+        camera::SimpleCamera,
+        scene::obj::SceneObject,
+        sampler::RandomSampler,
+        terminator::DepthTerminator,
+        tracer::NextEventTracer
+    };
+
+    #[test]
+    fn matches_next_event_tracer() {
+        let mut scene = Scene::new_with_vec_storage();
+        scene.insert_object(SceneObject::new_plane(
+            nalgebra_glm::DVec3::from_element(1.),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::DVec3::new(0., 0., -3.),
+            nalgebra_glm::DVec3::new(0., 0., 1.)
+        ));
+        // Above the image, lighting the plane
+        scene.insert_object(SceneObject::new_sphere(
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::from_element(100.),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::DVec3::new(0., 3., -2.),
+            0.5
+        ));
+
+        const SIZE: usize = 16;
+        let camera = SimpleCamera::new(SIZE as f64, SIZE as f64);
+        let render = |tracer: &dyn Tracer, passes: u64| {
+            let mut renderer = Renderer::new(SIZE, SIZE, 1.5, passes);
+            renderer.render(tracer, &camera, &scene).unwrap();
+            let (image, passes) = renderer.get_image();
+            image.into_iter().map(|pixel| pixel / passes as f64).collect::<Vec<_>>()
+        };
+        let expected = render(&NextEventTracer::new(Box::new(DepthTerminator::new(2)), Box::new(RandomSampler::new())), 4_096);
+        let light = render(&LightTracer::new(Box::new(DepthTerminator::new(2)), Box::new(RandomSampler::new())), 4_096);
+        // Light lands on the same pixels, compared by quadrant
+        let quadrant = |image: &[nalgebra_glm::DVec3], quadrant: (usize, usize)| {
+            image.iter()
+                .enumerate()
+                .filter(|(i, _)| (i / SIZE * 2 / SIZE, i % SIZE * 2 / SIZE) == quadrant)
+                .map(|(_, pixel)| pixel)
+                .sum::<nalgebra_glm::DVec3>()
+        };
+        for q in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            approx::assert_relative_eq!(quadrant(&light, q), quadrant(&expected, q), max_relative = 0.1);
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,474 @@
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    scene::Scene,
+    common::{Ray, RandomGen, RandomStream, Distribution1D},
+    renderer::{RenderParams, Splats},
+    camera::Camera
+};
+
+use super::{Tracer, TracerCapabilities};
+
+/// Standard deviation of the small steps of a primary sample
+const SMALL_STEP_SIGMA: f64 = 0.01;
+
+/// Number of a stream, with the values it had before the current iteration
+#[derive(Debug, Clone, Copy, Default)]
+struct PrimarySample {
+    value: f64,
+    /// Iteration the value was last changed on
+    last_modification: u64,
+    value_backup: f64,
+    modification_backup: u64
+}
+
+/// Random numbers of a Markov chain, mutated on every iteration, following Kelemen et al.
+///
+/// Numbers are created when they are first used, and changed lazily to catch up with the iterations they were not
+/// used in. Large steps replace every number, and small steps move them slightly.
+#[derive(Debug, Clone)]
+struct PrimarySampleStream {
+    rng: StdRng,
+    samples: Vec<PrimarySample>,
+    /// Next number to be used in the iteration
+    index: usize,
+    iteration: u64,
+    large_step: bool,
+    /// Last iteration with an accepted large step
+    last_large_step: u64,
+    large_step_probability: f64
+}
+
+impl PrimarySampleStream {
+    fn new(seed: u64, large_step_probability: f64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            samples: Vec::new(),
+            index: 0,
+            iteration: 0,
+            // The first numbers are all new
+            large_step: true,
+            last_large_step: 0,
+            large_step_probability
+        }
+    }
+
+    /// Uniform number that isn't part of the stream
+    fn uniform(&mut self) -> f64 {
+        self.rng.gen()
+    }
+
+    fn start_iteration(&mut self) {
+        self.iteration += 1;
+        self.large_step = self.uniform() < self.large_step_probability;
+        self.index = 0;
+    }
+
+    fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step = self.iteration;
+        }
+    }
+
+    /// Restores the numbers changed by the current iteration
+    fn reject(&mut self) {
+        for sample in self.samples.iter_mut().filter(|sample| sample.last_modification == self.iteration) {
+            sample.value = sample.value_backup;
+            sample.last_modification = sample.modification_backup;
+        }
+        self.iteration -= 1;
+    }
+
+    fn ensure_ready(&mut self, index: usize) {
+        if index >= self.samples.len() {
+            self.samples.resize(index + 1, PrimarySample::default());
+        }
+        if self.samples[index].last_modification < self.last_large_step {
+            // Skipped an accepted large step
+            let value = self.uniform();
+            let sample = &mut self.samples[index];
+            sample.value = value;
+            sample.last_modification = self.last_large_step;
+        }
+
+        let sample = self.samples[index];
+        self.samples[index].value_backup = sample.value;
+        self.samples[index].modification_backup = sample.last_modification;
+        let value = if self.large_step {
+            self.uniform()
+        } else {
+            // Catches up with every small step the number missed
+            let steps = (self.iteration - sample.last_modification) as f64;
+            let normal = (-2. * (1. - self.uniform()).ln()).sqrt() * (2. * std::f64::consts::PI * self.uniform()).cos();
+            let value = sample.value + normal * SMALL_STEP_SIGMA * steps.sqrt();
+            value - value.floor()
+        };
+        self.samples[index].value = value;
+        self.samples[index].last_modification = self.iteration;
+    }
+}
+
+impl RandomStream for PrimarySampleStream {
+    fn next_sample(&mut self) -> f64 {
+        self.ensure_ready(self.index);
+        self.index += 1;
+        self.samples[self.index - 1].value
+    }
+}
+
+/// State of a Markov chain
+struct Chain {
+    stream: Option<PrimarySampleStream>,
+    pixel: (usize, usize),
+    radiance: nalgebra_glm::DVec3,
+    importance: f64
+}
+
+/// Markov chains over the image, one for every pixel
+struct Chains {
+    dimensions: (usize, usize),
+    /// Mean importance of the paths, from the bootstrap
+    normalization: f64,
+    chains: Vec<std::sync::Mutex<Chain>>
+}
+
+/// Tracer that mutates the random numbers of another tracer, known as primary sample space Metropolis light transport
+///
+/// The paths of the wrapped tracer are defined by the numbers of `RandomGen`, which are replayed from streams that
+/// are mutated as Markov chains, see `RandomGen::replay`. Chains spend more time on the paths with more light,
+/// and each mutation splats light on the pixel of its path, so difficult paths, like light through a lens into a
+/// small opening, are explored once found.
+///
+/// On the first pass of a render, `bootstrap_count` independent paths estimate the brightness of the whole image,
+/// and pick the starting paths of one chain per pixel. Every pass then mutates each chain `mutations_per_pixel` times, and
+/// the image is only made of splats. The wrapped tracer should not spread its work to other threads while
+/// tracing, and samplers other than the `RandomSampler` and `HaltonSampler` are not replayed.
+/// Tracing a ray without a pixel, with `Tracer::trace`, uses the wrapped tracer.
+pub struct MetropolisTracer {
+    tracer: Box<dyn Tracer>,
+    bootstrap_count: usize,
+    mutations_per_pixel: usize,
+    large_step_probability: f64,
+    chains: std::sync::RwLock<Option<Chains>>
+}
+
+impl MetropolisTracer {
+    /// Creates a Metropolis tracer, with a large step probability of `0.3`
+    ///
+    /// # Arguments
+    /// * `tracer` - tracer whose paths are mutated
+    /// * `bootstrap_count` - number of paths used to normalize the image and start the chains
+    /// * `mutations_per_pixel` - number of mutations of each chain on every pass
+    pub fn new(tracer: Box<dyn Tracer>, bootstrap_count: usize, mutations_per_pixel: usize) -> Self {
+        Self {
+            tracer,
+            bootstrap_count: bootstrap_count.max(1),
+            mutations_per_pixel: mutations_per_pixel.max(1),
+            large_step_probability: 0.3,
+            chains: std::sync::RwLock::new(None)
+        }
+    }
+
+    /// Sets the probability of replacing every number of a path, instead of moving them slightly
+    pub fn with_large_step_probability(mut self, probability: f64) -> Self {
+        self.large_step_probability = probability.clamp(0., 1.);
+        self
+    }
+
+    /// Mean brightness of the paths estimated by the bootstrap, `None` before the first pass
+    pub fn normalization(&self) -> Option<f64> {
+        self.chains.read().ok()?.as_ref().map(|chains| chains.normalization)
+    }
+
+    /// Traces the path defined by the numbers of `stream`
+    ///
+    /// Returns the stream, the pixel of the path, and its light.
+    fn evaluate(
+        &self,
+        stream: PrimarySampleStream,
+        scene: &Scene,
+        camera: &dyn Camera,
+        render_params: &RenderParams,
+        dimensions: (usize, usize)
+    ) -> (PrimarySampleStream, (usize, usize), nalgebra_glm::DVec3) {
+        let ((pixel, radiance), stream) = RandomGen::replay(
+            stream,
+            || {
+                let x = ((RandomGen::rand2() * dimensions.0 as f64) as usize).min(dimensions.0 - 1);
+                let y = ((RandomGen::rand2() * dimensions.1 as f64) as usize).min(dimensions.1 - 1);
+                let ray = Ray::new(
+                    nalgebra_glm::zero(),
+                    camera.view_with_filtering(x as f64, y as f64).normalize()
+                );
+                ((x, y), self.tracer.trace(ray, scene, render_params, 0))
+            }
+        );
+        (stream, pixel, radiance)
+    }
+
+    fn bootstrap(
+        &self,
+        scene: &Scene,
+        camera: &dyn Camera,
+        render_params: &RenderParams,
+        dimensions: (usize, usize)
+    ) -> Chains {
+        let seed = rand::random::<u64>();
+        let importances = (0..self.bootstrap_count).into_par_iter()
+            .map(
+                |i| {
+                    let stream = PrimarySampleStream::new(seed.wrapping_add(i as u64), self.large_step_probability);
+                    importance(&self.evaluate(stream, scene, camera, render_params, dimensions).2)
+                }
+            )
+            .collect::<Vec<_>>();
+        let normalization = importances.iter().sum::<f64>() / self.bootstrap_count as f64;
+        let distribution = Distribution1D::new(&importances);
+
+        let chains = (0..(dimensions.0 * dimensions.1)).into_par_iter()
+            .map(
+                |_| {
+                    // Starts on a path picked by its importance, by replaying its numbers
+                    let (_, _, index) = distribution.sample(RandomGen::rand2());
+                    let stream = PrimarySampleStream::new(seed.wrapping_add(index as u64), self.large_step_probability);
+                    let (stream, pixel, radiance) = self.evaluate(stream, scene, camera, render_params, dimensions);
+                    std::sync::Mutex::new(
+                        Chain {
+                            stream: Some(stream),
+                            pixel,
+                            radiance,
+                            importance: importance(&radiance)
+                        }
+                    )
+                }
+            )
+            .collect();
+        Chains {
+            dimensions,
+            normalization,
+            chains
+        }
+    }
+}
+
+/// Brightness the chains are distributed by
+fn importance(radiance: &nalgebra_glm::DVec3) -> f64 {
+    let luminance = 0.2126 * radiance.x + 0.7152 * radiance.y + 0.0722 * radiance.z;
+    if luminance.is_finite() {
+        luminance.max(0.)
+    } else {
+        0.
+    }
+}
+
+impl Tracer for MetropolisTracer {
+    fn trace(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        render_params: &RenderParams,
+        depth: usize
+    ) -> nalgebra_glm::DVec3 {
+        self.tracer.trace(ray, scene, render_params, depth)
+    }
+
+    /// Starts the chains when a render starts, or when the size of the image changes
+    fn begin_pass(&self, scene: &Scene, camera: &dyn Camera, render_params: &RenderParams, dimensions: (usize, usize), pass: u64) {
+        self.tracer.begin_pass(scene, camera, render_params, dimensions, pass);
+        let started = self.chains.read()
+            .is_ok_and(|chains| chains.as_ref().is_some_and(|chains| chains.dimensions == dimensions));
+        if (pass == 0 || !started) && dimensions.0 > 0 && dimensions.1 > 0 {
+            let chains = self.bootstrap(scene, camera, render_params, dimensions);
+            if let Ok(mut current) = self.chains.write() {
+                *current = Some(chains);
+            }
+        }
+    }
+
+    /// Mutates the chain of the pixel, splatting the light of its paths
+    ///
+    /// The ray of the pixel is not used, the light of the image only comes from the splats.
+    fn trace_pixel(
+        &self,
+        _ray: Ray,
+        scene: &Scene,
+        render_params: &RenderParams,
+        camera: &dyn Camera,
+        pixel: (usize, usize),
+        splats: &Splats
+    ) -> nalgebra_glm::DVec3 {
+        let zero = nalgebra_glm::zero();
+        let Ok(chains) = self.chains.read() else {
+            return zero;
+        };
+        let Some(chains) = chains.as_ref() else {
+            return zero;
+        };
+        let Some(Ok(mut chain)) = chains.chains.get(pixel.0 * chains.dimensions.1 + pixel.1).map(|chain| chain.lock()) else {
+            return zero;
+        };
+        // Each pass mutates every chain the same number of times, adding up to the mean brightness per pixel
+        let scale = chains.normalization / self.mutations_per_pixel as f64;
+
+        for _ in 0..self.mutations_per_pixel {
+            let Some(mut stream) = chain.stream.take() else {
+                break;
+            };
+            stream.start_iteration();
+            let (mut stream, proposed_pixel, radiance) = self.evaluate(stream, scene, camera, render_params, chains.dimensions);
+            let proposed_importance = importance(&radiance);
+            let accept = if chain.importance > 0. {
+                (proposed_importance / chain.importance).min(1.)
+            } else {
+                1.
+            };
+
+            // Both paths add light weighted by the chance of the chain being on them
+            if proposed_importance > 0. {
+                splats.add(proposed_pixel.0, proposed_pixel.1, radiance * (accept * scale / proposed_importance));
+            }
+            if chain.importance > 0. {
+                splats.add(chain.pixel.0, chain.pixel.1, chain.radiance * ((1. - accept) * scale / chain.importance));
+            }
+
+            if stream.uniform() < accept {
+                stream.accept();
+                chain.pixel = proposed_pixel;
+                chain.radiance = radiance;
+                chain.importance = proposed_importance;
+            } else {
+                stream.reject();
+            }
+            chain.stream = Some(stream);
+        }
+        zero
+    }
+
+    fn capabilities() -> TracerCapabilities {
+        TracerCapabilities {
+            caustics: true,
+            fresnel: true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejected_mutations_are_restored() {
+        let mut stream = PrimarySampleStream::new(7, 0.);
+        let first = [stream.next_sample(), stream.next_sample(), stream.next_sample()];
+        stream.accept();
+
+        stream.start_iteration();
+        let mutated = [stream.next_sample(), stream.next_sample()];
+        for (mutated, first) in mutated.iter().zip(first) {
+            assert_ne!(*mutated, first);
+            // Small steps stay close, wrapping around
+            let distance = (mutated - first).abs();
+            assert!(distance.min(1. - distance) < 0.1);
+        }
+        stream.reject();
+        assert_eq!(stream.samples.iter().map(|sample| sample.value).collect::<Vec<_>>(), first);
+
+        // Replaying the same seed gives the same numbers
+        let mut replayed = PrimarySampleStream::new(7, 0.);
+        assert_eq!([replayed.next_sample(), replayed.next_sample(), replayed.next_sample()], first);
+    }
+
+    #[test]
+    fn matches_next_event_tracer() {
+        use crate::{
+            renderer::Renderer,
+            camera::SimpleCamera,
+            scene::obj::{SceneObject, SceneObjectMaterial},
+            sampler::RandomSampler,
+            terminator::DepthTerminator,
+            tracer::NextEventTracer
+        };
+
+        let mut scene = Scene::new_with_vec_storage();
+        scene.insert_object(SceneObject::new_plane(
+            nalgebra_glm::DVec3::from_element(1.),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::DVec3::new(0., 0., -3.),
+            nalgebra_glm::DVec3::new(0., 0., 1.)
+        ));
+        scene.insert_object(SceneObject::new_sphere(
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::from_element(100.),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::DVec3::new(1., 1., -2.),
+            0.5
+        ));
+
+        const SIZE: usize = 4;
+        let camera = SimpleCamera::new(SIZE as f64, SIZE as f64);
+        let mean = |tracer: &dyn Tracer, passes: u64| {
+            let mut renderer = Renderer::new(SIZE, SIZE, 1.5, passes);
+            renderer.render(tracer, &camera, &scene).unwrap();
+            let (image, passes) = renderer.get_image();
+            image.iter().sum::<nalgebra_glm::DVec3>() / (image.len() as u64 * passes) as f64
+        };
+        let next_event = || NextEventTracer::new(Box::new(DepthTerminator::new(2)), Box::new(RandomSampler::new()));
+        let expected = mean(&next_event(), 4_096);
+        let metropolis = MetropolisTracer::new(Box::new(next_event()), 200_000, 64);
+        approx::assert_relative_eq!(mean(&metropolis, 4), expected, max_relative = 0.05);
+        assert!(metropolis.normalization().is_some_and(|normalization| normalization > 0.));
+    }
+
+    #[test]
+    fn starts_over_with_new_render() {
+        use crate::{
+            camera::SimpleCamera,
+            scene::obj::{SceneObject, SceneObjectMaterial},
+            sampler::RandomSampler,
+            terminator::DepthTerminator,
+            tracer::NextEventTracer
+        };
+
+        let scene = |emission: f64| {
+            let mut scene = Scene::new_with_vec_storage();
+            scene.insert_object(SceneObject::new_plane(
+                nalgebra_glm::DVec3::from_element(1.),
+                nalgebra_glm::zero(),
+                SceneObjectMaterial::Diffuse,
+                nalgebra_glm::DVec3::new(0., 0., -3.),
+                nalgebra_glm::DVec3::new(0., 0., 1.)
+            ));
+            scene.insert_object(SceneObject::new_sphere(
+                nalgebra_glm::zero(),
+                nalgebra_glm::DVec3::from_element(emission),
+                SceneObjectMaterial::Diffuse,
+                nalgebra_glm::DVec3::new(1., 1., -2.),
+                0.5
+            ));
+            scene
+        };
+        let (dim, bright) = (scene(100.), scene(400.));
+        let camera = SimpleCamera::new(4., 4.);
+        let params = RenderParams { refraction_index: 1.5, samples_per_pixel: 1 };
+        let tracer = MetropolisTracer::new(
+            Box::new(NextEventTracer::new(Box::new(DepthTerminator::new(2)), Box::new(RandomSampler::new()))),
+            20_000,
+            1
+        );
+
+        tracer.begin_pass(&dim, &camera, &params, (4, 4), 0);
+        let normalization = tracer.normalization().unwrap();
+        // Resuming keeps the chains
+        tracer.begin_pass(&bright, &camera, &params, (4, 4), 1);
+        assert_eq!(tracer.normalization(), Some(normalization));
+        // A new render with the same tracer
+        tracer.begin_pass(&bright, &camera, &params, (4, 4), 0);
+        let brighter = tracer.normalization().unwrap();
+        approx::assert_relative_eq!(brighter, normalization * 4., max_relative = 0.2);
+        // A larger image of the same view
+        tracer.begin_pass(&dim, &SimpleCamera::new(8., 8.), &params, (8, 8), 1);
+        assert!(tracer.normalization().unwrap() < brighter / 2.);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,285 @@
+use crate::{
+    scene::{Scene, obj::SceneObjectMaterial, material::subsurface},
+    common::Ray,
+    sampler::Sampler,
+    renderer::RenderParams,
+    terminator::Terminator
+};
+
+use super::{Tracer, TracerCapabilities, direct_light::{sample_direct_light, sample_environment, sample_delta_lights, surface_response}};
+
+/// Heuristic that weights the samples of each strategy by their densities
+#[derive(Debug, Clone, Copy)]
+pub enum MisHeuristic {
+    /// Proportional to the density of each strategy
+    Balance,
+    /// Proportional to the squared density of each strategy, favors the strategy that is better locally
+    Power
+}
+
+impl MisHeuristic {
+    /// Weight of a sample taken with density `pdf`, when the other strategy could have taken it with density `other_pdf`
+    pub fn weight(&self, pdf: f64, other_pdf: f64) -> f64 {
+        let (a, b) = match self {
+            MisHeuristic::Balance => (pdf, other_pdf),
+            MisHeuristic::Power => (pdf * pdf, other_pdf * other_pdf)
+        };
+        if a + b > 0. {
+            a / (a + b)
+        } else {
+            0.
+        }
+    }
+}
+
+/// Tracer that combines light sampling and BSDF sampling with multiple importance sampling
+///
+/// Each non-delta bounce sends a shadow ray to a point sampled on one of the lights, like the `NextEventTracer`,
+/// and continues the path with a sample of the BSDF. When the bounce hits a light, both contributions are
+/// weighted by the `MisHeuristic` with the density of each strategy, so small lights are found by light sampling
+/// and glossy reflections of large lights by BSDF sampling.
+pub struct MisTracer(Box<dyn Terminator>, Box<dyn Sampler>, MisHeuristic);
+
+impl MisTracer {
+    pub fn new(terminator: Box<dyn Terminator>, sampler: Box<dyn Sampler>, heuristic: MisHeuristic) -> Self {
+        Self(
+            terminator,
+            sampler,
+            heuristic
+        )
+    }
+
+    /// # Arguments
+    /// * `bounce` - origin of the ray and density the BSDF sampled it with, `None` after the camera or delta bounces
+    fn trace_path(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        render_params: &RenderParams,
+        depth: usize,
+        bounce: Option<(nalgebra_glm::DVec3, f64)>
+    ) -> nalgebra_glm::DVec3 {
+        let zero = nalgebra_glm::zero();
+        if self.0.terminate(depth) {
+            return zero;
+        }
+        let rr_factor = self.0.factor(depth);
+        let mut ray = ray;
+        let (inter, material) = loop {
+            let Some(inter) = scene.find_intersection(&ray) else {
+                let Some(environment) = scene.environment() else {
+                    return zero;
+                };
+                let weight = bounce.map_or(1., |(_, bsdf_pdf)| self.2.weight(bsdf_pdf, environment.pdf(ray.direction())));
+                return environment.radiance(ray.direction()) * (weight * rr_factor);
+            };
+            let material = inter.object().material().select(&inter);
+            if !inter.passes_through(material) {
+                break (inter, material);
+            }
+            // Crosses the surface without bouncing, at the same depth
+            ray = Ray::new(inter.hit_point(), *ray.direction());
+        };
+        let hp = inter.hit_point();
+
+        let emission_weight = match bounce {
+            Some((origin, bsdf_pdf)) => {
+                let light_pdf = scene.light_pdf(inter.object(), &origin, &hp, &inter.normal());
+                self.2.weight(bsdf_pdf, light_pdf)
+            },
+            None => 1.
+        };
+        let emission = inter.emission(ray.direction()) * (emission_weight * rr_factor);
+        if let SceneObjectMaterial::Subsurface { mean_free_path, albedo } = material {
+            // Light leaves from another point of the surface, where the lights were not sampled
+            let scattered = match subsurface::scatter(&inter, ray.direction(), mean_free_path, albedo, render_params.refraction_index) {
+                Some((bounce, weight)) => self.trace_path(
+                    bounce,
+                    scene,
+                    render_params,
+                    depth + 1,
+                    None
+                ).component_mul(&weight) * rr_factor,
+                None => zero
+            };
+            return emission + scattered;
+        }
+
+        let shading_normal = inter.shading_normal(ray.direction());
+        let direct = if material.is_delta() {
+            zero
+        } else {
+            sample_direct_light(&inter, ray.direction(), &shading_normal, material, scene)
+                .map_or(
+                    zero,
+                    |light| light.radiance * (self.2.weight(light.light_pdf, light.bsdf_pdf) * rr_factor)
+                )
+                + sample_environment(&inter, ray.direction(), &shading_normal, material, scene)
+                    .map_or(
+                        zero,
+                        |light| light.radiance * (self.2.weight(light.light_pdf, light.bsdf_pdf) * rr_factor)
+                    )
+                // Delta lights can't be hit by bounces, they are only sampled
+                + sample_delta_lights(
+                    scene,
+                    &hp,
+                    |direction| surface_response(&inter, ray.direction(), &shading_normal, material, direction),
+                    |_| None
+                ) * rr_factor
+        };
+        let scattered = match material.sample(&inter, ray.direction(), render_params.refraction_index, self.1.as_ref()) {
+            Some(sample) if !inter.is_leaking(&sample.direction, &shading_normal) => {
+                self.trace_path(
+                    Ray::new(hp, sample.direction),
+                    scene,
+                    render_params,
+                    depth + 1,
+                    (!sample.delta).then_some((hp, sample.pdf))
+                ).component_mul(&sample.weight) * rr_factor
+            },
+            _ => zero
+        };
+        emission + direct + scattered
+    }
+}
+
+impl Tracer for MisTracer {
+    fn trace(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        render_params: &RenderParams,
+        depth: usize
+    ) -> nalgebra_glm::DVec3 {
+        self.trace_path(ray, scene, render_params, depth, None)
+    }
+
+    fn capabilities() -> TracerCapabilities {
+        TracerCapabilities {
+            caustics: true,
+            fresnel: true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heuristics() {
+        for heuristic in [MisHeuristic::Balance, MisHeuristic::Power] {
+            // The weights of both strategies add up to one
+            approx::assert_relative_eq!(heuristic.weight(0.3, 2.) + heuristic.weight(2., 0.3), 1.);
+            approx::assert_relative_eq!(heuristic.weight(1., 0.), 1.);
+            approx::assert_relative_eq!(heuristic.weight(0., 0.), 0.);
+        }
+        approx::assert_relative_eq!(MisHeuristic::Balance.weight(1., 3.), 0.25);
+        approx::assert_relative_eq!(MisHeuristic::Power.weight(1., 3.), 0.1);
+    }
+
+    #[test]
+    fn matches_simple_tracer() {
+        use crate::{
+            renderer::Renderer,
+            camera::SimpleCamera,
+            sampler::RandomSampler,
+            terminator::RussianRouletteTerminator,
+            scene::obj::SceneObject,
+            tracer::SimpleTracer
+        };
+
+        // Closed room with the light outside of the view, so the image only has light found by both strategies
+        let mut scene = Scene::new_with_vec_storage();
+        for (point, normal, color) in [
+            (nalgebra_glm::DVec3::new(0., -2., 0.), nalgebra_glm::DVec3::new(0., 1., 0.), nalgebra_glm::DVec3::new(0.8, 0.8, 0.8)),
+            (nalgebra_glm::DVec3::new(0., 3., 0.), nalgebra_glm::DVec3::new(0., -1., 0.), nalgebra_glm::DVec3::new(0.8, 0.8, 0.8)),
+            (nalgebra_glm::DVec3::new(-3., 0., 0.), nalgebra_glm::DVec3::new(1., 0., 0.), nalgebra_glm::DVec3::new(0.8, 0.2, 0.2)),
+            (nalgebra_glm::DVec3::new(3., 0., 0.), nalgebra_glm::DVec3::new(-1., 0., 0.), nalgebra_glm::DVec3::new(0.2, 0.8, 0.2)),
+            (nalgebra_glm::DVec3::new(0., 0., -8.), nalgebra_glm::DVec3::new(0., 0., 1.), nalgebra_glm::DVec3::new(0.8, 0.8, 0.8)),
+            (nalgebra_glm::DVec3::new(0., 0., 1.), nalgebra_glm::DVec3::new(0., 0., -1.), nalgebra_glm::DVec3::new(0.8, 0.8, 0.8))
+        ] {
+            scene.insert_object(SceneObject::new_plane(color, nalgebra_glm::zero(), SceneObjectMaterial::Diffuse, point, normal));
+        }
+        scene.insert_object(SceneObject::new_sphere(
+            nalgebra_glm::DVec3::from_element(1.),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Specular,
+            nalgebra_glm::DVec3::new(-1.5, -1., -6.),
+            1.
+        ));
+        scene.insert_object(SceneObject::new_sphere(
+            nalgebra_glm::DVec3::from_element(1.),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Refractive,
+            nalgebra_glm::DVec3::new(1.5, -1., -5.),
+            1.
+        ));
+        scene.insert_object(SceneObject::new_sphere(
+            nalgebra_glm::DVec3::from_element(1.),
+            nalgebra_glm::DVec3::new(4.4, 3.3, 2.2),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::DVec3::new(0., 1.7, 0.3),
+            1.2
+        ));
+
+        const SIZE: usize = 8;
+        let camera = SimpleCamera::new(SIZE as f64, SIZE as f64);
+        let mean = |tracer: &dyn Fn(&mut Renderer)| {
+            let mut renderer = Renderer::new(SIZE, SIZE, 1.5, 1024);
+            tracer(&mut renderer);
+            let (image, _) = renderer.get_image();
+            image.iter().sum::<nalgebra_glm::DVec3>() / image.len() as f64
+        };
+        let simple = mean(&|renderer| {
+            let tracer = SimpleTracer::new(Box::new(RussianRouletteTerminator::new(3, 0.5)), Box::new(RandomSampler::new()));
+            renderer.render(&tracer, &camera, &scene).unwrap();
+        });
+        for heuristic in [MisHeuristic::Balance, MisHeuristic::Power] {
+            let mis = mean(&|renderer| {
+                let tracer = MisTracer::new(Box::new(RussianRouletteTerminator::new(3, 0.5)), Box::new(RandomSampler::new()), heuristic);
+                renderer.render(&tracer, &camera, &scene).unwrap();
+            });
+            // The simple tracer only finds the light by chance, so it is still noisy
+            approx::assert_relative_eq!(mis, simple, max_relative = 0.1);
+        }
+    }
+
+    #[test]
+    fn environment_map() {
+        use crate::{
+            scene::{obj::SceneObject, light::EnvironmentLight, texture::{ImageTexture, WrapMode}},
+            sampler::RandomSampler,
+            terminator::DepthTerminator,
+            tracer::NextEventTracer
+        };
+
+        let mut scene = Scene::new_with_vec_storage();
+        scene.insert_object(SceneObject::new_plane(
+            nalgebra_glm::DVec3::from_element(1.),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::new(0., 1., 0.)
+        ));
+        let mut pixels = vec![nalgebra_glm::DVec3::from_element(0.5); 8 * 4];
+        pixels[8 + 3] = nalgebra_glm::DVec3::from_element(50.);
+        scene.set_environment(EnvironmentLight::image(ImageTexture::new(8, 4, pixels, WrapMode::Repeat).unwrap()));
+
+        let params = RenderParams { refraction_index: 1.5, samples_per_pixel: 1 };
+        let ray = Ray::new(nalgebra_glm::DVec3::new(0., 1., 0.), nalgebra_glm::DVec3::new(0., -1., 0.));
+        const SAMPLES: usize = 40_000;
+        let mean = |tracer: &dyn Tracer| {
+            (0..SAMPLES)
+                .map(|_| tracer.trace(ray.clone(), &scene, &params, 0))
+                .sum::<nalgebra_glm::DVec3>() / SAMPLES as f64
+        };
+        // Only samples the environment
+        let sampled = mean(&NextEventTracer::new(Box::new(DepthTerminator::new(1)), Box::new(RandomSampler::new())));
+        for heuristic in [MisHeuristic::Balance, MisHeuristic::Power] {
+            // Also finds the environment with the bounce that leaves the scene
+            let mis = mean(&MisTracer::new(Box::new(DepthTerminator::new(2)), Box::new(RandomSampler::new()), heuristic));
+            approx::assert_relative_eq!(mis, sampled, max_relative = 0.05);
+        }
+    }
+}
\ No newline at end of file
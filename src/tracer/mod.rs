@@ -1,14 +1,49 @@
-use crate::{common::Ray, scene::Scene, renderer::RenderParams};
+use crate::{common::Ray, scene::Scene, renderer::{RenderParams, Splats}, camera::Camera};
+
+mod direct_light;
 
 mod flat_tracer;
 pub use flat_tracer::*;
 
+mod ambient_occlusion_tracer;
+pub use ambient_occlusion_tracer::*;
+
+mod debug_tracer;
+pub use debug_tracer::*;
+
 mod simple_tracer;
 pub use simple_tracer::*;
 
 mod fresnel_tracer;
 pub use fresnel_tracer::*;
 
+mod volumetric_tracer;
+pub use volumetric_tracer::*;
+
+mod next_event_tracer;
+pub use next_event_tracer::*;
+
+mod mis_tracer;
+pub use mis_tracer::*;
+
+mod bidirectional_tracer;
+pub use bidirectional_tracer::*;
+
+mod light_tracer;
+pub use light_tracer::*;
+
+mod photon_map;
+pub use photon_map::*;
+
+mod photon_map_tracer;
+pub use photon_map_tracer::*;
+
+mod progressive_photon_map_tracer;
+pub use progressive_photon_map_tracer::*;
+
+mod metropolis_tracer;
+pub use metropolis_tracer::*;
+
 pub struct TracerCapabilities {
     pub caustics: bool,
     pub fresnel: bool,
@@ -23,5 +58,28 @@ pub trait Tracer: std::marker::Sync {
         depth: usize
     ) -> nalgebra_glm::DVec3;
 
+    /// Prepares the tracer for a pass of the `Renderer` over an image of `dimensions` pixels
+    ///
+    /// Tracers that keep state between pixels, like a photon map, build it here. `pass` is the number of passes
+    /// already added to the image, `0` when a render starts.
+    /// Does nothing by default.
+    fn begin_pass(&self, _scene: &Scene, _camera: &dyn Camera, _render_params: &RenderParams, _dimensions: (usize, usize), _pass: u64) {}
+
+    /// Traces the ray of the pixel at `pixel` seen by `camera`
+    ///
+    /// Tracers that also reach the camera from the lights add that light to `splats`.
+    /// Defaults to `trace`.
+    fn trace_pixel(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        render_params: &RenderParams,
+        _camera: &dyn Camera,
+        _pixel: (usize, usize),
+        _splats: &Splats
+    ) -> nalgebra_glm::DVec3 {
+        self.trace(ray, scene, render_params, 0)
+    }
+
     fn capabilities() -> TracerCapabilities where Self: Sized;
 }
\ No newline at end of file
@@ -0,0 +1,274 @@
+use crate::{
+    scene::{Scene, obj::SceneObjectMaterial, material::subsurface},
+    common::Ray,
+    sampler::Sampler,
+    renderer::RenderParams,
+    terminator::Terminator
+};
+
+use super::{Tracer, TracerCapabilities, direct_light::{sample_direct_light, sample_environment, sample_delta_lights, surface_response}};
+
+/// Tracer that samples the lights of the `Scene` on every bounce, known as next event estimation
+///
+/// Each non-delta bounce sends a shadow ray to a point sampled on one of the lights, see `SceneObject::is_light`,
+/// and another one towards the environment of the `Scene`.
+/// Bounces that hit a light or leave the scene only add its light after delta bounces, since it was already sampled otherwise.
+/// Emissive objects that can't be sampled, like planes and lenses, are still found by the bounces.
+pub struct NextEventTracer(Box<dyn Terminator>, Box<dyn Sampler>);
+
+impl NextEventTracer {
+    pub fn new(terminator: Box<dyn Terminator>, sampler: Box<dyn Sampler>) -> Self {
+        Self(
+            terminator,
+            sampler
+        )
+    }
+
+    fn trace_path(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        render_params: &RenderParams,
+        depth: usize,
+        count_lights: bool
+    ) -> nalgebra_glm::DVec3 {
+        let zero = nalgebra_glm::zero();
+        if self.0.terminate(depth) {
+            return zero;
+        }
+        let rr_factor = self.0.factor(depth);
+        let mut ray = ray;
+        let (inter, material) = loop {
+            let Some(inter) = scene.find_intersection(&ray) else {
+                // The environment was already sampled after non-delta bounces
+                return if count_lights {
+                    scene.environment_radiance(ray.direction()) * rr_factor
+                } else {
+                    zero
+                };
+            };
+            let material = inter.object().material().select(&inter);
+            if !inter.passes_through(material) {
+                break (inter, material);
+            }
+            // Crosses the surface without bouncing, at the same depth
+            ray = Ray::new(inter.hit_point(), *ray.direction());
+        };
+        let hp = inter.hit_point();
+
+        let emission = if count_lights || !inter.object().is_light() {
+            inter.emission(ray.direction()) * rr_factor
+        } else {
+            zero
+        };
+        if let SceneObjectMaterial::Subsurface { mean_free_path, albedo } = material {
+            // Light leaves from another point of the surface, where the lights were not sampled
+            let scattered = match subsurface::scatter(&inter, ray.direction(), mean_free_path, albedo, render_params.refraction_index) {
+                Some((bounce, weight)) => self.trace_path(
+                    bounce,
+                    scene,
+                    render_params,
+                    depth + 1,
+                    true
+                ).component_mul(&weight) * rr_factor,
+                None => zero
+            };
+            return emission + scattered;
+        }
+
+        let shading_normal = inter.shading_normal(ray.direction());
+        let direct = if material.is_delta() {
+            zero
+        } else {
+            sample_direct_light(&inter, ray.direction(), &shading_normal, material, scene)
+                .map_or(zero, |light| light.radiance * rr_factor)
+                + sample_environment(&inter, ray.direction(), &shading_normal, material, scene)
+                    .map_or(zero, |light| light.radiance * rr_factor)
+                + sample_delta_lights(
+                    scene,
+                    &hp,
+                    |direction| surface_response(&inter, ray.direction(), &shading_normal, material, direction),
+                    |_| None
+                ) * rr_factor
+        };
+        let scattered = match material.sample(&inter, ray.direction(), render_params.refraction_index, self.1.as_ref()) {
+            Some(sample) if !inter.is_leaking(&sample.direction, &shading_normal) => {
+                self.trace_path(
+                    Ray::new(hp, sample.direction),
+                    scene,
+                    render_params,
+                    depth + 1,
+                    sample.delta
+                ).component_mul(&sample.weight) * rr_factor
+            },
+            _ => zero
+        };
+        emission + direct + scattered
+    }
+}
+
+impl Tracer for NextEventTracer {
+    fn trace(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        render_params: &RenderParams,
+        depth: usize
+    ) -> nalgebra_glm::DVec3 {
+        self.trace_path(ray, scene, render_params, depth, true)
+    }
+
+    fn capabilities() -> TracerCapabilities {
+        TracerCapabilities {
+            caustics: true,
+            fresnel: true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scene::obj::SceneObject, sampler::RandomSampler, terminator::DepthTerminator};
+
+    #[test]
+    fn direct_light_from_sphere() {
+        let mut scene = Scene::new_with_vec_storage();
+        scene.insert_object(SceneObject::new_plane(
+            nalgebra_glm::DVec3::from_element(1.),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::new(0., 1., 0.)
+        ));
+        scene.insert_object(SceneObject::new_sphere(
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::from_element(100.),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::DVec3::new(0., 4., 0.),
+            1.
+        ));
+        assert_eq!(scene.light_count(), 1);
+
+        let tracer = NextEventTracer::new(Box::new(DepthTerminator::new(4)), Box::new(RandomSampler::new()));
+        let params = RenderParams { refraction_index: 1.5, samples_per_pixel: 1 };
+        let ray = Ray::new(nalgebra_glm::DVec3::new(0., 1., 0.), nalgebra_glm::DVec3::new(0., -1., 0.));
+        const SAMPLES: usize = 2_000;
+        let radiance = (0..SAMPLES)
+            .map(|_| tracer.trace(ray.clone(), &scene, &params, 0))
+            .sum::<nalgebra_glm::DVec3>() / SAMPLES as f64;
+        // Irradiance of a sphere right above the point is `pi * emission * (radius / distance)^2`,
+        // which the diffuse BSDF scales by `DIFFUSE_SCALE / (2 * pi)`
+        let expected = 0.1 / 2. * 100. / 16.;
+        approx::assert_relative_eq!(radiance, nalgebra_glm::DVec3::from_element(expected), max_relative = 0.02);
+    }
+
+    #[test]
+    fn delta_lights_with_shadows() {
+        use crate::scene::light::{PointLight, SpotLight};
+
+        let mut scene = Scene::new_with_vec_storage();
+        scene.insert_object(SceneObject::new_plane(
+            nalgebra_glm::DVec3::from_element(1.),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::new(0., 1., 0.)
+        ));
+        scene.insert_delta_light(Box::new(PointLight::new(nalgebra_glm::DVec3::new(0., 2., 0.), nalgebra_glm::DVec3::from_element(100.))));
+        // Points away from the plane
+        scene.insert_delta_light(Box::new(SpotLight::new(
+            nalgebra_glm::DVec3::new(0., 1., 0.),
+            nalgebra_glm::DVec3::new(0., 1., 0.),
+            nalgebra_glm::DVec3::from_element(100.),
+            0.5,
+            0.1
+        )));
+
+        let tracer = NextEventTracer::new(Box::new(DepthTerminator::new(1)), Box::new(RandomSampler::new()));
+        let params = RenderParams { refraction_index: 1.5, samples_per_pixel: 1 };
+        let ray = Ray::new(nalgebra_glm::DVec3::new(0.5, 1., 0.), nalgebra_glm::DVec3::new(-0.5, -1., 0.).normalize());
+        let lit = tracer.trace(ray.clone(), &scene, &params, 0);
+        // Only the point light reaches the plane, with the diffuse BSDF `DIFFUSE_SCALE / (2 * pi)`
+        let expected = 0.1 / (2. * std::f64::consts::PI) * 100. / 4.;
+        approx::assert_relative_eq!(lit, nalgebra_glm::DVec3::from_element(expected), max_relative = 1e-9);
+
+        scene.insert_object(SceneObject::new_sphere(
+            nalgebra_glm::zero(),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::DVec3::new(0., 1.5, 0.),
+            0.2
+        ));
+        approx::assert_abs_diff_eq!(tracer.trace(ray, &scene, &params, 0), nalgebra_glm::zero());
+    }
+
+    #[test]
+    fn constant_environment() {
+        use crate::scene::light::EnvironmentLight;
+
+        let mut scene = Scene::new_with_vec_storage();
+        scene.insert_object(SceneObject::new_plane(
+            nalgebra_glm::DVec3::from_element(1.),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::new(0., 1., 0.)
+        ));
+        scene.set_environment(EnvironmentLight::constant(nalgebra_glm::DVec3::from_element(1.)).with_intensity(4.));
+
+        let tracer = NextEventTracer::new(Box::new(DepthTerminator::new(1)), Box::new(RandomSampler::new()));
+        let params = RenderParams { refraction_index: 1.5, samples_per_pixel: 1 };
+        // Rays that leave the scene see the environment
+        let up = Ray::new(nalgebra_glm::DVec3::new(0., 1., 0.), nalgebra_glm::DVec3::new(0., 1., 0.));
+        approx::assert_relative_eq!(tracer.trace(up, &scene, &params, 0), nalgebra_glm::DVec3::from_element(4.));
+
+        let ray = Ray::new(nalgebra_glm::DVec3::new(0., 1., 0.), nalgebra_glm::DVec3::new(0., -1., 0.));
+        const SAMPLES: usize = 20_000;
+        let radiance = (0..SAMPLES)
+            .map(|_| tracer.trace(ray.clone(), &scene, &params, 0))
+            .sum::<nalgebra_glm::DVec3>() / SAMPLES as f64;
+        // Irradiance under a constant sky is `pi * radiance`, scaled by the diffuse BSDF `DIFFUSE_SCALE / (2 * pi)`
+        approx::assert_relative_eq!(radiance, nalgebra_glm::DVec3::from_element(0.05 * 4.), max_relative = 0.03);
+    }
+
+    #[test]
+    fn half_transparent_light() {
+        use crate::{scene::light::EmissionProfile, tracer::{MisTracer, MisHeuristic, SimpleTracer}};
+
+        let mut scene = Scene::new_with_vec_storage();
+        scene.insert_object(SceneObject::new_plane(
+            nalgebra_glm::DVec3::from_element(1.),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::new(0., 1., 0.)
+        ));
+        // Only the outside emits, so rays crossing the front of the sphere don't see the back from inside
+        scene.insert_object(
+            SceneObject::new_sphere(
+                nalgebra_glm::zero(),
+                nalgebra_glm::DVec3::from_element(100.),
+                SceneObjectMaterial::Diffuse,
+                nalgebra_glm::DVec3::new(0., 4., 0.),
+                1.
+            )
+                .with_emission_profile(EmissionProfile::OneSided)
+                .with_opacity(0.5)
+        );
+
+        let params = RenderParams { refraction_index: 1.5, samples_per_pixel: 1 };
+        let ray = Ray::new(nalgebra_glm::DVec3::new(0., 1., 0.), nalgebra_glm::DVec3::new(0., -1., 0.));
+        let mean = |tracer: &dyn Tracer, samples: usize| (0..samples)
+            .map(|_| tracer.trace(ray.clone(), &scene, &params, 0))
+            .sum::<nalgebra_glm::DVec3>() / samples as f64;
+        // Half of the light of `direct_light_from_sphere`
+        let expected = nalgebra_glm::DVec3::from_element(0.1 / 2. * 100. / 16. / 2.);
+        let next_event = NextEventTracer::new(Box::new(DepthTerminator::new(2)), Box::new(RandomSampler::new()));
+        approx::assert_relative_eq!(mean(&next_event, 20_000), expected, max_relative = 0.03);
+        let mis = MisTracer::new(Box::new(DepthTerminator::new(2)), Box::new(RandomSampler::new()), MisHeuristic::Power);
+        approx::assert_relative_eq!(mean(&mis, 20_000), expected, max_relative = 0.03);
+        let simple = SimpleTracer::new(Box::new(DepthTerminator::new(2)), Box::new(RandomSampler::new()));
+        approx::assert_relative_eq!(mean(&simple, 400_000), expected, max_relative = 0.05);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,126 @@
+/// Light carried by a photon when it landed on a surface
+#[derive(Debug, Clone)]
+pub struct Photon {
+    pub position: nalgebra_glm::DVec3,
+    /// Direction the photon was travelling
+    pub direction: nalgebra_glm::DVec3,
+    /// Geometric normal of the surface, on the side the photon arrived from
+    pub normal: nalgebra_glm::DVec3,
+    /// Flux carried by the photon
+    pub power: nalgebra_glm::DVec3
+}
+
+/// Photons stored in a balanced kd-tree, to find the photons around a point
+///
+/// The tree is implicit, each range of the photons has its median in the middle, splitting the range along the
+/// axis where it's widest.
+#[derive(Debug, Default)]
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    /// Axis the photon at the same index splits its range along
+    axes: Vec<usize>
+}
+
+impl PhotonMap {
+    pub fn new(mut photons: Vec<Photon>) -> Self {
+        let mut axes = vec![0; photons.len()];
+        build(&mut photons, &mut axes);
+        Self {
+            photons,
+            axes
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.photons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+
+    /// Calls `f` with every photon closer than `radius` to `point`
+    pub fn for_each_within(&self, point: &nalgebra_glm::DVec3, radius: f64, mut f: impl FnMut(&Photon)) {
+        visit(&self.photons, &self.axes, point, radius * radius, &mut f);
+    }
+}
+
+fn build(photons: &mut [Photon], axes: &mut [usize]) {
+    if photons.len() <= 1 {
+        return;
+    }
+    let (min, max) = photons.iter().fold(
+        (nalgebra_glm::DVec3::from_element(f64::INFINITY), nalgebra_glm::DVec3::from_element(f64::NEG_INFINITY)),
+        |(min, max), photon| (nalgebra_glm::min2(&min, &photon.position), nalgebra_glm::max2(&max, &photon.position))
+    );
+    let axis = (max - min).imax();
+    let median = photons.len() / 2;
+    photons.select_nth_unstable_by(median, |a, b| a.position[axis].total_cmp(&b.position[axis]));
+    axes[median] = axis;
+    let (left, right) = photons.split_at_mut(median);
+    let (left_axes, right_axes) = axes.split_at_mut(median);
+    build(left, left_axes);
+    build(&mut right[1..], &mut right_axes[1..]);
+}
+
+fn visit(
+    photons: &[Photon],
+    axes: &[usize],
+    point: &nalgebra_glm::DVec3,
+    radius_squared: f64,
+    f: &mut impl FnMut(&Photon)
+) {
+    if photons.is_empty() {
+        return;
+    }
+    let median = photons.len() / 2;
+    let photon = &photons[median];
+    if (photon.position - point).norm_squared() <= radius_squared {
+        f(photon);
+    }
+    let offset = point[axes[median]] - photon.position[axes[median]];
+    let (near, far) = if offset < 0. {
+        ((&photons[..median], &axes[..median]), (&photons[median + 1..], &axes[median + 1..]))
+    } else {
+        ((&photons[median + 1..], &axes[median + 1..]), (&photons[..median], &axes[..median]))
+    };
+    visit(near.0, near.1, point, radius_squared, f);
+    // The other side can only have photons in range if the splitting plane is
+    if offset * offset <= radius_squared {
+        visit(far.0, far.1, point, radius_squared, f);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::RandomGen;
+
+    #[test]
+    fn finds_photons_in_range() {
+        let photons = (0..2_000)
+            .map(
+                |_| Photon {
+                    position: nalgebra_glm::DVec3::new(RandomGen::rand2(), RandomGen::rand2() * 2., RandomGen::rand2() * 0.5),
+                    direction: nalgebra_glm::DVec3::new(0., -1., 0.),
+                    normal: nalgebra_glm::DVec3::new(0., 1., 0.),
+                    power: nalgebra_glm::DVec3::from_element(1.)
+                }
+            )
+            .collect::<Vec<_>>();
+        let map = PhotonMap::new(photons.clone());
+        assert_eq!(map.len(), photons.len());
+        for point in [nalgebra_glm::DVec3::new(0.5, 1., 0.25), nalgebra_glm::DVec3::new(0., 0., 0.), nalgebra_glm::DVec3::new(0.9, 1.9, 0.1)] {
+            for radius in [0.05, 0.2, 1.] {
+                let mut found = 0;
+                map.for_each_within(&point, radius, |photon| {
+                    assert!((photon.position - point).magnitude() <= radius);
+                    found += 1;
+                });
+                let expected = photons.iter().filter(|photon| (photon.position - point).magnitude() <= radius).count();
+                assert_eq!(found, expected);
+            }
+        }
+        PhotonMap::new(Vec::new()).for_each_within(&nalgebra_glm::zero(), 1., |_| panic!("The map is empty"));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,355 @@
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    scene::{Scene, obj::{SceneObjectMaterial, SceneObjectIntersection}, material::subsurface},
+    common::{Ray, RandomGen},
+    sampler::Sampler,
+    renderer::RenderParams,
+    terminator::Terminator,
+    camera::Camera
+};
+
+use super::{
+    Tracer, TracerCapabilities, Photon, PhotonMap,
+    direct_light::{sample_direct_light, sample_environment, sample_delta_lights, surface_response, shading_normal_correction}
+};
+
+/// Tracer that estimates indirect light from photons shot by the lights, known as photon mapping
+///
+/// Before every pass of the `Renderer`, `photon_count` photons leave the lights of the `Scene`, see
+/// `Scene::sample_emitter`, and are stored in a `PhotonMap` on every non-delta surface after their first bounce.
+/// Camera rays follow delta bounces, and on the first non-delta surface add the direct light, sampled like the
+/// `NextEventTracer`, and the light of the photons closer than `radius`.
+///
+/// Photons resolve caustics from small lights that bounces rarely find, at the cost of blurring indirect light
+/// over `radius`. Without a `Renderer`, `Tracer::begin_pass` has to be called to shoot the photons.
+/// Emissive objects that can't be sampled, like planes and lenses, are seen but don't light the scene.
+/// The environment only adds direct light, and media are ignored.
+pub struct PhotonMapTracer {
+    terminator: Box<dyn Terminator>,
+    sampler: Box<dyn Sampler>,
+    photon_count: usize,
+    radius: f64,
+    photon_map: std::sync::RwLock<PhotonMap>
+}
+
+impl PhotonMapTracer {
+    /// Creates a photon mapping tracer
+    ///
+    /// # Arguments
+    /// * `photon_count` - number of photons shot from the lights on every pass
+    /// * `radius` - distance around a hit in which photons are gathered
+    pub fn new(terminator: Box<dyn Terminator>, sampler: Box<dyn Sampler>, photon_count: usize, radius: f64) -> Self {
+        Self {
+            terminator,
+            sampler,
+            photon_count,
+            radius,
+            photon_map: std::sync::RwLock::new(PhotonMap::default())
+        }
+    }
+
+    pub fn photon_count(&self) -> usize {
+        self.photon_count
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+}
+
+impl Tracer for PhotonMapTracer {
+    fn trace(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        render_params: &RenderParams,
+        depth: usize
+    ) -> nalgebra_glm::DVec3 {
+        let (radiance, visible_point) = trace_visible_point(self.terminator.as_ref(), self.sampler.as_ref(), ray, scene, render_params, depth);
+        let Some(visible_point) = visible_point else {
+            return radiance;
+        };
+        let Ok(photon_map) = self.photon_map.read() else {
+            return radiance;
+        };
+        let (flux, _) = visible_point.gather(&photon_map, self.radius);
+        radiance + flux / (std::f64::consts::PI * self.radius * self.radius)
+    }
+
+    /// Shoots a new set of photons, so passes average over more photons
+    fn begin_pass(&self, scene: &Scene, _camera: &dyn Camera, render_params: &RenderParams, _dimensions: (usize, usize), _pass: u64) {
+        let photon_map = shoot_photons(self.terminator.as_ref(), self.sampler.as_ref(), scene, render_params, self.photon_count);
+        if let Ok(mut map) = self.photon_map.write() {
+            *map = photon_map;
+        }
+    }
+
+    fn capabilities() -> TracerCapabilities {
+        TracerCapabilities {
+            caustics: true,
+            fresnel: true
+        }
+    }
+}
+
+/// Shoots `photon_count` photons from the lights of `scene`
+///
+/// Photons are stored on every non-delta surface after their first bounce, the direct light is sampled instead.
+/// The power of the photons adds up to the power of the lights.
+pub(super) fn shoot_photons(
+    terminator: &dyn Terminator,
+    sampler: &dyn Sampler,
+    scene: &Scene,
+    render_params: &RenderParams,
+    photon_count: usize
+) -> PhotonMap {
+    let photons = (0..photon_count).into_par_iter()
+        .flat_map_iter(
+            |_| {
+                let mut photons = Vec::new();
+                trace_photon(terminator, sampler, scene, render_params, photon_count, &mut photons);
+                photons
+            }
+        )
+        .collect::<Vec<_>>();
+    PhotonMap::new(photons)
+}
+
+/// Follows a photon from one of the lights, adding it to `photons` on every non-delta surface after the first
+fn trace_photon(
+    terminator: &dyn Terminator,
+    sampler: &dyn Sampler,
+    scene: &Scene,
+    render_params: &RenderParams,
+    photon_count: usize,
+    photons: &mut Vec<Photon>
+) {
+    let Some((emitter, selection_pdf)) = scene.sample_emitter(RandomGen::rand2()) else {
+        return;
+    };
+    let Some(emission) = emitter.sample_emission(
+        &nalgebra_glm::DVec2::new(RandomGen::rand2(), RandomGen::rand2()),
+        &nalgebra_glm::DVec2::new(RandomGen::rand2(), RandomGen::rand2())
+    ) else {
+        return;
+    };
+    let cos = if emission.normal == nalgebra_glm::DVec3::zeros() {
+        1.
+    } else {
+        emission.normal.dot(&emission.direction).abs()
+    };
+    let mut power = emission.radiance
+        * (cos / (selection_pdf * emission.pdf_position * emission.pdf_direction * photon_count as f64));
+    let mut ray = Ray::new(emission.origin, emission.direction);
+
+    for depth in 0.. {
+        if terminator.terminate(depth) {
+            return;
+        }
+        power *= terminator.factor(depth);
+        let (inter, material) = loop {
+            let Some(inter) = scene.find_intersection(&ray) else {
+                return;
+            };
+            let material = inter.object().material().select(&inter);
+            if !inter.passes_through(material) {
+                break (inter, material);
+            }
+            ray = Ray::new(inter.hit_point(), *ray.direction());
+        };
+        let hp = inter.hit_point();
+        let incoming = *ray.direction();
+        let normal = inter.normal();
+        if let SceneObjectMaterial::Subsurface { mean_free_path, albedo } = material {
+            let Some((bounce, weight)) = subsurface::scatter(&inter, &incoming, mean_free_path, albedo, render_params.refraction_index) else {
+                return;
+            };
+            power = power.component_mul(&weight);
+            ray = bounce;
+            continue;
+        }
+        if !material.is_delta() && depth > 0 {
+            // The first hit is direct light, which is sampled at the camera hits
+            photons.push(
+                Photon {
+                    position: hp,
+                    direction: incoming,
+                    normal: if normal.dot(&incoming) < 0. { normal } else { -normal },
+                    power
+                }
+            );
+        }
+
+        let shading_normal = inter.shading_normal(&incoming);
+        match material.sample(&inter, &incoming, render_params.refraction_index, sampler) {
+            Some(sample) if !inter.is_leaking(&sample.direction, &shading_normal) => {
+                let correction = shading_normal_correction(&normal, &shading_normal, &-incoming, &sample.direction);
+                power = power.component_mul(&sample.weight) * correction;
+                ray = Ray::new(hp, sample.direction);
+            },
+            _ => return
+        }
+        if power.max() <= 0. {
+            return;
+        }
+    }
+}
+
+/// First non-delta surface hit by a camera ray, where photons are gathered
+pub(super) struct VisiblePoint<'a> {
+    inter: SceneObjectIntersection<'a>,
+    /// Direction of the camera ray that hit the surface
+    incoming: nalgebra_glm::DVec3,
+    material: &'a SceneObjectMaterial,
+    /// Fraction of the light leaving the point that reaches the camera
+    beta: nalgebra_glm::DVec3
+}
+
+impl VisiblePoint<'_> {
+    /// Light of the photons closer than `radius` leaving towards the camera, times the area they were gathered in
+    ///
+    /// Returns the light and the number of photons.
+    pub(super) fn gather(&self, photon_map: &PhotonMap, radius: f64) -> (nalgebra_glm::DVec3, usize) {
+        let normal = if self.inter.normal().dot(&self.incoming) < 0. { self.inter.normal() } else { -self.inter.normal() };
+        let mut flux = nalgebra_glm::DVec3::zeros();
+        let mut count = 0;
+        photon_map.for_each_within(
+            &self.inter.hit_point(),
+            radius,
+            |photon| {
+                // Skips photons on the other side of the surface, or on surfaces around a corner
+                if photon.normal.dot(&normal) > 0.9 {
+                    flux += self.material.eval(&self.inter, &self.incoming, &-photon.direction).component_mul(&photon.power);
+                    count += 1;
+                }
+            }
+        );
+        (flux.component_mul(&self.beta), count)
+    }
+}
+
+/// Follows a camera ray through delta bounces until the first non-delta surface
+///
+/// Returns the light found on the way, with the direct light of the surface sampled like the `NextEventTracer`,
+/// and the surface, if the ray reached one.
+pub(super) fn trace_visible_point<'a>(
+    terminator: &dyn Terminator,
+    sampler: &dyn Sampler,
+    mut ray: Ray,
+    scene: &'a Scene,
+    render_params: &RenderParams,
+    depth: usize
+) -> (nalgebra_glm::DVec3, Option<VisiblePoint<'a>>) {
+    let mut radiance = nalgebra_glm::zero();
+    let mut beta = nalgebra_glm::DVec3::from_element(1.);
+    for depth in depth.. {
+        if terminator.terminate(depth) {
+            break;
+        }
+        beta *= terminator.factor(depth);
+        let (inter, material) = loop {
+            let Some(inter) = scene.find_intersection(&ray) else {
+                return (radiance + scene.environment_radiance(ray.direction()).component_mul(&beta), None);
+            };
+            let material = inter.object().material().select(&inter);
+            if !inter.passes_through(material) {
+                break (inter, material);
+            }
+            // Crosses the surface without bouncing
+            ray = Ray::new(inter.hit_point(), *ray.direction());
+        };
+        let hp = inter.hit_point();
+        let incoming = *ray.direction();
+
+        // Camera rays only reach lights directly or after delta bounces, which the lights aren't sampled for
+        radiance += inter.emission(&incoming).component_mul(&beta);
+        if let SceneObjectMaterial::Subsurface { mean_free_path, albedo } = material {
+            let Some((bounce, weight)) = subsurface::scatter(&inter, &incoming, mean_free_path, albedo, render_params.refraction_index) else {
+                break;
+            };
+            beta = beta.component_mul(&weight);
+            ray = bounce;
+            continue;
+        }
+
+        let shading_normal = inter.shading_normal(&incoming);
+        if !material.is_delta() {
+            // Photons carry the light of the following bounces
+            let zero = nalgebra_glm::zero();
+            let direct = sample_direct_light(&inter, &incoming, &shading_normal, material, scene)
+                .map_or(zero, |light| light.radiance)
+                + sample_environment(&inter, &incoming, &shading_normal, material, scene)
+                    .map_or(zero, |light| light.radiance)
+                + sample_delta_lights(
+                    scene,
+                    &hp,
+                    |direction| surface_response(&inter, &incoming, &shading_normal, material, direction),
+                    |_| None
+                );
+            radiance += direct.component_mul(&beta);
+            return (radiance, Some(VisiblePoint { inter, incoming, material, beta }));
+        }
+        match material.sample(&inter, &incoming, render_params.refraction_index, sampler) {
+            Some(sample) if !inter.is_leaking(&sample.direction, &shading_normal) => {
+                beta = beta.component_mul(&sample.weight);
+                ray = Ray::new(hp, sample.direction);
+            },
+            _ => break
+        }
+    }
+    (radiance, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        scene::{obj::SceneObject, light::SpotLight},
+        sampler::RandomSampler,
+        terminator::RussianRouletteTerminator,
+        camera::SimpleCamera,
+        tracer::MisTracer,
+        tracer::MisHeuristic
+    };
+
+    #[test]
+    fn indirect_light_from_wall() {
+        let mut scene = Scene::new_with_vec_storage();
+        scene.insert_object(SceneObject::new_plane(
+            nalgebra_glm::DVec3::from_element(10.),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::new(0., 1., 0.)
+        ));
+        scene.insert_object(SceneObject::new_plane(
+            nalgebra_glm::DVec3::from_element(10.),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::DVec3::new(1., 0., 0.),
+            nalgebra_glm::DVec3::new(-1., 0., 0.)
+        ));
+        // Lights the wall, but not the point of the floor that is traced
+        scene.insert_delta_light(Box::new(SpotLight::new(
+            nalgebra_glm::DVec3::new(0.5, 1., 0.),
+            nalgebra_glm::DVec3::new(1., 0., 0.),
+            nalgebra_glm::DVec3::from_element(100.),
+            0.5,
+            0.1
+        )));
+
+        let params = RenderParams { refraction_index: 1.5, samples_per_pixel: 1 };
+        let ray = Ray::new(nalgebra_glm::DVec3::new(0., 1., 0.), nalgebra_glm::DVec3::new(0., -1., 0.));
+        const SAMPLES: usize = 100_000;
+        let mis = MisTracer::new(Box::new(RussianRouletteTerminator::new(3, 0.5)), Box::new(RandomSampler::new()), MisHeuristic::Power);
+        let expected = (0..SAMPLES)
+            .map(|_| mis.trace(ray.clone(), &scene, &params, 0))
+            .sum::<nalgebra_glm::DVec3>() / SAMPLES as f64;
+
+        let tracer = PhotonMapTracer::new(Box::new(RussianRouletteTerminator::new(3, 0.5)), Box::new(RandomSampler::new()), 400_000, 0.15);
+        approx::assert_abs_diff_eq!(tracer.trace(ray.clone(), &scene, &params, 0), nalgebra_glm::zero());
+        tracer.begin_pass(&scene, &SimpleCamera::new(1., 1.), &params, (1, 1), 0);
+        approx::assert_relative_eq!(tracer.trace(ray, &scene, &params, 0), expected, max_relative = 0.1);
+    }
+}
\ No newline at end of file
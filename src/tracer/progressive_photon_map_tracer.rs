@@ -0,0 +1,315 @@
+use crate::{
+    scene::Scene,
+    common::Ray,
+    sampler::Sampler,
+    renderer::{RenderParams, Splats},
+    terminator::Terminator,
+    camera::Camera
+};
+
+use super::{Tracer, TracerCapabilities, PhotonMap, photon_map_tracer::{shoot_photons, trace_visible_point}};
+
+/// Photons gathered by a pixel over the passes
+#[derive(Debug, Clone)]
+struct PixelStatistics {
+    radius: f64,
+    /// Number of photons that count towards the estimate
+    photons: f64,
+    /// Light of the gathered photons, scaled to `radius`
+    flux: nalgebra_glm::DVec3,
+    /// Sum of the estimates of the passes added to the image so far
+    accumulated: nalgebra_glm::DVec3
+}
+
+/// Statistics of every pixel of the image
+struct Pixels {
+    dimensions: (usize, usize),
+    statistics: Vec<std::sync::Mutex<PixelStatistics>>
+}
+
+impl Pixels {
+    fn new(dimensions: (usize, usize), radius: f64) -> Self {
+        Self {
+            dimensions,
+            statistics: (0..(dimensions.0 * dimensions.1))
+                .map(
+                    |_| std::sync::Mutex::new(PixelStatistics {
+                        radius,
+                        photons: 0.,
+                        flux: nalgebra_glm::zero(),
+                        accumulated: nalgebra_glm::zero()
+                    })
+                )
+                .collect()
+        }
+    }
+
+    /// Statistics of the pixel at `pixel`, in the same order as the pixels of the `Renderer`
+    fn get(&self, pixel: (usize, usize)) -> Option<&std::sync::Mutex<PixelStatistics>> {
+        if pixel.0 >= self.dimensions.0 || pixel.1 >= self.dimensions.1 {
+            return None;
+        }
+        self.statistics.get(pixel.0 * self.dimensions.1 + pixel.1)
+    }
+}
+
+/// Tracer that shrinks the gathering radius of every pixel over the passes, known as stochastic progressive photon mapping
+///
+/// Works like the `PhotonMapTracer`, but each pixel keeps the photons it gathered and the radius it gathered them
+/// in. After every pass, the radius shrinks so that only a fraction `alpha` of the new photons count, and the image
+/// converges to the correct result as passes are added, instead of staying blurred by the radius.
+///
+/// The statistics of the pixels are kept by the tracer between passes, so the `Renderer` can pause, resume, and
+/// stop at any pass. They start over when a render starts or the size of the image changes. Tracing a ray without
+/// a pixel, with `Tracer::trace`, uses the initial radius like the `PhotonMapTracer`.
+pub struct ProgressivePhotonMapTracer {
+    terminator: Box<dyn Terminator>,
+    sampler: Box<dyn Sampler>,
+    photon_count: usize,
+    radius: f64,
+    alpha: f64,
+    photon_map: std::sync::RwLock<PhotonMap>,
+    pixels: std::sync::RwLock<Option<Pixels>>
+}
+
+impl ProgressivePhotonMapTracer {
+    /// Creates a progressive photon mapping tracer, with `alpha` of `2/3`
+    ///
+    /// # Arguments
+    /// * `photon_count` - number of photons shot from the lights on every pass
+    /// * `radius` - initial distance around a hit in which photons are gathered
+    pub fn new(terminator: Box<dyn Terminator>, sampler: Box<dyn Sampler>, photon_count: usize, radius: f64) -> Self {
+        Self {
+            terminator,
+            sampler,
+            photon_count,
+            radius,
+            alpha: 2. / 3.,
+            photon_map: std::sync::RwLock::new(PhotonMap::default()),
+            pixels: std::sync::RwLock::new(None)
+        }
+    }
+
+    /// Sets the fraction of the new photons that are kept on every pass, in `(0, 1)`
+    ///
+    /// Lower values shrink the radius faster, with less blur and more noise.
+    pub fn with_alpha(mut self, alpha: f64) -> Self {
+        self.alpha = alpha.clamp(f64::EPSILON, 1.);
+        self
+    }
+
+    pub fn photon_count(&self) -> usize {
+        self.photon_count
+    }
+
+    /// Gathering radius of the pixel at `pixel`, the initial radius if it wasn't traced yet
+    pub fn pixel_radius(&self, pixel: (usize, usize)) -> f64 {
+        self.pixels.read()
+            .ok()
+            .and_then(
+                |pixels| pixels.as_ref()?
+                    .get(pixel)?
+                    .lock()
+                    .ok()
+                    .map(|statistics| statistics.radius)
+            )
+            .unwrap_or(self.radius)
+    }
+}
+
+impl Tracer for ProgressivePhotonMapTracer {
+    fn trace(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        render_params: &RenderParams,
+        depth: usize
+    ) -> nalgebra_glm::DVec3 {
+        let (radiance, visible_point) = trace_visible_point(self.terminator.as_ref(), self.sampler.as_ref(), ray, scene, render_params, depth);
+        let Some(visible_point) = visible_point else {
+            return radiance;
+        };
+        let Ok(photon_map) = self.photon_map.read() else {
+            return radiance;
+        };
+        let (flux, _) = visible_point.gather(&photon_map, self.radius);
+        radiance + flux / (std::f64::consts::PI * self.radius * self.radius)
+    }
+
+    /// Shoots a new set of photons for the pixels to gather
+    ///
+    /// The statistics of the pixels start over on the first pass, or when the size of the image changes.
+    fn begin_pass(&self, scene: &Scene, _camera: &dyn Camera, render_params: &RenderParams, dimensions: (usize, usize), pass: u64) {
+        let photon_map = shoot_photons(self.terminator.as_ref(), self.sampler.as_ref(), scene, render_params, self.photon_count);
+        if let Ok(mut map) = self.photon_map.write() {
+            *map = photon_map;
+        }
+        let started = self.pixels.read()
+            .is_ok_and(|pixels| pixels.as_ref().is_some_and(|pixels| pixels.dimensions == dimensions));
+        if pass == 0 || !started {
+            if let Ok(mut pixels) = self.pixels.write() {
+                *pixels = Some(Pixels::new(dimensions, self.radius));
+            }
+        }
+    }
+
+    /// Gathers the photons of the pass with the radius of the pixel, and shrinks it
+    ///
+    /// The `Renderer` averages the passes, so each pass returns the change of the sum of the estimates, which adds up
+    /// to the latest estimate times the number of passes. Pixels outside of the image of the pass are traced like
+    /// with `Tracer::trace`.
+    fn trace_pixel(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        render_params: &RenderParams,
+        _camera: &dyn Camera,
+        pixel: (usize, usize),
+        _splats: &Splats
+    ) -> nalgebra_glm::DVec3 {
+        let Ok(pixels) = self.pixels.read() else {
+            return self.trace(ray, scene, render_params, 0);
+        };
+        let Some(Ok(mut statistics)) = pixels.as_ref().and_then(|pixels| pixels.get(pixel)).map(|statistics| statistics.lock()) else {
+            return self.trace(ray, scene, render_params, 0);
+        };
+        let (radiance, visible_point) = trace_visible_point(self.terminator.as_ref(), self.sampler.as_ref(), ray, scene, render_params, 0);
+        let (flux, count) = match (visible_point, self.photon_map.read()) {
+            (Some(visible_point), Ok(photon_map)) => visible_point.gather(&photon_map, statistics.radius),
+            _ => (nalgebra_glm::zero(), 0)
+        };
+
+        if count > 0 {
+            let photons = statistics.photons + self.alpha * count as f64;
+            let radius = statistics.radius * (photons / (statistics.photons + count as f64)).sqrt();
+            statistics.flux = (statistics.flux + flux) * (radius * radius / (statistics.radius * statistics.radius));
+            statistics.photons = photons;
+            statistics.radius = radius;
+        }
+        let accumulated = statistics.flux / (std::f64::consts::PI * statistics.radius * statistics.radius);
+        let indirect = accumulated - statistics.accumulated;
+        statistics.accumulated = accumulated;
+        radiance + indirect
+    }
+
+    fn capabilities() -> TracerCapabilities {
+        TracerCapabilities {
+            caustics: true,
+            fresnel: true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        scene::{obj::{SceneObject, SceneObjectMaterial}, light::SpotLight},
+        camera::SimpleCamera,
+        sampler::RandomSampler,
+        terminator::RussianRouletteTerminator,
+        tracer::{MisTracer, MisHeuristic}
+    };
+
+    #[test]
+    fn converges_over_passes() {
+        let mut scene = Scene::new_with_vec_storage();
+        scene.insert_object(SceneObject::new_plane(
+            nalgebra_glm::DVec3::from_element(10.),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::new(0., 1., 0.)
+        ));
+        scene.insert_object(SceneObject::new_plane(
+            nalgebra_glm::DVec3::from_element(10.),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::DVec3::new(1., 0., 0.),
+            nalgebra_glm::DVec3::new(-1., 0., 0.)
+        ));
+        // Lights the floor only after bouncing on the wall
+        scene.insert_delta_light(Box::new(SpotLight::new(
+            nalgebra_glm::DVec3::new(0.5, 1., 0.),
+            nalgebra_glm::DVec3::new(1., 0., 0.),
+            nalgebra_glm::DVec3::from_element(100.),
+            0.5,
+            0.1
+        )));
+
+        let params = RenderParams { refraction_index: 1.5, samples_per_pixel: 1 };
+        let ray = Ray::new(nalgebra_glm::DVec3::new(0., 1., 0.), nalgebra_glm::DVec3::new(0., -1., 0.));
+        const SAMPLES: usize = 100_000;
+        let mis = MisTracer::new(Box::new(RussianRouletteTerminator::new(3, 0.5)), Box::new(RandomSampler::new()), MisHeuristic::Power);
+        let expected = (0..SAMPLES)
+            .map(|_| mis.trace(ray.clone(), &scene, &params, 0))
+            .sum::<nalgebra_glm::DVec3>() / SAMPLES as f64;
+
+        // A large radius, that the pixel shrinks
+        let tracer = ProgressivePhotonMapTracer::new(Box::new(RussianRouletteTerminator::new(3, 0.5)), Box::new(RandomSampler::new()), 20_000, 0.5);
+        let camera = SimpleCamera::new(1., 1.);
+        let splats = Splats::new(1, 1);
+        const PASSES: usize = 40;
+        let mut image = nalgebra_glm::DVec3::zeros();
+        for pass in 0..PASSES {
+            tracer.begin_pass(&scene, &camera, &params, (1, 1), pass as u64);
+            image += tracer.trace_pixel(ray.clone(), &scene, &params, &camera, (0, 0), &splats);
+        }
+        assert!(tracer.pixel_radius((0, 0)) < 0.5);
+        assert_eq!(tracer.pixel_radius((1, 0)), 0.5);
+        approx::assert_relative_eq!(image / PASSES as f64, expected, max_relative = 0.1);
+    }
+
+    #[test]
+    fn starts_over_with_new_render() {
+        let mut scene = Scene::new_with_vec_storage();
+        scene.insert_object(SceneObject::new_plane(
+            nalgebra_glm::DVec3::from_element(1.),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::new(0., 1., 0.)
+        ));
+        scene.insert_object(SceneObject::new_plane(
+            nalgebra_glm::DVec3::from_element(1.),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::DVec3::new(1., 0., 0.),
+            nalgebra_glm::DVec3::new(-1., 0., 0.)
+        ));
+        scene.insert_delta_light(Box::new(SpotLight::new(
+            nalgebra_glm::DVec3::new(0.5, 1., 0.),
+            nalgebra_glm::DVec3::new(1., 0., 0.),
+            nalgebra_glm::DVec3::from_element(100.),
+            0.5,
+            0.1
+        )));
+
+        let params = RenderParams { refraction_index: 1.5, samples_per_pixel: 1 };
+        let ray = Ray::new(nalgebra_glm::DVec3::new(0., 1., 0.), nalgebra_glm::DVec3::new(0., -1., 0.));
+        let tracer = ProgressivePhotonMapTracer::new(Box::new(RussianRouletteTerminator::new(3, 0.5)), Box::new(RandomSampler::new()), 1_000, 0.5);
+        let camera = SimpleCamera::new(2., 1.);
+        let splats = Splats::new(2, 1);
+        let render = |dimensions: (usize, usize), passes: std::ops::Range<u64>| {
+            for pass in passes {
+                tracer.begin_pass(&scene, &camera, &params, dimensions, pass);
+                tracer.trace_pixel(ray.clone(), &scene, &params, &camera, (0, 0), &splats);
+            }
+        };
+
+        render((2, 1), 0..5);
+        assert!(tracer.pixel_radius((0, 0)) < 0.5);
+        // Resuming keeps the statistics
+        let radius = tracer.pixel_radius((0, 0));
+        render((2, 1), 5..10);
+        assert!(tracer.pixel_radius((0, 0)) < radius);
+        // A new render with the same tracer
+        tracer.begin_pass(&scene, &camera, &params, (2, 1), 0);
+        assert_eq!(tracer.pixel_radius((0, 0)), 0.5);
+        render((2, 1), 0..5);
+        assert!(tracer.pixel_radius((0, 0)) < 0.5);
+        // A different image
+        tracer.begin_pass(&scene, &camera, &params, (1, 2), 5);
+        assert_eq!(tracer.pixel_radius((0, 0)), 0.5);
+    }
+}
\ No newline at end of file
@@ -1,12 +1,13 @@
 use crate::{
-    scene::{Scene, obj::SceneObjectMaterial},
+    scene::{Scene, obj::SceneObjectMaterial, material::{thin_film, subsurface}},
     common::Ray,
     sampler::{Sampler},
     renderer::RenderParams,
-    terminator::Terminator
+    terminator::Terminator,
+    extension::vector_ext::OrthonormalVectorExt
 };
 
-use super::{Tracer, TracerCapabilities};
+use super::{Tracer, TracerCapabilities, direct_light::{sample_delta_lights, surface_response}};
 
 pub struct SimpleTracer(Box<dyn Terminator>, Box<dyn Sampler>);
 
@@ -21,6 +22,7 @@ impl SimpleTracer {
 }
 
 impl Tracer for SimpleTracer {
+    /// Follows the path in a loop, carrying the throughput of its bounces
     fn trace(
         &self,
         ray: Ray,
@@ -28,82 +30,114 @@ impl Tracer for SimpleTracer {
         render_params: &RenderParams,
         depth: usize
     ) -> nalgebra_glm::DVec3 {
-        let zero = nalgebra_glm::zero();
-        if self.0.terminate(depth) {
-            zero
-        } else {
-            let intersection = scene.find_intersection(&ray);
-            let rr_factor = self.0.factor(depth); 
-
-            if let Some(inter) = intersection {
-                // Travel the ray to the hit point where the closest object lies and compute the surface normal there.
-                let hp = inter.hit_point();
-                let normal = inter.normal();
-
-                let emission_color = nalgebra_glm::DVec3::from_element(inter.object().emission()) * rr_factor;
-
-                let (normal, refr) = {
-                    let internal_inter_test = normal.dot(ray.direction());
-                    if internal_inter_test > 0. {
-                        (normal * -1., render_params.refraction_index)
-                    } else {
-                        (normal, 1. / render_params.refraction_index)
-                    }
-                };
+        let mut ray = ray;
+        let mut depth = depth;
+        let mut radiance = nalgebra_glm::DVec3::zeros();
+        let mut throughput = nalgebra_glm::DVec3::from_element(1.);
 
-                let material_color = match inter.object().material() {
-                    SceneObjectMaterial::Diffuse => {
-                        let bounce_dir = (normal + self.1.hemisphere()).normalize();
-                        let cost = bounce_dir.dot(&normal);
-                        let bounce = Ray::new(
-                            hp,
-                            bounce_dir
-                        );
-                        let diffuse_color = self.trace(
-                            bounce,
-                            scene,
-                            render_params,
-                            depth + 1
-                        );
-                        ((diffuse_color.component_mul(inter.object().color())) * cost) * 0.1 * rr_factor
-                    },
-                    SceneObjectMaterial::Specular => {
-                        let cost = ray.direction().dot(&normal);
-                        let bounce = Ray::new(
-                            hp,
-                            (ray.direction() - normal * (cost * 2.)).normalize()
-                        );
-                        self.trace(
-                            bounce,
-                            scene,
-                            render_params,
-                            depth + 1
-                        ) * rr_factor
-                    },
-                    SceneObjectMaterial::Refractive => {
-                        let cost1 = normal.dot(ray.direction()) * -1.;
-                        let cost2 = 1.0 - refr.powi(2) * (1. - cost1.powi(2));
-                        if cost2 > 0. {
-                            let bounce = Ray::new(
-                                hp,
-                                (ray.direction() * refr + (normal * (refr * cost1 - cost2.sqrt()))).normalize()
-                            );
-                            self.trace(
-                                bounce,
-                                scene,
-                                render_params,
-                                depth + 1
-                            ) * rr_factor
-                        } else {
-                            zero
-                        }
-                    }
+        while let Some(rr_factor) = self.0.continue_path(depth, &throughput) {
+            // Crosses interfaces and the transparent parts of surfaces without bouncing, at the same depth
+            let hit = loop {
+                let Some(inter) = scene.find_intersection(&ray) else {
+                    break None;
                 };
-                emission_color + material_color
-            } else {
-                zero
+                let material = inter.object().material().select(&inter);
+                if !inter.passes_through(material) {
+                    break Some((inter, material));
+                }
+                ray = Ray::new(inter.hit_point(), *ray.direction());
+            };
+            let Some((inter, material)) = hit else {
+                radiance += scene.environment_radiance(ray.direction()).component_mul(&throughput) * rr_factor;
+                break;
+            };
+            // Travel the ray to the hit point where the closest object lies and compute the surface normal there.
+            let hp = inter.hit_point();
+            radiance += inter.emission(ray.direction()).component_mul(&throughput) * rr_factor;
+            throughput *= rr_factor;
+
+            // The geometric normal tells if the ray is inside the object, shading uses the perturbed normal
+            let refr = {
+                let internal_inter_test = inter.normal().dot(ray.direction());
+                if internal_inter_test > 0. {
+                    render_params.refraction_index
+                } else {
+                    1. / render_params.refraction_index
+                }
+            };
+            let normal = inter.shading_normal(ray.direction());
+
+            if !material.is_delta() {
+                radiance += sample_delta_lights(
+                    scene,
+                    &hp,
+                    |direction| surface_response(&inter, ray.direction(), &normal, material, direction),
+                    |_| None
+                ).component_mul(&throughput);
             }
+
+            let bounce = match material {
+                SceneObjectMaterial::Diffuse => {
+                    let (orth_a, orth_b) = normal.orthonormal();
+                    let hemi_sample = self.1.hemisphere().normalize();
+                    let bounce_dir = nalgebra_glm::DVec3::new(
+                        nalgebra_glm::DVec3::new(orth_a.x, orth_b.x, normal.x).dot(&hemi_sample),
+                        nalgebra_glm::DVec3::new(orth_a.y, orth_b.y, normal.y).dot(&hemi_sample),
+                        nalgebra_glm::DVec3::new(orth_a.z, orth_b.z, normal.z).dot(&hemi_sample),
+                    );
+                    let cost = bounce_dir.dot(&normal);
+                    Some((bounce_dir, inter.color() * (cost * 0.1)))
+                },
+                SceneObjectMaterial::Specular => {
+                    let cost = ray.direction().dot(&normal);
+                    Some(((ray.direction() - normal * (cost * 2.)).normalize(), nalgebra_glm::DVec3::from_element(1.)))
+                },
+                SceneObjectMaterial::Refractive => {
+                    let cost1 = -normal.dot(ray.direction());
+                    let cost2 = 1.0 - refr.powi(2) * (1. - cost1.powi(2));
+                    (cost2 > 0.).then(
+                        || ((ray.direction() * refr + (normal * (refr * cost1 - cost2.sqrt()))).normalize(), nalgebra_glm::DVec3::from_element(1.))
+                    )
+                },
+                SceneObjectMaterial::ThinFilm { base, thickness, ior } => {
+                    Some(
+                        thin_film::scatter(
+                            base,
+                            *thickness,
+                            *ior,
+                            ray.direction(),
+                            &normal,
+                            inter.normal().dot(ray.direction()) < 0.,
+                            render_params.refraction_index
+                        )
+                    )
+                },
+                SceneObjectMaterial::Anisotropic { .. } | SceneObjectMaterial::Principled(_) => {
+                    material.sample(&inter, ray.direction(), render_params.refraction_index, self.1.as_ref())
+                        .map(|sample| (sample.direction, sample.weight))
+                },
+                SceneObjectMaterial::Subsurface { mean_free_path, albedo } => {
+                    // Leaves from another point of the surface, which can't leak
+                    let Some((bounce, weight)) = subsurface::scatter(&inter, ray.direction(), mean_free_path, albedo, render_params.refraction_index) else {
+                        break;
+                    };
+                    throughput = throughput.component_mul(&weight);
+                    ray = bounce;
+                    depth += 1;
+                    continue;
+                },
+                SceneObjectMaterial::Interface => unreachable!("Interfaces are crossed without bouncing"),
+                SceneObjectMaterial::Mix { .. } => unreachable!("`select` resolves mixed materials")
+            };
+            // Bounces that cross the geometric surface on the other side of the shading normal would leak light
+            let Some((direction, weight)) = bounce.filter(|(direction, _)| !inter.is_leaking(direction, &normal)) else {
+                break;
+            };
+            throughput = throughput.component_mul(&weight);
+            ray = Ray::new(hp, direction);
+            depth += 1;
         }
+        radiance
     }
 
     fn capabilities() -> TracerCapabilities {
@@ -112,4 +146,142 @@ impl Tracer for SimpleTracer {
             fresnel: false
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        scene::{obj::SceneObject, light::EnvironmentLight, texture::ConstantTexture},
+        sampler::RandomSampler,
+        terminator::{DepthTerminator, RussianRouletteTerminator}
+    };
+
+    #[test]
+    fn deep_paths() {
+        let mut scene = Scene::new_with_vec_storage();
+        // Two glowing mirrors facing each other
+        for (position, normal) in [(0., 1.), (1., -1.)] {
+            scene.insert_object(SceneObject::new_plane(
+                nalgebra_glm::zero(),
+                nalgebra_glm::DVec3::from_element(1.),
+                SceneObjectMaterial::Specular,
+                nalgebra_glm::DVec3::new(0., 0., position),
+                nalgebra_glm::DVec3::new(0., 0., normal)
+            ));
+        }
+        let params = RenderParams { refraction_index: 1.5, samples_per_pixel: 1 };
+        let ray = Ray::new(nalgebra_glm::DVec3::new(0., 0., 0.5), nalgebra_glm::DVec3::new(0., 0., 1.));
+        let emission = SimpleTracer::new(Box::new(DepthTerminator::new(1)), Box::new(RandomSampler::new()))
+            .trace(ray.clone(), &scene, &params, 0);
+        assert!(emission.max() > 0.);
+        // Deeper than the stack of a thread allows with a call per bounce
+        const DEPTH: usize = 200_000;
+        let tracer = SimpleTracer::new(Box::new(DepthTerminator::new(DEPTH)), Box::new(RandomSampler::new()));
+        approx::assert_relative_eq!(tracer.trace(ray, &scene, &params, 0), emission * DEPTH as f64, max_relative = 1e-9);
+    }
+
+    #[test]
+    fn throughput_russian_roulette() {
+        let mut scene = Scene::new_with_vec_storage();
+        scene.insert_object(SceneObject::new_plane(
+            nalgebra_glm::DVec3::from_element(1.),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::new(0., 1., 0.)
+        ));
+        scene.set_environment(EnvironmentLight::constant(nalgebra_glm::DVec3::from_element(1.)));
+        let params = RenderParams { refraction_index: 1.5, samples_per_pixel: 1 };
+        let ray = Ray::new(nalgebra_glm::DVec3::new(0., 1., 0.), nalgebra_glm::DVec3::new(0., -1., 0.));
+        const SAMPLES: usize = 100_000;
+        let mean = |tracer: SimpleTracer| (0..SAMPLES)
+            .map(|_| tracer.trace(ray.clone(), &scene, &params, 0))
+            .sum::<nalgebra_glm::DVec3>() / SAMPLES as f64;
+
+        let expected = mean(SimpleTracer::new(Box::new(DepthTerminator::new(2)), Box::new(RandomSampler::new())));
+        // Paths are dim after bouncing on the floor, and are stopped most of the time
+        let roulette = mean(SimpleTracer::new(Box::new(RussianRouletteTerminator::new(1, 0.5).with_throughput()), Box::new(RandomSampler::new())));
+        approx::assert_relative_eq!(roulette, expected, max_relative = 0.05);
+    }
+
+    #[test]
+    fn half_opacity() {
+        let scene_with = |object: SceneObject| {
+            let mut scene = Scene::new_with_vec_storage();
+            scene.insert_object(object);
+            scene
+        };
+        let plane = || SceneObject::new_plane(
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::from_element(1.),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::new(0., 1., 0.)
+        );
+        let params = RenderParams { refraction_index: 1.5, samples_per_pixel: 1 };
+        let ray = Ray::new(nalgebra_glm::DVec3::new(0., 1., 0.), nalgebra_glm::DVec3::new(0., -1., 0.));
+        let tracer = SimpleTracer::new(Box::new(DepthTerminator::new(1)), Box::new(RandomSampler::new()));
+        const SAMPLES: usize = 100_000;
+        let mean = |scene: &Scene| (0..SAMPLES)
+            .map(|_| tracer.trace(ray.clone(), scene, &params, 0))
+            .sum::<nalgebra_glm::DVec3>() / SAMPLES as f64;
+
+        let opaque = mean(&scene_with(plane()));
+        approx::assert_relative_eq!(opaque, nalgebra_glm::DVec3::from_element(1.));
+        // Half of the rays cross the plane and miss everything
+        let constant = mean(&scene_with(plane().with_opacity(0.5)));
+        approx::assert_relative_eq!(constant, opaque / 2., max_relative = 0.02);
+        let textured = mean(&scene_with(plane().with_opacity_texture(Arc::new(ConstantTexture(nalgebra_glm::DVec3::from_element(0.5))))));
+        approx::assert_relative_eq!(textured, opaque / 2., max_relative = 0.02);
+    }
+
+    #[test]
+    fn roulette_through_crossed_surfaces() {
+        use crate::tracer::{FresnelTracer, VolumetricTracer, NextEventTracer, MisTracer, MisHeuristic};
+
+        let mut scene = Scene::new_with_vec_storage();
+        scene.insert_object(SceneObject::new_plane(
+            nalgebra_glm::zero(),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Interface,
+            nalgebra_glm::DVec3::new(0., 0.75, 0.),
+            nalgebra_glm::DVec3::new(0., 1., 0.)
+        ));
+        // Black, half of the rays cross it
+        scene.insert_object(SceneObject::new_plane(
+            nalgebra_glm::zero(),
+            nalgebra_glm::zero(),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::DVec3::new(0., 0.5, 0.),
+            nalgebra_glm::DVec3::new(0., 1., 0.)
+        ).with_opacity(0.5));
+        scene.insert_object(SceneObject::new_plane(
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::from_element(1.),
+            SceneObjectMaterial::Diffuse,
+            nalgebra_glm::zero(),
+            nalgebra_glm::DVec3::new(0., 1., 0.)
+        ));
+        let params = RenderParams { refraction_index: 1.5, samples_per_pixel: 1 };
+        let ray = Ray::new(nalgebra_glm::DVec3::new(0., 1., 0.), nalgebra_glm::DVec3::new(0., -1., 0.));
+        const SAMPLES: usize = 40_000;
+        let mean = |tracer: &dyn Tracer| (0..SAMPLES)
+            .map(|_| tracer.trace(ray.clone(), &scene, &params, 0))
+            .sum::<nalgebra_glm::DVec3>() / SAMPLES as f64;
+        // Crossing the interface and the transparent plane doesn't roll the roulette again
+        let terminator = || Box::new(RussianRouletteTerminator::new(0, 0.5));
+        let tracers: [Box<dyn Tracer>; 5] = [
+            Box::new(SimpleTracer::new(terminator(), Box::new(RandomSampler::new()))),
+            Box::new(FresnelTracer::new(terminator(), Box::new(RandomSampler::new()))),
+            Box::new(VolumetricTracer::new(terminator(), Box::new(RandomSampler::new()))),
+            Box::new(NextEventTracer::new(terminator(), Box::new(RandomSampler::new()))),
+            Box::new(MisTracer::new(terminator(), Box::new(RandomSampler::new()), MisHeuristic::Power))
+        ];
+        for tracer in tracers {
+            approx::assert_relative_eq!(mean(tracer.as_ref()), nalgebra_glm::DVec3::from_element(0.5), max_relative = 0.05);
+        }
+    }
 }
\ No newline at end of file
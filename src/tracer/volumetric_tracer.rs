@@ -0,0 +1,161 @@
+use std::sync::Arc;
+
+use crate::{
+    scene::{Scene, obj::SceneObjectMaterial, material::subsurface, medium::{Medium, MediumEvent}},
+    common::{Ray, RandomGen},
+    sampler::Sampler,
+    renderer::RenderParams,
+    terminator::Terminator
+};
+
+use super::{Tracer, TracerCapabilities, direct_light::{sample_delta_lights, surface_response}};
+
+/// Tracer with participating media, samples free-flight distances through the medium the ray is in
+///
+/// Rays start in the medium of the `Scene` and switch to the interior medium of an object when they
+/// cross its surface, and back to the medium of the `Scene` when they leave it. Media can't be nested.
+pub struct VolumetricTracer(Box<dyn Terminator>, Box<dyn Sampler>);
+
+impl VolumetricTracer {
+    pub fn new(terminator: Box<dyn Terminator>, sampler: Box<dyn Sampler>) -> Self {
+        Self(
+            terminator,
+            sampler
+        )
+    }
+
+    fn trace_in_medium<'a>(
+        &self,
+        ray: Ray,
+        scene: &'a Scene,
+        render_params: &RenderParams,
+        depth: usize,
+        medium: Option<&'a Arc<dyn Medium>>
+    ) -> nalgebra_glm::DVec3 {
+        let zero = nalgebra_glm::zero();
+        if self.0.terminate(depth) {
+            return zero;
+        }
+        let rr_factor = self.0.factor(depth);
+        let mut ray = ray;
+        let mut medium = medium;
+        let mut transmittance = nalgebra_glm::DVec3::from_element(1.);
+        // Crosses interfaces, like the boundaries of media, and transparent surfaces at the same depth
+        let (inter, material) = loop {
+            let intersection = scene.find_intersection(&ray);
+            let max_distance = intersection.as_ref().map_or(f64::INFINITY, |inter| inter.ray_length());
+
+            if let Some(medium) = medium {
+                match medium.sample(&ray, max_distance) {
+                    MediumEvent::Scatter { distance, weight } => {
+                        let point = ray.origin() + ray.direction() * distance;
+                        let delta_lighting = sample_delta_lights(
+                            scene,
+                            &point,
+                            |direction| nalgebra_glm::DVec3::from_element(medium.phase().eval(ray.direction().dot(direction))),
+                            |_| Some(medium)
+                        );
+                        let direction = medium.phase().sample(ray.direction(), RandomGen::rand2(), RandomGen::rand2());
+                        let scattered = self.trace_in_medium(
+                            Ray::new(point, direction),
+                            scene,
+                            render_params,
+                            depth + 1,
+                            Some(medium)
+                        );
+                        return (delta_lighting + scattered).component_mul(&weight).component_mul(&transmittance) * rr_factor;
+                    },
+                    MediumEvent::Pass { weight } => transmittance = transmittance.component_mul(&weight),
+                    MediumEvent::Absorb => return zero
+                }
+            }
+
+            let Some(inter) = intersection else {
+                return scene.environment_radiance(ray.direction()).component_mul(&transmittance) * rr_factor;
+            };
+            let material = inter.object().material().select(&inter);
+            if !inter.passes_through(material) {
+                break (inter, material);
+            }
+            // Going straight through the surface enters the medium of the object or goes back to the medium of the scene
+            medium = if ray.direction().dot(&inter.normal()) < 0. {
+                inter.object().interior_medium()
+            } else {
+                scene.medium()
+            };
+            ray = Ray::new(inter.hit_point(), *ray.direction());
+        };
+        let hp = inter.hit_point();
+        let normal = inter.normal();
+        // Going through the surface enters the medium of the object or goes back to the medium of the scene
+        let medium_after = |direction: &nalgebra_glm::DVec3| {
+            if direction.dot(&normal) * ray.direction().dot(&normal) <= 0. {
+                medium
+            } else if direction.dot(&normal) < 0. {
+                inter.object().interior_medium()
+            } else {
+                scene.medium()
+            }
+        };
+
+        let emission = inter.emission(ray.direction()) * rr_factor;
+        if let SceneObjectMaterial::Subsurface { mean_free_path, albedo } = material {
+            // Light leaves the object on the same side it came from
+            let scattered = match subsurface::scatter(&inter, ray.direction(), mean_free_path, albedo, render_params.refraction_index) {
+                Some((bounce, weight)) => self.trace_in_medium(
+                    bounce,
+                    scene,
+                    render_params,
+                    depth + 1,
+                    medium
+                ).component_mul(&weight) * rr_factor,
+                None => zero
+            };
+            return (emission + scattered).component_mul(&transmittance);
+        }
+
+        let shading_normal = inter.shading_normal(ray.direction());
+        let delta_lighting = if material.is_delta() {
+            zero
+        } else {
+            sample_delta_lights(
+                scene,
+                &hp,
+                |direction| surface_response(&inter, ray.direction(), &shading_normal, material, direction),
+                medium_after
+            ) * rr_factor
+        };
+        let scattered = match material.sample(&inter, ray.direction(), render_params.refraction_index, self.1.as_ref()) {
+            Some(sample) if !inter.is_leaking(&sample.direction, &shading_normal) => {
+                self.trace_in_medium(
+                    Ray::new(hp, sample.direction),
+                    scene,
+                    render_params,
+                    depth + 1,
+                    medium_after(&sample.direction)
+                ).component_mul(&sample.weight) * rr_factor
+            },
+            _ => zero
+        };
+        (emission + delta_lighting + scattered).component_mul(&transmittance)
+    }
+}
+
+impl Tracer for VolumetricTracer {
+    fn trace(
+        &self,
+        ray: Ray,
+        scene: &Scene,
+        render_params: &RenderParams,
+        depth: usize
+    ) -> nalgebra_glm::DVec3 {
+        self.trace_in_medium(ray, scene, render_params, depth, scene.medium())
+    }
+
+    fn capabilities() -> TracerCapabilities {
+        TracerCapabilities {
+            caustics: true,
+            fresnel: true
+        }
+    }
+}
\ No newline at end of file